@@ -18,16 +18,51 @@ use core::event_request::{CreateRequest, RequestPayload, StateRequest};
 use core::{DatabaseSettings, NetworkSettings, NodeSettings, Taple, SignatureRequest, ExternalEventRequestBody};
 use log::{debug, info};
 use rest::bodys::{
-    CreateRequestBody, EventRequestTypeBody, PostEventBody, PostEventRequestBody, PutVoteBody,
-    StateRequestBody,
+    BatchVote, CreateRequestBody, EventRequestTypeBody, PostEventBody, PostEventRequestBody,
+    PostSimulatedBatchBody, PostSubjectBody, PutVoteBody, PutVotesBody, StateRequestBody, VoteKind,
 };
 use rest::handlers::{
     __path_get_all_governances_handler, __path_get_all_subjects_handler, __path_get_event_handler,
     __path_get_event_properties_handler, __path_get_events_of_subject_handler,
-    __path_get_governance_handler, __path_get_pending_requests_handler,
-    __path_get_single_request_handler, __path_get_subject_handler,
-    __path_post_event_request_handler,
-    __path_put_approval_handler,
+    __path_get_events_stream_handler, __path_get_governance_handler,
+    __path_get_pending_requests_handler, __path_get_single_request_handler,
+    __path_get_subject_handler, __path_get_request_timeline_handler,
+    __path_post_event_request_handler, __path_patch_governance_handler,
+    __path_post_governance_impact_handler, __path_put_approval_handler,
+    __path_get_resolve_alias_handler, __path_get_approver_stats_handler,
+    __path_get_governance_version_handler, __path_post_validate_request_handler,
+    __path_get_schema_history_handler, __path_get_routes_handler,
+    __path_get_governance_activity_handler, __path_post_maintenance_handler,
+    __path_get_flat_properties_handler, __path_get_compare_subjects_handler,
+    __path_get_approval_progress_handler, __path_get_rejected_requests_handler,
+    __path_get_owned_subjects_handler, __path_get_namespaces_handler,
+    __path_get_request_signatures_handler, __path_get_subject_snapshot_handler,
+    __path_get_schema_ui_handler, __path_post_simulated_batch_handler,
+    __path_post_validate_subject_handler, __path_get_last_updated_handler,
+    __path_get_schema_bundle_handler, __path_get_snapshot_handler,
+    __path_get_request_payload_handler, __path_get_state_as_of_handler,
+    __path_get_member_of_handler, __path_get_diagnostics_handler,
+    __path_get_verify_quorum_handler, __path_get_pending_count_handler,
+    __path_get_transitions_handler, __path_get_key_encodings_handler,
+    __path_get_governance_versions_handler, __path_put_approvals_handler,
+    __path_get_search_subjects_handler, __path_get_signatures_handler,
+};
+use rest::body_log::BodyLog;
+use rest::catalog::RouteInfo;
+use rest::error::{FieldError, Problem};
+use rest::identity::NodeIdentity;
+use rest::settings::RestSettings;
+use rest::snapshot::SubjectSnapshot;
+use rest::startup::{wait_initialization, Startup};
+use rest::querys::{EventsFormat, GovernanceOrder, SearchOperator, SortOrder};
+use rest::bodys::PostMaintenanceBody;
+use rest::responses::{
+    AliasKind, ApprovalProgress, ApprovalSignature, ApproverStats, BatchVoteResult, BundledSchema,
+    CompactEvent, Diagnostic, GovernanceRevision, GovernanceVersion, ImpactedSubject, KeyEncodings,
+    LastUpdated, MaintenanceStatus, Membership, NamespaceCount, PendingCount, QuorumCheck,
+    RejectedRequest, RequestResolution, RequestSignatures, RequestSummary, RequestTimeline,
+    ResolvedAlias, SchemaPeriod, SchemaUi, SimulatedBatch, StateAsOf, SubjectComparison,
+    SubjectState, TimelineEntry, Transition, UiField, ValidationCheck, ValidationReport,
 };
 use serde::Deserialize;
 use std::collections::HashMap;
@@ -96,6 +131,9 @@ struct Args {
     /// Flag to activate swagger-ui
     #[arg(long("ui"))]
     swaggerui: bool,
+    /// List of schemas of the subjects that can be created. Each element is separated by ';'
+    #[arg(long("schemas"))]
+    schemaallowlist: Option<Vec<String>>,
 }
 
 impl Source for Args {
@@ -115,6 +153,12 @@ impl Source for Args {
         if self.swaggerui {
             map.insert("swaggerui".into(), self.swaggerui.into());
         }
+        if self.schemaallowlist.is_some() {
+            map.insert(
+                "rest.schemaallowlist".into(),
+                self.schemaallowlist.clone().unwrap().into(),
+            );
+        }
         if self.p2pport.is_some() {
             map.insert(
                 "network.p2pport".into(),
@@ -236,13 +280,21 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let settings = load_settings_from_file(args)?;
     let api_key = settings.x_api_key.clone();
     let swaggerui = settings.swagger_ui.clone();
+    let rest_settings = settings.rest.clone();
+    let max_timeout = rest_settings.max_timeout();
+    let read_timeout = rest_settings.read_timeout();
+    let body_log = rest_settings
+        .log_bodies
+        .then(|| BodyLog::new(rest_settings.log_bodies_limit()));
+    let startup_timeout = rest_settings.startup_timeout();
     if dev_mode {
         info!("DEV MODE is enabled. This is not a proper mode for production apps");
     }
     info!("{:?}", settings);
     let mut taple = Taple::new(settings.get_taple_settings());
-    taple.start().await?;
-    info!("Controller ID: {}", taple.controller_id().unwrap());
+    // The server is up while the node starts, answering that it is initializing
+    let startup = Startup::new();
+    let identity = NodeIdentity::default();
     let http_addr = format!("{}:{}", settings.http_addr, settings.http_port)
         .parse::<SocketAddr>()
         .unwrap();
@@ -253,10 +305,24 @@ async fn main() -> Result<(), Box<dyn Error>> {
         paths(get_single_request_handler, post_event_request_handler, get_subject_handler, 
             get_all_subjects_handler, get_events_of_subject_handler, get_event_handler, 
             get_event_properties_handler, get_pending_requests_handler,
-            put_approval_handler, get_all_governances_handler, get_governance_handler
+            put_approval_handler, get_all_governances_handler, get_governance_handler,
+            get_request_timeline_handler, post_governance_impact_handler,
+            get_events_stream_handler, patch_governance_handler, get_resolve_alias_handler,
+            get_approver_stats_handler, get_governance_version_handler, get_last_updated_handler,
+            post_validate_request_handler, get_schema_history_handler, get_routes_handler,
+            get_governance_activity_handler, post_maintenance_handler,
+            get_flat_properties_handler, get_compare_subjects_handler,
+            get_approval_progress_handler, get_rejected_requests_handler,
+            get_owned_subjects_handler, get_namespaces_handler, get_request_signatures_handler,
+            get_subject_snapshot_handler, get_schema_ui_handler, get_schema_bundle_handler,
+            get_snapshot_handler, get_request_payload_handler, get_state_as_of_handler,
+            get_member_of_handler, get_diagnostics_handler, get_verify_quorum_handler,
+            get_pending_count_handler, get_transitions_handler, get_key_encodings_handler,
+            get_governance_versions_handler, put_approvals_handler, get_search_subjects_handler,
+            post_validate_subject_handler, post_simulated_batch_handler, get_signatures_handler
         ),
         components(
-            schemas(StateRequestBodyUpper, StateRequestBody, SignatureRequest, SignatureRequestContent, PostEventBody, RequestPayload, CreateRequestBody, CreateRequest, StateRequest, EventRequestTypeBody, RequestData, SubjectData, Acceptance, ApprovalResponse, ApprovalResponseContent, EventRequest, Payload, PostEventRequestBody, PutVoteBody, Event, EventRequestType, Signature, EventContent, SignatureContent, EventRequest, Metadata, ExternalEventRequestBody)
+            schemas(StateRequestBodyUpper, StateRequestBody, SignatureRequest, SignatureRequestContent, PostEventBody, RequestPayload, CreateRequestBody, CreateRequest, StateRequest, EventRequestTypeBody, RequestData, SubjectData, Acceptance, ApprovalResponse, ApprovalResponseContent, EventRequest, Payload, PostEventRequestBody, PutVoteBody, Event, EventRequestType, Signature, EventContent, SignatureContent, EventRequest, Metadata, ExternalEventRequestBody, RequestTimeline, TimelineEntry, RequestResolution, ImpactedSubject, CompactEvent, EventsFormat, ResolvedAlias, AliasKind, ApproverStats, GovernanceVersion, ValidationReport, ValidationCheck, SchemaPeriod, RouteInfo, PostMaintenanceBody, MaintenanceStatus, SubjectComparison, ApprovalProgress, RejectedRequest, RequestSummary, NamespaceCount, RequestSignatures, ApprovalSignature, SubjectSnapshot, SchemaUi, UiField, PostSubjectBody, GovernanceOrder, SortOrder, PostSimulatedBatchBody, SimulatedBatch, LastUpdated, BundledSchema, SubjectState, StateAsOf, Membership, Diagnostic, QuorumCheck, PendingCount, Transition, KeyEncodings, GovernanceRevision, Problem, FieldError, PutVotesBody, BatchVote, VoteKind, BatchVoteResult, SearchOperator)
         ),
         modifiers(&SecurityAddon),
         security(),
@@ -286,7 +352,14 @@ async fn main() -> Result<(), Box<dyn Error>> {
         .and(warp::get())
         .map(|| warp::reply::json(&ApiDoc::openapi()));
 
-    if swaggerui {
+    let routes = rest::routes::routes(
+        taple.get_api(),
+        api_key,
+        rest_settings,
+        identity.clone(),
+        startup.clone(),
+    )?;
+    let server = if swaggerui {
         let swagger_ui = warp::path("api")
             .and(warp::path("doc"))
             .and(warp::path("ui"))
@@ -295,24 +368,33 @@ async fn main() -> Result<(), Box<dyn Error>> {
             .and(warp::path::tail())
             .and(warp::any().map(move || config.clone()))
             .and_then(serve_swagger);
-        warp::serve(
-            api_doc
-                .or(swagger_ui)
-                .or(rest::routes::routes(taple.get_api(), api_key)),
-        )
-        .bind_with_graceful_shutdown(http_addr, async move {
-            stream.recv().await;
-        })
-        .1
-        .await;
+        tokio::spawn(rest::deadline::serve(
+            api_doc.or(swagger_ui).or(routes),
+            http_addr,
+            max_timeout,
+            read_timeout,
+            body_log,
+            async move {
+                stream.recv().await;
+            },
+        ))
     } else {
-        warp::serve(api_doc.or(rest::routes::routes(taple.get_api(), api_key)))
-            .bind_with_graceful_shutdown(http_addr, async move {
+        tokio::spawn(rest::deadline::serve(
+            api_doc.or(routes),
+            http_addr,
+            max_timeout,
+            read_timeout,
+            body_log,
+            async move {
                 stream.recv().await;
-            })
-            .1
-            .await;
-    }
+            },
+        ))
+    };
+    wait_initialization(startup_timeout, taple.start()).await?;
+    info!("Controller ID: {}", taple.controller_id().unwrap());
+    identity.set_controllers(vec![taple.controller_id().unwrap().to_string()]);
+    startup.set_ready();
+    server.await??;
     Ok(())
 }
 
@@ -362,6 +444,7 @@ struct AppSettings {
     pub x_api_key: Option<String>,
     #[serde(rename = "swaggerui")]
     pub swagger_ui: bool,
+    pub rest: RestSettings,
 }
 
 impl AppSettings {
@@ -392,6 +475,30 @@ fn load_settings_from_file(args: Args) -> Result<AppSettings, ConfigError> {
         }
         Err(_) => {}
     };
+    match std::env::var("TAPLE_REST_SCHEMAALLOWLIST") {
+        Ok(value) => {
+            let schemas: Vec<String> = value.split(';').map(|f| f.to_string()).collect();
+            config = config.set_override("rest.schemaallowlist", schemas)?;
+        }
+        Err(_) => {}
+    };
+    for list in ["responseallowlist", "responsedenylist"] {
+        // TAPLE_REST_RESPONSEDENYLIST=application/cbor;application/msgpack
+        if let Ok(value) = std::env::var(format!("TAPLE_REST_{}", list.to_uppercase())) {
+            let content_types: Vec<String> = value.split(';').map(|f| f.to_string()).collect();
+            config = config.set_override(format!("rest.{}", list), content_types)?;
+        }
+    }
+    for (key, value) in std::env::vars() {
+        // TAPLE_REST_CONTENTTYPES_<GROUP>=application/json;application/msgpack
+        if let Some(group) = key.strip_prefix("TAPLE_REST_CONTENTTYPES_") {
+            let content_types: Vec<String> = value.split(';').map(|f| f.to_string()).collect();
+            config = config.set_override(
+                format!("rest.contenttypes.{}", group.to_lowercase()),
+                content_types,
+            )?;
+        }
+    }
     let config = config.build()?;
     Ok(config.try_deserialize().unwrap())
 }
@@ -404,6 +511,7 @@ fn set_default(
     let config = config.set_default("httpaddr", "0.0.0.0")?;
     let config = config.set_default("apikey", Option::<String>::None)?;
     let config = config.set_default("swaggerui", false)?;
+    let config = config.set_default("rest.schemaallowlist", Vec::<String>::new())?;
 
     //Core settings
     let default_taple_settings = Taple::get_default_settings();
@@ -0,0 +1,80 @@
+use serde_json::Value;
+
+use super::responses::{AliasKind, ResolvedAlias};
+
+// Properties of a subject that hold its human readable name
+const SUBJECT_ALIAS_FIELDS: [&str; 2] = ["name", "tag"];
+
+// Finds every member and subject known by the given alias. Subjects are given as
+// (subject_id, is_governance, properties) tuples. Members are matched by their id inside the
+// governances and resolve to their key. Every match is returned, in the order subjects are
+// given, so callers decide how to handle ambiguous aliases.
+pub fn resolve_alias(alias: &str, subjects: Vec<(String, bool, String)>) -> Vec<ResolvedAlias> {
+    let mut resolved = Vec::new();
+    for (subject_id, is_governance, properties) in subjects {
+        let Ok(properties) = serde_json::from_str::<Value>(&properties) else {
+            continue;
+        };
+        if SUBJECT_ALIAS_FIELDS
+            .iter()
+            .any(|field| properties.get(field).and_then(Value::as_str) == Some(alias))
+        {
+            resolved.push(ResolvedAlias {
+                kind: AliasKind::Subject,
+                id: subject_id.clone(),
+                governance_id: None,
+            });
+        }
+        if !is_governance {
+            continue;
+        }
+        let members = properties.get("members").and_then(Value::as_array);
+        for member in members.into_iter().flatten() {
+            if member.get("id").and_then(Value::as_str) != Some(alias) {
+                continue;
+            }
+            if let Some(key) = member.get("key").and_then(Value::as_str) {
+                resolved.push(ResolvedAlias {
+                    kind: AliasKind::Member,
+                    id: key.to_owned(),
+                    governance_id: Some(subject_id.clone()),
+                });
+            }
+        }
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_resolve_known_alias() {
+        let subjects = vec![
+            (
+                "JGSPR6FL-vE7iZxWMd17o09qn7NeTqlcImDVWmijXczw".into(),
+                true,
+                r#"{"members":[{"id":"Company","key":"EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w"}],"schemas":[]}"#.into(),
+            ),
+            (
+                "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc".into(),
+                false,
+                r#"{"name":"sensor-1","temperatura":10}"#.into(),
+            ),
+        ];
+        let resolved = resolve_alias("Company", subjects.clone());
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].kind, AliasKind::Member);
+        assert_eq!(
+            resolved[0].id,
+            "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w"
+        );
+        let resolved = resolve_alias("sensor-1", subjects.clone());
+        assert_eq!(
+            resolved[0].id,
+            "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc"
+        );
+        assert!(resolve_alias("unknown", subjects).is_empty());
+    }
+}
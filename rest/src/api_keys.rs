@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+
+use super::error::Error;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+const BEARER_PREFIX: &str = "bearer ";
+
+// API KEYS accepted by the routes that require one, sent in the X-API-KEY header or as a bearer
+// token in the Authorization header. No key is required when none is configured
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeys {
+    keys: Arc<Vec<String>>,
+}
+
+impl ApiKeys {
+    pub fn new(keys: impl IntoIterator<Item = String>) -> Self {
+        let keys: Vec<String> = keys.into_iter().filter(|key| !key.is_empty()).collect();
+        Self {
+            keys: Arc::new(keys),
+        }
+    }
+
+    pub fn is_required(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    // Key of the request. The key is empty when none is required
+    pub fn check(
+        &self,
+        api_key: Option<&str>,
+        authorization: Option<&str>,
+    ) -> Result<String, Error> {
+        if !self.is_required() {
+            return Ok(String::new());
+        }
+        match presented_key(api_key, authorization) {
+            Some(key) if self.is_allowed(key) => Ok(key.to_owned()),
+            _ => Err(Error::Unauthorized),
+        }
+    }
+
+    // Every configured key is compared, none of them stopping early, so the time taken does not
+    // tell how much of a key was right
+    fn is_allowed(&self, key: &str) -> bool {
+        self.keys.iter().fold(false, |allowed, configured| {
+            same_key(configured, key) | allowed
+        })
+    }
+}
+
+// Key sent with a request. X-API-KEY takes precedence, so a wrong key in it is not made up for
+// by the Authorization header
+pub fn presented_key<'a>(
+    api_key: Option<&'a str>,
+    authorization: Option<&'a str>,
+) -> Option<&'a str> {
+    api_key.or_else(|| authorization.and_then(bearer_token))
+}
+
+// Constant time comparison. Digests are compared instead of the keys, so keys of different
+// lengths take the same time too
+fn same_key(a: &str, b: &str) -> bool {
+    let (a, b) = (Sha256::digest(a.as_bytes()), Sha256::digest(b.as_bytes()));
+    a.iter()
+        .zip(b.iter())
+        .fold(0, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+fn bearer_token(authorization: &str) -> Option<&str> {
+    let prefix = authorization.get(..BEARER_PREFIX.len())?;
+    if !prefix.eq_ignore_ascii_case(BEARER_PREFIX) {
+        return None;
+    }
+    Some(authorization[BEARER_PREFIX.len()..].trim())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_only_configured_keys_are_accepted() {
+        let keys = ApiKeys::new(vec!["apikeyexamplevalue123".into(), "readonlykey".into()]);
+        assert!(keys.is_required());
+        // Missing key
+        assert!(matches!(keys.check(None, None), Err(Error::Unauthorized)));
+        // Wrong key, in either header
+        assert!(matches!(
+            keys.check(Some("wrongkey"), None),
+            Err(Error::Unauthorized)
+        ));
+        assert!(matches!(
+            keys.check(None, Some("Bearer wrongkey")),
+            Err(Error::Unauthorized)
+        ));
+        assert!(matches!(
+            keys.check(Some("wrongkey"), Some("Bearer readonlykey")),
+            Err(Error::Unauthorized)
+        ));
+        // Only bearer tokens are taken from the Authorization header
+        assert!(matches!(
+            keys.check(None, Some("Basic readonlykey")),
+            Err(Error::Unauthorized)
+        ));
+        // Correct key
+        assert_eq!(
+            keys.check(Some("apikeyexamplevalue123"), None).unwrap(),
+            "apikeyexamplevalue123"
+        );
+        assert_eq!(
+            keys.check(None, Some("bearer readonlykey")).unwrap(),
+            "readonlykey"
+        );
+        // Without configured keys every request is accepted
+        let keys = ApiKeys::new(vec![String::new()]);
+        assert!(!keys.is_required());
+        assert_eq!(keys.check(None, None).unwrap(), "");
+    }
+
+    #[test]
+    fn test_keys_are_compared_whole() {
+        assert!(same_key("apikeyexamplevalue123", "apikeyexamplevalue123"));
+        assert!(!same_key("apikeyexamplevalue123", "apikeyexamplevalue12"));
+        assert!(!same_key("apikeyexamplevalue123", "apikeyexamplevalue124"));
+        assert!(!same_key("apikeyexamplevalue123", ""));
+    }
+}
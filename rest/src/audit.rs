@@ -0,0 +1,289 @@
+use std::{fs::OpenOptions, future::Future, io::Write};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::{mpsc, oneshot};
+use warp::{Rejection, Reply};
+
+use super::error::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRecord {
+    pub timestamp: i64,
+    pub operation: String,
+    // Fingerprint of the API KEY used, never the key itself
+    pub identity: String,
+    pub target: String,
+    pub outcome: AuditOutcome,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+enum AuditMessage {
+    Record(Vec<u8>),
+    Flush(oneshot::Sender<()>),
+}
+
+// Trail of the mutating operations, one JSON record per line. Kept apart from the regular
+// logging so it can not be silenced by the log level. The records are written by a thread of
+// its own, so the handlers never wait for the output. They are queued without a bound, as
+// dropping them would leave operations out of the trail.
+#[derive(Clone)]
+pub struct AuditLog {
+    sender: mpsc::UnboundedSender<AuditMessage>,
+}
+
+impl AuditLog {
+    pub fn new(mut output: Box<dyn Write + Send>) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        // The writer stops once every clone of the log is dropped
+        std::thread::spawn(move || {
+            while let Some(message) = receiver.blocking_recv() {
+                match message {
+                    AuditMessage::Record(line) => {
+                        if let Err(error) = output.write_all(&line).and_then(|_| output.flush()) {
+                            log::error!("Audit record could not be written: {}", error);
+                        }
+                    }
+                    AuditMessage::Flush(written) => {
+                        let _ = written.send(());
+                    }
+                }
+            }
+        });
+        Self { sender }
+    }
+
+    // Appends to the given file, or writes to stdout if there is none
+    pub fn open(path: Option<&str>) -> std::io::Result<Self> {
+        match path {
+            Some(path) => Ok(Self::new(Box::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            ))),
+            None => Ok(Self::new(Box::new(std::io::stdout()))),
+        }
+    }
+
+    pub fn record(&self, record: &AuditRecord) {
+        let mut line = serde_json::to_vec(record).expect("Audit records are always serializable");
+        line.push(b'\n');
+        if self.sender.send(AuditMessage::Record(line)).is_err() {
+            log::error!("Audit record could not be written: the writer has stopped");
+        }
+    }
+
+    // Waits until the records sent before have been written
+    pub async fn flush(&self) {
+        let (written, wait) = oneshot::channel();
+        if self.sender.send(AuditMessage::Flush(written)).is_ok() {
+            let _ = wait.await;
+        }
+    }
+
+    // Runs a mutating operation and records its outcome, whether it succeeds or not
+    pub async fn audited(
+        &self,
+        operation: &str,
+        api_key: &str,
+        target: &str,
+        handler: impl Future<Output = Result<Box<dyn Reply>, Rejection>>,
+    ) -> Result<Box<dyn Reply>, Rejection> {
+        let result = handler.await;
+        let mut record = AuditRecord {
+            timestamp: chrono::Utc::now().timestamp(),
+            operation: operation.to_owned(),
+            identity: identity(api_key),
+            target: target.to_owned(),
+            outcome: AuditOutcome::Success,
+            status: None,
+            error: None,
+        };
+        let result = match result {
+            Ok(reply) => {
+                let response = reply.into_response();
+                record.status = Some(response.status().as_u16());
+                if !response.status().is_success() {
+                    record.outcome = AuditOutcome::Failure;
+                }
+                Ok(Box::new(response) as Box<dyn Reply>)
+            }
+            Err(rejection) => {
+                record.outcome = AuditOutcome::Failure;
+                record.error = Some(match rejection.find::<Error>() {
+                    Some(error) => error.to_string(),
+                    None => format!("{:?}", rejection),
+                });
+                Err(rejection)
+            }
+        };
+        self.record(&record);
+        result
+    }
+}
+
+fn identity(api_key: &str) -> String {
+    if api_key.is_empty() {
+        // The API KEY is not required by the node
+        return String::from("anonymous");
+    }
+    let digest = Sha256::digest(api_key.as_bytes());
+    format!(
+        "apikey:{}",
+        base64::encode_config(&digest[..9], base64::URL_SAFE_NO_PAD)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{mpsc::Receiver, Arc, Mutex};
+    use warp::http::StatusCode;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Output that waits for the test before each write
+    struct Gated(Receiver<()>, Buffer);
+
+    impl Write for Gated {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.recv().unwrap();
+            self.1.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn records(buffer: &Buffer) -> Vec<serde_json::Value> {
+        let lines = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        lines
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_created_subject_is_audited() {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                let buffer = Buffer::default();
+                let audit = AuditLog::new(Box::new(buffer.clone()));
+                let response = audited_post(&audit, Ok(Box::new(StatusCode::CREATED))).await;
+                assert!(response.is_ok());
+                audit.flush().await;
+                let records = records(&buffer);
+                assert_eq!(records.len(), 1);
+                assert_eq!(records[0]["operation"], "post_subject");
+                assert_eq!(
+                    records[0]["target"],
+                    "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0"
+                );
+                assert_eq!(records[0]["outcome"], "success");
+                assert_eq!(records[0]["status"], 201);
+                assert!(records[0]["timestamp"].as_i64().unwrap() > 0);
+                let identity = records[0]["identity"].as_str().unwrap();
+                assert!(identity.starts_with("apikey:"));
+            });
+    }
+
+    #[test]
+    fn test_failed_operation_is_audited() {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                let buffer = Buffer::default();
+                let audit = AuditLog::new(Box::new(buffer.clone()));
+                let response =
+                    audited_post(&audit, Err(warp::reject::custom(Error::NotFound))).await;
+                assert!(response.is_err());
+                audit.flush().await;
+                let records = records(&buffer);
+                assert_eq!(records[0]["outcome"], "failure");
+                assert_eq!(records[0]["error"], "Not found");
+            });
+    }
+
+    #[test]
+    fn test_operations_do_not_wait_for_the_output() {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                let buffer = Buffer::default();
+                let (open, gate) = std::sync::mpsc::channel();
+                let audit = AuditLog::new(Box::new(Gated(gate, buffer.clone())));
+                // The operations are answered while the output is still busy
+                for _ in 0..3 {
+                    let response = audited_post(&audit, Ok(Box::new(StatusCode::CREATED))).await;
+                    assert!(response.is_ok());
+                }
+                assert!(records(&buffer).is_empty());
+                for _ in 0..3 {
+                    open.send(()).unwrap();
+                }
+                audit.flush().await;
+                assert_eq!(records(&buffer).len(), 3);
+            });
+    }
+
+    #[test]
+    fn test_concurrent_records_are_written_whole() {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                let buffer = Buffer::default();
+                let audit = AuditLog::new(Box::new(buffer.clone()));
+                let tasks: Vec<_> = (0..200)
+                    .map(|_| {
+                        let audit = audit.clone();
+                        tokio::spawn(async move {
+                            audited_post(&audit, Ok(Box::new(StatusCode::CREATED)))
+                                .await
+                                .is_ok()
+                        })
+                    })
+                    .collect();
+                for task in futures::future::join_all(tasks).await {
+                    assert!(task.unwrap());
+                }
+                audit.flush().await;
+                // Each line is a whole record
+                let records = records(&buffer);
+                assert_eq!(records.len(), 200);
+                assert!(records.iter().all(|record| record["status"] == 201));
+            });
+    }
+
+    async fn audited_post(
+        audit: &AuditLog,
+        result: Result<Box<dyn Reply>, Rejection>,
+    ) -> Result<Box<dyn Reply>, Rejection> {
+        audit
+            .audited(
+                "post_subject",
+                "1234",
+                "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+                async move { result },
+            )
+            .await
+    }
+}
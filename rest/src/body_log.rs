@@ -0,0 +1,228 @@
+use std::{
+    convert::Infallible,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures::Stream;
+use hyper::body::{Bytes, HttpBody};
+use warp::{
+    http::{HeaderMap, StatusCode},
+    hyper::{Body, Request},
+    reply::Response,
+};
+
+// Headers that carry the API KEY
+const REDACTED_HEADERS: [&str; 2] = ["x-api-key", "authorization"];
+
+// First bytes of a request body, as they are read by the routes, and its total size
+#[derive(Debug, Default)]
+struct Captured {
+    bytes: Vec<u8>,
+    size: usize,
+}
+
+struct TeeBody {
+    body: Body,
+    captured: Arc<Mutex<Captured>>,
+    limit: usize,
+}
+
+impl Stream for TeeBody {
+    type Item = Result<Bytes, io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let chunk = Pin::new(&mut self.body).poll_next(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &chunk {
+            let mut captured = self.captured.lock().unwrap();
+            let room = self.limit.saturating_sub(captured.bytes.len());
+            captured
+                .bytes
+                .extend_from_slice(&chunk[..room.min(chunk.len())]);
+            captured.size += chunk.len();
+        }
+        chunk.map(|chunk| {
+            chunk.map(|chunk| chunk.map_err(|error| io::Error::new(io::ErrorKind::Other, error)))
+        })
+    }
+}
+
+// Logs at debug level the bodies of the requests to the API and of their responses, up to a
+// number of bytes each. Streamed responses are passed through untouched and only their status
+// is logged.
+#[derive(Debug, Clone)]
+pub struct BodyLog {
+    limit: usize,
+}
+
+impl BodyLog {
+    pub fn new(limit: usize) -> Self {
+        Self { limit }
+    }
+
+    pub async fn logged<F, R>(
+        &self,
+        request: Request<Body>,
+        handle: F,
+    ) -> Result<Response, Infallible>
+    where
+        F: FnOnce(Request<Body>) -> R,
+        R: Future<Output = Result<Response, Infallible>>,
+    {
+        let path = request.uri().path();
+        if path != "/api" && !path.starts_with("/api/") {
+            return handle(request).await;
+        }
+        let line = format!(
+            "{} {} [{}]",
+            request.method(),
+            request.uri(),
+            redacted_headers(request.headers())
+        );
+        let captured = Arc::new(Mutex::new(Captured::default()));
+        let tee = captured.clone();
+        let limit = self.limit;
+        let request = request.map(|body| {
+            Body::wrap_stream(TeeBody {
+                body,
+                captured: tee,
+                limit,
+            })
+        });
+        let response = handle(request).await?;
+        let request_body = {
+            let captured = captured.lock().unwrap();
+            preview(&captured.bytes, captured.size, self.limit)
+        };
+        if response.body().size_hint().exact().is_none() {
+            log::debug!(
+                "{} body: {} -> {} body: <streamed>",
+                line,
+                request_body,
+                response.status()
+            );
+            return Ok(response);
+        }
+        let (parts, body) = response.into_parts();
+        let body = match hyper::body::to_bytes(body).await {
+            Ok(body) => body,
+            Err(error) => {
+                log::error!("Response body could not be read: {}", error);
+                let mut response = Response::new(String::from("Internal Server Error").into());
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                return Ok(response);
+            }
+        };
+        log::debug!(
+            "{} body: {} -> {} body: {}",
+            line,
+            request_body,
+            parts.status,
+            preview(&body, body.len(), self.limit)
+        );
+        Ok(Response::from_parts(parts, body.into()))
+    }
+}
+
+fn redacted_headers(headers: &HeaderMap) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            if REDACTED_HEADERS.contains(&name.as_str()) {
+                format!("{}: <redacted>", name)
+            } else {
+                format!("{}: {}", name, String::from_utf8_lossy(value.as_bytes()))
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn preview(bytes: &[u8], size: usize, limit: usize) -> String {
+    let text = String::from_utf8_lossy(&bytes[..limit.min(bytes.len())]);
+    if size > limit {
+        format!("{}... ({} bytes)", text, size)
+    } else {
+        text.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tower_service::Service;
+    use warp::Filter;
+
+    // Keeps the debug records of this module
+    struct Capture(Mutex<Vec<String>>);
+
+    impl log::Log for Capture {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.target().ends_with("body_log")
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) && record.level() == log::Level::Debug {
+                self.0.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static CAPTURE: Capture = Capture(Mutex::new(Vec::new()));
+
+    #[test]
+    fn test_bodies_are_logged_without_the_api_key() {
+        log::set_logger(&CAPTURE).unwrap();
+        log::set_max_level(log::LevelFilter::Debug);
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                let echo = warp::path!("api" / "requests")
+                    .and(warp::body::bytes())
+                    .map(|body: Bytes| body.to_vec());
+                let stream = warp::path!("api" / "stream").map(|| {
+                    let chunks = futures::stream::iter(vec![Ok::<_, io::Error>("data: 1\n\n")]);
+                    Response::new(Body::wrap_stream(chunks))
+                });
+                let filter = echo.or(stream);
+                let log = BodyLog::new(16);
+                let request = Request::post("/api/requests")
+                    .header("x-api-key", "apikeyexamplevalue123")
+                    .header("authorization", "Bearer readonlykey")
+                    .body(Body::from(r#"{"subject_id":"J1"}"#))
+                    .unwrap();
+                let response = log
+                    .logged(request, |request| {
+                        warp::service(filter.clone()).call(request)
+                    })
+                    .await
+                    .unwrap();
+                let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+                assert_eq!(body, r#"{"subject_id":"J1"}"#);
+                // Streams are not buffered
+                let request = Request::get("/api/stream").body(Body::empty()).unwrap();
+                let response = log
+                    .logged(request, |request| {
+                        warp::service(filter.clone()).call(request)
+                    })
+                    .await
+                    .unwrap();
+                assert!(response.body().size_hint().exact().is_none());
+
+                let records = CAPTURE.0.lock().unwrap();
+                assert_eq!(records.len(), 2);
+                assert!(records[0].starts_with("POST /api/requests"));
+                assert!(records[0].contains(r#"body: {"subject_id":"J... (19 bytes)"#));
+                assert!(records[0].contains("x-api-key: <redacted>"));
+                assert!(records[0].contains("authorization: <redacted>"));
+                assert!(!records[0].contains("apikeyexamplevalue123"));
+                assert!(!records[0].contains("readonlykey"));
+                assert!(records[1].ends_with("-> 200 OK body: <streamed>"));
+            });
+    }
+}
@@ -25,6 +25,17 @@ impl Into<RequestPayload> for Payload {
     }
 }
 
+// Decodes the payload of a stored request, so it can be sent again in a new one
+impl TryFrom<&RequestPayload> for Payload {
+    type Error = serde_json::Error;
+    fn try_from(payload: &RequestPayload) -> Result<Self, Self::Error> {
+        Ok(match payload {
+            RequestPayload::Json(data) => Self::Json(serde_json::from_str(data)?),
+            RequestPayload::JsonPatch(data) => Self::JsonPatch(serde_json::from_str(data)?),
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct PostSubjectBody {
     pub governance_id: String,
@@ -106,6 +117,12 @@ pub struct PostEventBody {
     pub payload: Payload,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PostSimulatedBatchBody {
+    // Applied in order, each one to the state left by the previous one
+    pub payloads: Vec<Payload>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct PostGovernanceBody {
     pub payload: Payload,
@@ -118,9 +135,43 @@ pub struct SignatureRequestContent {
     pub timestamp: i64,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PostMaintenanceBody {
+    pub enabled: bool,
+    // Seconds sent in the Retry-After header of blocked writes
+    pub retry_after: Option<u64>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(tag = "approvalType")]
 pub enum PutVoteBody {
     Accept,
     Reject,
 }
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum VoteKind {
+    Accept,
+    Reject,
+}
+
+impl From<VoteKind> for PutVoteBody {
+    fn from(vote: VoteKind) -> Self {
+        match vote {
+            VoteKind::Accept => PutVoteBody::Accept,
+            VoteKind::Reject => PutVoteBody::Reject,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BatchVote {
+    pub request_id: String,
+    pub vote: VoteKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PutVotesBody {
+    // Cast in order, each one as with PUT /approvals/{id}
+    pub votes: Vec<BatchVote>,
+}
@@ -0,0 +1,175 @@
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use utoipa::{Path, ToSchema};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct RouteInfo {
+    pub path: String,
+    pub methods: Vec<String>,
+    pub auth: bool,
+}
+
+// Routes registered while composing the API filter. Paths and methods come from the
+// documentation of each handler, so the catalog can not diverge from the served routes. It is
+// only written before serving, requests just read it.
+#[derive(Debug, Clone, Default)]
+pub struct RouteCatalog {
+    routes: Arc<Mutex<Vec<RouteInfo>>>,
+    // Whether an API key is configured, otherwise no route requires authentication
+    auth: bool,
+}
+
+impl RouteCatalog {
+    pub fn new(auth: bool) -> Self {
+        Self {
+            routes: Default::default(),
+            auth,
+        }
+    }
+
+    // Records the route documented by P
+    pub fn add<P: Path>(&self) {
+        let path_item = P::path_item(None);
+        let mut routes = self.routes.lock().unwrap();
+        for (method, operation) in path_item.operations {
+            let auth = self.auth
+                && operation
+                    .security
+                    .as_ref()
+                    .map_or(false, |security| !security.is_empty());
+            let method = format!("{:?}", method).to_uppercase();
+            match routes.iter_mut().find(|route| route.path == P::path()) {
+                Some(route) if route.methods.contains(&method) => {}
+                Some(route) => {
+                    route.methods.push(method);
+                    route.auth |= auth;
+                }
+                None => routes.push(RouteInfo {
+                    path: P::path().to_owned(),
+                    methods: vec![method],
+                    auth,
+                }),
+            }
+        }
+    }
+
+    pub fn routes(&self) -> Vec<RouteInfo> {
+        let mut routes = self.routes.lock().unwrap().clone();
+        routes.sort_by(|a, b| a.path.cmp(&b.path));
+        routes
+    }
+
+    // Documented route of a requested path. Fixed segments take precedence over parameters, as
+    // "/api/subjects/owned" also matches "/api/subjects/{id}"
+    pub fn route_of(&self, path: &str) -> Option<String> {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let routes = self.routes.lock().unwrap();
+        routes
+            .iter()
+            .filter_map(|route| {
+                let pattern: Vec<&str> = route.path.trim_matches('/').split('/').collect();
+                if pattern.len() != segments.len() {
+                    return None;
+                }
+                let mut fixed = 0;
+                for (expected, segment) in pattern.iter().zip(&segments) {
+                    if expected.starts_with('{') && expected.ends_with('}') {
+                        continue;
+                    }
+                    if expected != segment {
+                        return None;
+                    }
+                    fixed += 1;
+                }
+                Some((fixed, &route.path))
+            })
+            .max_by_key(|(fixed, _)| *fixed)
+            .map(|(_, path)| path.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::handlers::{
+        __path_get_owned_subjects_handler, __path_get_subject_handler,
+        __path_patch_governance_handler, __path_post_event_request_handler,
+    };
+
+    #[test]
+    fn test_known_routes_are_listed() {
+        let catalog = RouteCatalog::new(true);
+        catalog.add::<__path_get_subject_handler>();
+        catalog.add::<__path_post_event_request_handler>();
+        catalog.add::<__path_patch_governance_handler>();
+        // Routes composed more than once are listed once
+        catalog.add::<__path_get_subject_handler>();
+        let routes = catalog.routes();
+        let listed: Vec<(&str, &[String])> = routes
+            .iter()
+            .map(|route| (route.path.as_str(), route.methods.as_slice()))
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                ("/api/governances/{id}", ["PATCH".to_owned()].as_slice()),
+                ("/api/requests", ["POST".to_owned()].as_slice()),
+                ("/api/subjects/{id}", ["GET".to_owned()].as_slice()),
+            ]
+        );
+        assert!(routes.iter().all(|route| route.auth));
+        let catalog = RouteCatalog::new(false);
+        catalog.add::<__path_get_subject_handler>();
+        assert!(!catalog.routes()[0].auth);
+    }
+
+    #[test]
+    fn test_paths_are_matched_to_their_route() {
+        let catalog = RouteCatalog::new(true);
+        catalog.add::<__path_get_subject_handler>();
+        catalog.add::<__path_get_owned_subjects_handler>();
+        assert_eq!(
+            catalog.route_of("/api/subjects/JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc"),
+            Some(String::from("/api/subjects/{id}"))
+        );
+        assert_eq!(
+            catalog.route_of("/api/subjects/owned"),
+            Some(String::from("/api/subjects/owned"))
+        );
+        assert_eq!(catalog.route_of("/api/subjects/J1/unknown"), None);
+    }
+
+    #[test]
+    fn test_concurrent_requests_read_the_same_routes() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let catalog = RouteCatalog::new(true);
+            catalog.add::<__path_get_subject_handler>();
+            catalog.add::<__path_get_owned_subjects_handler>();
+            let tasks: Vec<_> = (0..200)
+                .map(|i| {
+                    let catalog = catalog.clone();
+                    tokio::spawn(async move {
+                        let path = if i % 2 == 0 {
+                            format!("/api/subjects/J{}", i)
+                        } else {
+                            String::from("/api/subjects/owned")
+                        };
+                        (i, catalog.route_of(&path), catalog.routes().len())
+                    })
+                })
+                .collect();
+            for task in futures::future::join_all(tasks).await {
+                let (i, route, listed) = task.unwrap();
+                let expected = if i % 2 == 0 {
+                    "/api/subjects/{id}"
+                } else {
+                    "/api/subjects/owned"
+                };
+                assert_eq!(route.as_deref(), Some(expected));
+                assert_eq!(listed, 2);
+            }
+        });
+    }
+}
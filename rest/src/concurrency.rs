@@ -0,0 +1,138 @@
+use std::{collections::HashMap, sync::Arc};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use warp::http::Method;
+
+use super::error::Error;
+
+// Seconds clients are told to wait when the group of the route is saturated
+pub const SATURATED_RETRY_AFTER: u64 = 1;
+
+pub const SIMULATION_GROUP: &str = "simulation";
+pub const VERIFICATION_GROUP: &str = "verification";
+pub const READS_GROUP: &str = "reads";
+pub const WRITES_GROUP: &str = "writes";
+
+// Group whose limit applies to a request. Simulations and verifications replay or validate
+// states, so they are kept apart from the cheap reads and from the writes
+pub fn route_group(method: &Method, path: &str) -> &'static str {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    if segments.contains(&"simulated") || (method == Method::POST && path.ends_with("/impact")) {
+        SIMULATION_GROUP
+    } else if method == Method::POST && segments.last() == Some(&"validate") {
+        VERIFICATION_GROUP
+    } else if method == Method::GET || method == Method::HEAD {
+        READS_GROUP
+    } else {
+        WRITES_GROUP
+    }
+}
+
+// Maximum number of requests in flight by route group. Groups without a limit are not
+// restricted
+#[derive(Debug, Clone, Default)]
+pub struct ConcurrencyLimits {
+    semaphores: Arc<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl ConcurrencyLimits {
+    pub fn new(limits: &HashMap<String, usize>) -> Self {
+        let semaphores = limits
+            .iter()
+            .map(|(group, limit)| (group.to_lowercase(), Arc::new(Semaphore::new(*limit))))
+            .collect();
+        Self {
+            semaphores: Arc::new(semaphores),
+        }
+    }
+
+    // The request keeps its place in the group until the permit is dropped
+    pub fn try_acquire(&self, group: &str) -> Result<Option<OwnedSemaphorePermit>, Error> {
+        let Some(semaphore) = self.semaphores.get(group) else {
+            return Ok(None);
+        };
+        semaphore
+            .clone()
+            .try_acquire_owned()
+            .map(Some)
+            .map_err(|_| Error::ServiceUnavailable(SATURATED_RETRY_AFTER))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_route_groups() {
+        let group = |method: Method, path: &str| route_group(&method, path);
+        assert_eq!(
+            group(Method::POST, "/api/subjects/J1/events/simulated/batch"),
+            SIMULATION_GROUP
+        );
+        assert_eq!(
+            group(Method::POST, "/api/governances/J1/impact"),
+            SIMULATION_GROUP
+        );
+        assert_eq!(
+            group(Method::POST, "/api/requests/validate"),
+            VERIFICATION_GROUP
+        );
+        // A subject could have "validate" as id
+        assert_eq!(group(Method::GET, "/api/subjects/validate"), READS_GROUP);
+        assert_eq!(group(Method::PUT, "/api/approvals/J1"), WRITES_GROUP);
+    }
+
+    #[test]
+    fn test_groups_are_limited_independently() {
+        let limits = ConcurrencyLimits::new(&HashMap::from([(SIMULATION_GROUP.into(), 1)]));
+        let permit = limits.try_acquire(SIMULATION_GROUP).unwrap();
+        assert!(permit.is_some());
+        assert!(matches!(
+            limits.try_acquire(SIMULATION_GROUP),
+            Err(Error::ServiceUnavailable(SATURATED_RETRY_AFTER))
+        ));
+        assert!(limits.try_acquire(READS_GROUP).unwrap().is_none());
+        drop(permit);
+        assert!(limits.try_acquire(SIMULATION_GROUP).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_concurrent_requests_respect_the_limit() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let limits = ConcurrencyLimits::new(&HashMap::from([(READS_GROUP.into(), 3)]));
+            let in_flight = Arc::new(AtomicUsize::new(0));
+            let most = Arc::new(AtomicUsize::new(0));
+            let tasks: Vec<_> = (0..200)
+                .map(|_| {
+                    let limits = limits.clone();
+                    let in_flight = in_flight.clone();
+                    let most = most.clone();
+                    tokio::spawn(async move {
+                        let Ok(permit) = limits.try_acquire(READS_GROUP) else {
+                            return false;
+                        };
+                        let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                        most.fetch_max(current, Ordering::SeqCst);
+                        tokio::task::yield_now().await;
+                        in_flight.fetch_sub(1, Ordering::SeqCst);
+                        drop(permit);
+                        true
+                    })
+                })
+                .collect();
+            let served = futures::future::join_all(tasks)
+                .await
+                .into_iter()
+                .filter(|served| *served.as_ref().unwrap())
+                .count();
+            assert!(served > 0);
+            assert!(most.load(Ordering::SeqCst) <= 3);
+            // Every permit is given back
+            assert_eq!(limits.semaphores[READS_GROUP].available_permits(), 3);
+        });
+    }
+}
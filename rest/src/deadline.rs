@@ -0,0 +1,188 @@
+use std::{
+    convert::Infallible, future::Future, net::SocketAddr, sync::atomic::Ordering, time::Duration,
+};
+
+use hyper::{
+    server::conn::AddrIncoming,
+    service::{make_service_fn, service_fn},
+    Body, Request, Server,
+};
+use tower_service::Service;
+use warp::{
+    http::{HeaderMap, StatusCode},
+    reply::Response,
+    Filter, Rejection, Reply,
+};
+
+use super::{
+    body_log::BodyLog,
+    read_timeout::{request_timed_out, timed_request, TimedIncoming, TimedStream},
+};
+
+// Unix time in milliseconds after which the client is no longer interested in the response
+pub const DEADLINE_HEADER: &str = "x-deadline";
+// Milliseconds the client is willing to wait for the response
+pub const TIMEOUT_HEADER: &str = "x-timeout";
+
+// Time the server may spend on a request. Clients can shorten it with either header, but never
+// extend it past the server maximum.
+pub fn request_timeout(headers: &HeaderMap, now: i64, max: Duration) -> Result<Duration, String> {
+    let header = |name: &str| -> Result<Option<i64>, String> {
+        headers
+            .get(name)
+            .map(|value| {
+                value
+                    .to_str()
+                    .ok()
+                    .and_then(|value| value.trim().parse::<i64>().ok())
+                    .ok_or(format!("Invalid {} header", name))
+            })
+            .transpose()
+    };
+    let mut timeout = max;
+    if let Some(deadline) = header(DEADLINE_HEADER)? {
+        // A deadline already in the past leaves no time at all
+        timeout = timeout.min(Duration::from_millis((deadline - now).max(0) as u64));
+    }
+    if let Some(millis) = header(TIMEOUT_HEADER)? {
+        if millis < 0 {
+            return Err(format!("Invalid {} header", TIMEOUT_HEADER));
+        }
+        timeout = timeout.min(Duration::from_millis(millis as u64));
+    }
+    Ok(timeout)
+}
+
+// Runs a request through the filter within its timeout. The work is dropped once the timeout
+// expires, answering with 504.
+pub async fn bounded<F>(
+    filter: F,
+    max: Duration,
+    request: Request<Body>,
+) -> Result<Response, Infallible>
+where
+    F: Filter<Error = Rejection> + Clone + Send + Sync + 'static,
+    F::Extract: Reply,
+{
+    let now = chrono::Utc::now().timestamp_millis();
+    let timeout = match request_timeout(request.headers(), now, max) {
+        Ok(timeout) => timeout,
+        Err(error) => {
+            let mut response = Response::new(error.into());
+            *response.status_mut() = StatusCode::BAD_REQUEST;
+            return Ok(response);
+        }
+    };
+    match tokio::time::timeout(timeout, warp::service(filter).call(request)).await {
+        Ok(response) => response,
+        Err(_) => {
+            let mut response = Response::new(String::from("Gateway Timeout").into());
+            *response.status_mut() = StatusCode::GATEWAY_TIMEOUT;
+            Ok(response)
+        }
+    }
+}
+
+// Serves the filter bounding every request by its deadline. Clients must also send the headers
+// and then the body of each request within the read timeout, answering with 408 otherwise.
+// The bodies of the exchanges with the API are logged if a body log is given.
+pub async fn serve<F>(
+    filter: F,
+    addr: SocketAddr,
+    max: Duration,
+    read_timeout: Duration,
+    body_log: Option<BodyLog>,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), hyper::Error>
+where
+    F: Filter<Error = Rejection> + Clone + Send + Sync + 'static,
+    F::Extract: Reply,
+{
+    let make_service = make_service_fn(move |conn: &TimedStream| {
+        let (filter, body_log) = (filter.clone(), body_log.clone());
+        let phase = conn.phase();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let (filter, phase, body_log) = (filter.clone(), phase.clone(), body_log.clone());
+                async move {
+                    phase.start_request();
+                    let (request, expired) = timed_request(request, read_timeout);
+                    let response = match body_log {
+                        Some(body_log) => {
+                            body_log
+                                .logged(request, |request| bounded(filter, max, request))
+                                .await
+                        }
+                        None => bounded(filter, max, request).await,
+                    };
+                    phase.finish_request();
+                    if expired.load(Ordering::SeqCst) {
+                        return Ok(request_timed_out());
+                    }
+                    response
+                }
+            }))
+        }
+    });
+    let incoming = TimedIncoming::new(AddrIncoming::bind(&addr)?, read_timeout);
+    Server::builder(incoming)
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown)
+        .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MAX: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn test_client_can_only_shorten_the_timeout() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(request_timeout(&headers, 0, MAX), Ok(MAX));
+        headers.insert(TIMEOUT_HEADER, "500".parse().unwrap());
+        assert_eq!(
+            request_timeout(&headers, 0, MAX),
+            Ok(Duration::from_millis(500))
+        );
+        headers.insert(TIMEOUT_HEADER, "3600000".parse().unwrap());
+        assert_eq!(request_timeout(&headers, 0, MAX), Ok(MAX));
+        headers.insert(DEADLINE_HEADER, "1200".parse().unwrap());
+        assert_eq!(
+            request_timeout(&headers, 1000, MAX),
+            Ok(Duration::from_millis(200))
+        );
+        // Deadline already expired
+        assert_eq!(request_timeout(&headers, 2000, MAX), Ok(Duration::ZERO));
+        headers.insert(TIMEOUT_HEADER, "soon".parse().unwrap());
+        assert!(request_timeout(&headers, 0, MAX).is_err());
+    }
+
+    #[test]
+    fn test_short_deadline_is_gateway_timeout() {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                // Stands for a node call slower than the client is willing to wait
+                let slow = warp::path!("api" / "subjects").and_then(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    Ok::<_, Rejection>(warp::reply())
+                });
+                let request = Request::get("/api/subjects")
+                    .header(TIMEOUT_HEADER, "50")
+                    .body(Body::empty())
+                    .unwrap();
+                let response = bounded(slow.clone(), MAX, request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+                let request = Request::get("/api/subjects")
+                    .header(TIMEOUT_HEADER, "10000")
+                    .body(Body::empty())
+                    .unwrap();
+                let response = bounded(slow, Duration::from_millis(50), request)
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+            });
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use hyper::body::HttpBody;
+use serde_json::Value;
+use warp::{
+    http::{HeaderValue, Method, StatusCode},
+    reply::Response,
+};
+
+use super::responses::Diagnostic;
+
+pub const CORRELATION_HEADER: &str = "x-request-id";
+// Bytes of the error message kept in each entry
+const MAX_ERROR_LENGTH: usize = 256;
+
+// Last internal errors of the API, so they can be checked without access to the logs. Only the
+// error messages are kept, never the bodies of the requests. The lock is only taken to push or
+// copy entries and never across an await, so a blocking lock is enough.
+#[derive(Debug, Clone)]
+pub struct Diagnostics {
+    entries: Arc<Mutex<VecDeque<Diagnostic>>>,
+    capacity: usize,
+    next_id: Arc<AtomicU64>,
+}
+
+impl Diagnostics {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+            next_id: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    // The oldest entries are dropped once the buffer is full
+    pub fn record(&self, entry: Diagnostic) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.push_back(entry);
+        while entries.len() > self.capacity {
+            entries.pop_front();
+        }
+    }
+
+    // Most recent first
+    pub fn recent(&self) -> Vec<Diagnostic> {
+        self.entries.lock().unwrap().iter().rev().cloned().collect()
+    }
+
+    // Records the response if it is an internal error. Requests that did not send a correlation
+    // id get one, which is returned in the header of the response
+    pub async fn observe(
+        &self,
+        method: &Method,
+        route: &str,
+        correlation_id: Option<String>,
+        response: Response,
+    ) -> Response {
+        if response.status() != StatusCode::INTERNAL_SERVER_ERROR {
+            return response;
+        }
+        let correlation_id = correlation_id.unwrap_or_else(|| {
+            format!(
+                "{}-{}",
+                chrono::Utc::now().timestamp(),
+                self.next_id.fetch_add(1, Ordering::SeqCst)
+            )
+        });
+        let (mut parts, body) = response.into_parts();
+        let (error, body) = if body.size_hint().exact().is_some() {
+            match hyper::body::to_bytes(body).await {
+                Ok(body) => (message(&body), body.into()),
+                Err(error) => (error.to_string(), hyper::Body::empty()),
+            }
+        } else {
+            (String::from("<streamed>"), body)
+        };
+        self.record(Diagnostic {
+            timestamp: chrono::Utc::now().timestamp(),
+            method: method.to_string(),
+            route: route.to_owned(),
+            correlation_id: correlation_id.clone(),
+            error,
+        });
+        if let Ok(value) = HeaderValue::from_str(&correlation_id) {
+            parts.headers.insert(CORRELATION_HEADER, value);
+        }
+        Response::from_parts(parts, body)
+    }
+}
+
+// The detail of the problems, or the whole body of other errors
+fn message(body: &[u8]) -> String {
+    let mut message = serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|problem| problem.get("detail")?.as_str().map(str::to_owned))
+        .unwrap_or_else(|| String::from_utf8_lossy(body).into_owned());
+    if message.len() > MAX_ERROR_LENGTH {
+        let mut end = MAX_ERROR_LENGTH;
+        while !message.is_char_boundary(end) {
+            end -= 1;
+        }
+        message.truncate(end);
+    }
+    message
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+    use warp::Reply;
+
+    fn entry(route: &str) -> Diagnostic {
+        Diagnostic {
+            timestamp: 0,
+            method: String::from("GET"),
+            route: route.to_owned(),
+            correlation_id: String::from("1"),
+            error: String::from("Internal Server Error"),
+        }
+    }
+
+    #[test]
+    fn test_buffer_keeps_the_last_entries() {
+        let diagnostics = Diagnostics::new(2);
+        for route in ["/api/subjects", "/api/governances", "/api/approvals"] {
+            diagnostics.record(entry(route));
+        }
+        let routes: Vec<String> = diagnostics
+            .recent()
+            .into_iter()
+            .map(|entry| entry.route)
+            .collect();
+        assert_eq!(routes, vec!["/api/approvals", "/api/governances"]);
+    }
+
+    #[test]
+    fn test_concurrent_errors_get_their_own_id() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let diagnostics = Diagnostics::new(50);
+            let tasks: Vec<_> = (0..200)
+                .map(|i| {
+                    let diagnostics = diagnostics.clone();
+                    tokio::spawn(async move {
+                        let response = warp::reply::with_status(
+                            "Internal Server Error",
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                        )
+                        .into_response();
+                        let route = format!("/api/subjects/J{}", i);
+                        let response = diagnostics
+                            .observe(&Method::GET, &route, None, response)
+                            .await;
+                        assert!(diagnostics.recent().len() <= 50);
+                        response.headers()[CORRELATION_HEADER].clone()
+                    })
+                })
+                .collect();
+            let ids: HashSet<HeaderValue> = futures::future::join_all(tasks)
+                .await
+                .into_iter()
+                .map(Result::unwrap)
+                .collect();
+            assert_eq!(ids.len(), 200);
+            let routes: HashSet<String> = diagnostics
+                .recent()
+                .into_iter()
+                .map(|entry| entry.route)
+                .collect();
+            assert_eq!(routes.len(), 50);
+        });
+    }
+}
@@ -0,0 +1,83 @@
+use std::io::Read;
+
+use flate2::read::{MultiGzDecoder, ZlibDecoder};
+
+use super::error::Error;
+
+// Size that a request body may reach once decompressed, so that a small compressed body can
+// not exhaust the memory of the node
+pub const MAX_DECOMPRESSED_SIZE: usize = 1024 * 1024;
+
+// Undoes the codings of the Content-Encoding header, listed in the order they were applied
+pub fn decode_content(
+    content_encoding: Option<&str>,
+    body: &[u8],
+    limit: usize,
+) -> Result<Vec<u8>, Error> {
+    let mut body = body.to_vec();
+    let codings = content_encoding.unwrap_or("").split(',').rev();
+    for coding in codings.map(|coding| coding.trim().to_lowercase()) {
+        body = match coding.as_str() {
+            "" | "identity" => continue,
+            "gzip" | "x-gzip" => read_limited(MultiGzDecoder::new(&body[..]), limit)?,
+            "deflate" => read_limited(ZlibDecoder::new(&body[..]), limit)?,
+            _ => {
+                return Err(Error::UnsupportedMediaType(format!(
+                    "Unsupported Content-Encoding {}",
+                    coding
+                )))
+            }
+        };
+    }
+    Ok(body)
+}
+
+fn read_limited(decoder: impl Read, limit: usize) -> Result<Vec<u8>, Error> {
+    let mut decoded = Vec::new();
+    // One byte past the limit is enough to know that it is exceeded
+    decoder
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|error| Error::RequestError(format!("Malformed compressed body: {}", error)))?;
+    if decoded.len() > limit {
+        return Err(Error::PayloadTooLarge(limit));
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_compressed_body_is_limited() {
+        let bomb = gzip(&vec![b'0'; 64 * 1024]);
+        assert!(bomb.len() < 1024);
+        assert_eq!(
+            decode_content(Some("gzip"), &bomb, 64 * 1024)
+                .unwrap()
+                .len(),
+            64 * 1024
+        );
+        assert!(matches!(
+            decode_content(Some("gzip"), &bomb, 1024),
+            Err(Error::PayloadTooLarge(1024))
+        ));
+        assert!(matches!(
+            decode_content(Some("gzip"), b"not gzip", 1024),
+            Err(Error::RequestError(_))
+        ));
+        assert!(matches!(
+            decode_content(Some("br"), &bomb, 1024),
+            Err(Error::UnsupportedMediaType(_))
+        ));
+    }
+}
@@ -1,22 +1,303 @@
+use serde::Serialize;
 use thiserror::Error;
-use warp::reject;
+use utoipa::ToSchema;
+use warp::{
+    http::{
+        header::{CONTENT_TYPE, RETRY_AFTER},
+        HeaderValue, StatusCode,
+    },
+    reject,
+    reply::Response,
+};
+
+use super::settings::PROBLEM_CONTENT_TYPE;
+
+// Prefix of the type of the problems, followed by the name of the error. The names are stable,
+// so clients can tell the errors apart without parsing their details
+pub const PROBLEM_TYPE_PREFIX: &str = "urn:taple:problem:";
 
 #[derive(Error, Debug, Clone)]
 pub enum Error {
     #[error("Request Error {0}")]
     RequestError(String),
+    #[error("Validation Errors")]
+    ValidationErrors(Vec<FieldError>),
+    #[error("Unprocessable Entity")]
+    Unprocessable(Vec<FieldError>),
     #[error("Internal Server Error")]
     InternalServerError,
     #[error("Execution Error")]
     ExecutionError,
+    #[error("Serialization Error {0}")]
+    SerializationError(String),
     #[error("Invalid Parameters")]
     InvalidParameters,
     #[error("Not found")]
     NotFound,
+    #[error("Conflict {0}")]
+    Conflict(String),
+    #[error("Service Unavailable. Retry after {0} seconds")]
+    ServiceUnavailable(u64),
+    #[error("Node is initializing. Retry after {0} seconds")]
+    Initializing(u64),
+    #[error("Unsupported Media Type {0}")]
+    UnsupportedMediaType(String),
+    #[error("Not Acceptable {0}")]
+    NotAcceptable(String),
+    #[error("Payload Too Large. Limit of {0} bytes")]
+    PayloadTooLarge(usize),
+    #[error("Forbidden {0}")]
+    Forbidden(String),
     #[error("Not enough permissions")]
     NotEnoughPermissions,
     #[error("Unauthorized. Invalud API KEY")]
     Unauthorized,
+    // Answered as the wrapped error, with a code that tells it apart from others of its kind
+    #[error("{error}")]
+    Coded { code: String, error: Box<Error> },
 }
 
 impl reject::Reject for Error {}
+
+impl Error {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Error::InternalServerError | Error::SerializationError(_) | Error::ExecutionError => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            Error::RequestError(_) | Error::ValidationErrors(_) | Error::InvalidParameters => {
+                StatusCode::BAD_REQUEST
+            }
+            Error::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::ServiceUnavailable(_) | Error::Initializing(_) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            Error::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Error::NotAcceptable(_) => StatusCode::NOT_ACCEPTABLE,
+            Error::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Error::Forbidden(_) => StatusCode::FORBIDDEN,
+            Error::NotEnoughPermissions | Error::Unauthorized => StatusCode::UNAUTHORIZED,
+            Error::Conflict(_) => StatusCode::CONFLICT,
+            Error::NotFound => StatusCode::NOT_FOUND,
+            Error::Coded { error, .. } => error.status(),
+        }
+    }
+
+    // Name in the type of the problem and its title
+    fn kind(&self) -> (&'static str, &'static str) {
+        match self {
+            Error::InternalServerError => ("internal-server-error", "Internal Server Error"),
+            Error::SerializationError(_) => ("serialization-error", "Serialization Error"),
+            Error::ExecutionError => ("execution-error", "Execution Error"),
+            Error::RequestError(_) => ("request-error", "Request Error"),
+            Error::ValidationErrors(_) => ("validation-errors", "Validation Errors"),
+            Error::Unprocessable(_) => ("unprocessable", "Unprocessable Entity"),
+            Error::InvalidParameters => ("invalid-parameters", "Invalid Parameters"),
+            Error::ServiceUnavailable(_) => ("service-unavailable", "Service Unavailable"),
+            Error::Initializing(_) => ("initializing", "Node is initializing"),
+            Error::UnsupportedMediaType(_) => ("unsupported-media-type", "Unsupported Media Type"),
+            Error::NotAcceptable(_) => ("not-acceptable", "Not Acceptable"),
+            Error::PayloadTooLarge(_) => ("payload-too-large", "Payload Too Large"),
+            Error::Forbidden(_) => ("forbidden", "Forbidden"),
+            Error::NotEnoughPermissions => ("not-enough-permissions", "Not Allowed"),
+            Error::Conflict(_) => ("conflict", "Conflict"),
+            Error::NotFound => ("not-found", "Not Found"),
+            Error::Unauthorized => ("unauthorized", "Unauthorized"),
+            Error::Coded { error, .. } => error.kind(),
+        }
+    }
+
+    pub fn problem(&self) -> Problem {
+        if let Error::Coded { code, error } = self {
+            return Problem {
+                code: Some(code.clone()),
+                ..error.problem()
+            };
+        }
+        let (name, title) = self.kind();
+        let detail = match self {
+            Error::RequestError(message)
+            | Error::UnsupportedMediaType(message)
+            | Error::NotAcceptable(message)
+            | Error::Forbidden(message)
+            | Error::Conflict(message) => message.clone(),
+            Error::SerializationError(error) => {
+                format!("Response could not be serialized: {}", error)
+            }
+            Error::ServiceUnavailable(_) | Error::Initializing(_) | Error::PayloadTooLarge(_) => {
+                self.to_string()
+            }
+            _ => title.to_owned(),
+        };
+        let errors = match self {
+            Error::ValidationErrors(errors) | Error::Unprocessable(errors) => Some(errors.clone()),
+            _ => None,
+        };
+        Problem {
+            problem_type: format!("{}{}", PROBLEM_TYPE_PREFIX, name),
+            title: title.to_owned(),
+            status: self.status().as_u16(),
+            detail,
+            errors,
+            code: None,
+        }
+    }
+
+    // Response with the problem of the error. Clients are told when to retry the unavailable
+    // services
+    pub fn response(&self) -> Response {
+        let body = serde_json::to_vec(&self.problem()).expect("Problems are always serializable");
+        let mut response = Response::new(body.into());
+        *response.status_mut() = self.status();
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_CONTENT_TYPE));
+        if let Error::ServiceUnavailable(retry_after) | Error::Initializing(retry_after) = self {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(*retry_after));
+        }
+        response
+    }
+}
+
+// Error body of the API, as described in RFC 7807
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    // Fields of the request that are not valid, for the validation errors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<Vec<FieldError>>,
+    // Machine-readable cause of the error, for the errors of the node that share a type
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: &str) -> Self {
+        Self {
+            field: field.to_owned(),
+            message: message.to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_every_error_is_a_problem() {
+        let field = FieldError::new("namespace", "Invalid namespace");
+        let errors = [
+            (Error::InternalServerError, 500, "internal-server-error"),
+            (
+                Error::SerializationError("key must be a string".into()),
+                500,
+                "serialization-error",
+            ),
+            (Error::ExecutionError, 500, "execution-error"),
+            (
+                Error::RequestError("Missing signature".into()),
+                400,
+                "request-error",
+            ),
+            (
+                Error::ValidationErrors(vec![field.clone()]),
+                400,
+                "validation-errors",
+            ),
+            (
+                Error::Unprocessable(vec![field.clone()]),
+                422,
+                "unprocessable",
+            ),
+            (Error::InvalidParameters, 400, "invalid-parameters"),
+            (Error::ServiceUnavailable(1), 503, "service-unavailable"),
+            (Error::Initializing(5), 503, "initializing"),
+            (
+                Error::UnsupportedMediaType("text/plain".into()),
+                415,
+                "unsupported-media-type",
+            ),
+            (
+                Error::NotAcceptable("text/html".into()),
+                406,
+                "not-acceptable",
+            ),
+            (Error::PayloadTooLarge(1024), 413, "payload-too-large"),
+            (
+                Error::Forbidden("Governance not allowed".into()),
+                403,
+                "forbidden",
+            ),
+            (Error::NotEnoughPermissions, 401, "not-enough-permissions"),
+            (Error::Conflict("Already voted".into()), 409, "conflict"),
+            (Error::NotFound, 404, "not-found"),
+            (Error::Unauthorized, 401, "unauthorized"),
+        ];
+        for (error, status, name) in errors {
+            let response = error.response();
+            assert_eq!(response.status().as_u16(), status);
+            assert_eq!(response.headers()[CONTENT_TYPE], PROBLEM_CONTENT_TYPE);
+            let retries = matches!(error, Error::ServiceUnavailable(_) | Error::Initializing(_));
+            assert_eq!(response.headers().contains_key(RETRY_AFTER), retries);
+            let problem = serde_json::to_value(error.problem()).unwrap();
+            assert_eq!(problem["type"], format!("urn:taple:problem:{}", name));
+            assert_eq!(problem["status"], status);
+            assert!(problem["title"].is_string());
+            assert!(problem["detail"].is_string());
+            let has_errors = matches!(error, Error::ValidationErrors(_) | Error::Unprocessable(_));
+            let mut fields: Vec<&str> = problem
+                .as_object()
+                .unwrap()
+                .keys()
+                .map(|k| k.as_str())
+                .collect();
+            fields.sort();
+            if has_errors {
+                assert_eq!(fields, vec!["detail", "errors", "status", "title", "type"]);
+            } else {
+                assert_eq!(fields, vec!["detail", "status", "title", "type"]);
+            }
+        }
+        let problem = serde_json::to_value(Error::Unprocessable(vec![field]).problem()).unwrap();
+        assert_eq!(
+            problem["errors"],
+            json!([{ "field": "namespace", "message": "Invalid namespace" }])
+        );
+        let problem = serde_json::to_value(Error::Conflict("Already voted".into()).problem());
+        assert_eq!(problem.unwrap()["detail"], Value::from("Already voted"));
+    }
+
+    #[test]
+    fn test_coded_error_keeps_its_kind() {
+        let error = Error::Coded {
+            code: "schema-validation-error".into(),
+            error: Box::new(Error::RequestError("Invalid payload".into())),
+        };
+        assert_eq!(error.response().status(), StatusCode::BAD_REQUEST);
+        let problem = serde_json::to_value(error.problem()).unwrap();
+        assert_eq!(
+            problem,
+            json!({
+                "type": "urn:taple:problem:request-error",
+                "title": "Request Error",
+                "status": 400,
+                "detail": "Invalid payload",
+                "code": "schema-validation-error"
+            })
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use sha2::{Digest, Sha256};
+
+// Strong ETag over a set of subjects, given as (subject_id, sn) pairs in any order.
+// Any new subject or new event on an existing one changes it. Strong ETags promise the same
+// bytes, so the representation of the response, as Shaping::representation, is part of it.
+pub fn aggregate_etag(
+    subjects: impl IntoIterator<Item = (String, u64)>,
+    representation: &str,
+) -> String {
+    let mut subjects: Vec<(String, u64)> = subjects.into_iter().collect();
+    subjects.sort();
+    let mut hasher = Sha256::new();
+    hasher.update(representation.as_bytes());
+    for (subject_id, sn) in subjects {
+        hasher.update(subject_id.as_bytes());
+        hasher.update(sn.to_be_bytes());
+    }
+    format!("\"{}\"", encode(hasher))
+}
+
+// Strong ETag of a single subject in a representation. Its data only changes when a new
+// event is applied, which changes both the sn and the state hash
+pub fn state_etag(sn: u64, state_hash: &str, representation: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(state_hash.as_bytes());
+    hasher.update(representation.as_bytes());
+    format!("\"{}-{}\"", sn, encode(hasher))
+}
+
+fn encode(hasher: Sha256) -> String {
+    base64::encode_config(hasher.finalize(), base64::URL_SAFE_NO_PAD)
+}
+
+// Checks an If-None-Match header value, which may list several ETags or be a wildcard
+pub fn none_match(if_none_match: Option<&str>, etag: &str) -> bool {
+    match if_none_match {
+        None => true,
+        Some(header) => !header
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag == etag || tag.strip_prefix("W/") == Some(etag)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        settings::{CBOR_CONTENT_TYPE, JSON_CONTENT_TYPE},
+        shaping::Shaping,
+    };
+
+    const JSON: &str = "application/json;fields=;truncate=None;meta=false";
+
+    #[test]
+    fn test_etag_changes_with_new_governance() {
+        let governances = vec![("governance1".to_owned(), 0), ("governance2".to_owned(), 3)];
+        let etag = aggregate_etag(governances.clone(), JSON);
+        let mut updated = governances.clone();
+        updated.push(("governance3".to_owned(), 0));
+        assert_ne!(etag, aggregate_etag(updated, JSON));
+        // A new event in an existing governance changes it too
+        assert_ne!(
+            etag,
+            aggregate_etag(
+                vec![("governance1".to_owned(), 1), ("governance2".to_owned(), 3)],
+                JSON
+            )
+        );
+        // The order given by the node is irrelevant
+        assert_eq!(etag, aggregate_etag(governances.into_iter().rev(), JSON));
+    }
+
+    #[test]
+    fn test_unchanged_list_is_not_modified() {
+        let etag = aggregate_etag(vec![("governance1".to_owned(), 0)], JSON);
+        assert!(!none_match(Some(&etag), &etag));
+        assert!(!none_match(Some(&format!("\"other\", {}", etag)), &etag));
+        assert!(!none_match(Some("*"), &etag));
+        assert!(none_match(Some("\"other\""), &etag));
+        assert!(none_match(None, &etag));
+    }
+
+    #[test]
+    fn test_subject_is_not_modified_until_a_new_event() {
+        let etag = state_etag(0, "JrmBobio8phNFI3Fxd3pDo7NhyFi3OYYmRgds8Gpa1TY", JSON);
+        // The first request has no ETag, so the subject is sent
+        assert!(none_match(None, &etag));
+        assert!(!none_match(Some(&etag), &etag));
+        let updated = state_etag(1, "JX6KgyxQGGV0X81gsFU72klOBT39PS1R1cUEUIq8Ja0I", JSON);
+        assert!(none_match(Some(&etag), &updated));
+    }
+
+    #[test]
+    fn test_representations_have_their_own_etag() {
+        let json = Shaping {
+            content_type: JSON_CONTENT_TYPE.into(),
+            ..Default::default()
+        };
+        assert_eq!(json.representation(), JSON);
+        let etag = state_etag(0, "JrmBobio8phNFI3Fxd3pDo7NhyFi3OYYmRgds8Gpa1TY", JSON);
+        let cbor = Shaping {
+            content_type: CBOR_CONTENT_TYPE.into(),
+            ..Default::default()
+        };
+        let projected = Shaping {
+            pointers: Some(vec!["/sn".into()]),
+            ..json.clone()
+        };
+        for representation in [cbor, projected] {
+            let other = state_etag(
+                0,
+                "JrmBobio8phNFI3Fxd3pDo7NhyFi3OYYmRgds8Gpa1TY",
+                &representation.representation(),
+            );
+            // A client must not keep one representation as valid for another
+            assert!(none_match(Some(&etag), &other));
+        }
+        let enveloped = Shaping {
+            enveloped: true,
+            ..json
+        };
+        let governances = vec![("governance1".to_owned(), 0)];
+        assert_ne!(
+            aggregate_etag(governances.clone(), JSON),
+            aggregate_etag(governances, &enveloped.representation())
+        );
+    }
+}
@@ -0,0 +1,714 @@
+use commons::models::approval_signature::Acceptance;
+use json_patch::Patch;
+use jsonschema::JSONSchema;
+use serde_json::Value;
+
+use super::{
+    error::FieldError,
+    querys::{GovernanceOrder, SortOrder},
+    responses::{
+        ApprovalProgress, ApproverStats, BundledSchema, ImpactedSubject, Membership, QuorumCheck,
+        SchemaUi, UiField,
+    },
+    validation::validate_governance,
+};
+
+// Returns the JSON Schema registered in a governance for the given schema id
+pub fn find_schema<'a>(governance: &'a Value, schema_id: &str) -> Option<&'a Value> {
+    governance
+        .get("schemas")?
+        .as_array()?
+        .iter()
+        .find(|schema| schema.get("id").and_then(Value::as_str) == Some(schema_id))?
+        .get("content")
+}
+
+// Form layout of a schema. The field order and widgets can be given with the "x-ui" extension
+// ({"order": [...]} in the schema, {"widget": ...} in each property). Without them, required
+// fields go first and the widget is derived from the field type.
+pub fn schema_ui(schema_id: &str, schema: &Value) -> SchemaUi {
+    let names = |value: Option<&Value>| -> Vec<String> {
+        value
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(str::to_owned)
+            .collect()
+    };
+    let required = names(schema.get("required"));
+    let properties = schema.get("properties").and_then(Value::as_object);
+    let mut order = names(schema.pointer("/x-ui/order"));
+    if order.is_empty() {
+        order = required.clone();
+    }
+    // Fields left out of the order follow, in the order of the properties
+    order.retain(|name| properties.map_or(false, |properties| properties.contains_key(name)));
+    for name in properties
+        .into_iter()
+        .flat_map(|properties| properties.keys())
+    {
+        if !order.contains(name) {
+            order.push(name.clone());
+        }
+    }
+    let fields = order
+        .into_iter()
+        .map(|name| {
+            let property = &properties.unwrap()[&name];
+            let field_type = match property.get("type") {
+                Some(Value::String(field_type)) => field_type.clone(),
+                // Nullable fields are written as ["type", "null"]
+                Some(Value::Array(types)) => types
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .find(|field_type| *field_type != "null")
+                    .unwrap_or("any")
+                    .to_owned(),
+                _ => String::from("any"),
+            };
+            let hints: serde_json::Map<String, Value> = property
+                .as_object()
+                .into_iter()
+                .flatten()
+                .filter(|(keyword, _)| keyword.starts_with("x-"))
+                .map(|(keyword, value)| (keyword.clone(), value.clone()))
+                .collect();
+            let widget = match property.pointer("/x-ui/widget").and_then(Value::as_str) {
+                Some(widget) => widget.to_owned(),
+                None if property.get("enum").is_some() => String::from("select"),
+                None => match field_type.as_str() {
+                    "integer" | "number" => "number",
+                    "boolean" => "checkbox",
+                    "object" => "fieldset",
+                    "array" => "list",
+                    _ => "text",
+                }
+                .to_owned(),
+            };
+            UiField {
+                required: required.contains(&name),
+                name,
+                field_type,
+                widget,
+                hints: Value::Object(hints),
+            }
+        })
+        .collect();
+    SchemaUi {
+        schema_id: schema_id.to_owned(),
+        fields,
+    }
+}
+
+// Checks the current properties of each subject against the schemas of a proposed governance.
+// Subjects are given as (subject_id, schema_id, properties) tuples.
+pub fn governance_impact(
+    proposed: &Value,
+    subjects: Vec<(String, String, String)>,
+) -> Vec<ImpactedSubject> {
+    let mut impacted = Vec::new();
+    for (subject_id, schema_id, properties) in subjects {
+        let reason = match find_schema(proposed, &schema_id) {
+            None => Some(format!("Schema {} is not defined", schema_id)),
+            Some(schema) => validate_properties(schema, &properties).err(),
+        };
+        if let Some(reason) = reason {
+            impacted.push(ImpactedSubject {
+                subject_id,
+                schema_id,
+                reason,
+            });
+        }
+    }
+    impacted
+}
+
+pub fn validate_properties(schema: &Value, properties: &str) -> Result<(), String> {
+    let compiled = JSONSchema::compile(schema).map_err(|e| format!("Invalid schema: {}", e))?;
+    let properties: Value = serde_json::from_str(properties)
+        .map_err(|e| format!("Properties are not valid JSON: {}", e))?;
+    let result = compiled.validate(&properties).map_err(|errors| {
+        errors
+            .map(|error| error.to_string())
+            .collect::<Vec<String>>()
+            .join("; ")
+    });
+    result
+}
+
+// Applies a JSON Patch to the properties of a governance and checks that the result is
+// still a valid governance
+pub fn patch_governance(properties: &str, patch: &Patch) -> Result<Value, Vec<FieldError>> {
+    let mut governance: Value = serde_json::from_str(properties)
+        .map_err(|_| vec![FieldError::new("properties", "are not valid JSON")])?;
+    json_patch::patch(&mut governance, patch)
+        .map_err(|error| vec![FieldError::new("patch", &error.to_string())])?;
+    validate_governance(&governance)?;
+    Ok(governance)
+}
+
+// Counts the votes cast by each approver. Every current member of the governance is listed,
+// even without votes, followed by former members that voted in the past.
+pub fn approver_stats(governance: &Value, votes: Vec<(String, Acceptance)>) -> Vec<ApproverStats> {
+    let mut stats: Vec<ApproverStats> = governance
+        .get("members")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|member| {
+            Some(ApproverStats {
+                key: member.get("key")?.as_str()?.to_owned(),
+                member: member.get("id").and_then(Value::as_str).map(str::to_owned),
+                approvals: 0,
+                rejections: 0,
+            })
+        })
+        .collect();
+    for (signer, acceptance) in votes {
+        let index = match stats.iter().position(|approver| approver.key == signer) {
+            Some(index) => index,
+            None => {
+                stats.push(ApproverStats {
+                    key: signer,
+                    member: None,
+                    approvals: 0,
+                    rejections: 0,
+                });
+                stats.len() - 1
+            }
+        };
+        match acceptance {
+            Acceptance::Accept => stats[index].approvals += 1,
+            Acceptance::Reject => stats[index].rejections += 1,
+        }
+    }
+    stats
+}
+
+// Every member of the governance is an approver of its subjects, so an event needs the acceptance
+// of all of them. Votes of keys that are no longer members are ignored.
+pub fn approval_progress(
+    governance: &Value,
+    approved: bool,
+    votes: Vec<(String, Acceptance)>,
+) -> ApprovalProgress {
+    let members: Vec<&str> = governance
+        .get("members")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|member| member.get("key")?.as_str())
+        .collect();
+    let voted = |key: &str| votes.iter().any(|(signer, _)| signer == key);
+    let received = members
+        .iter()
+        .filter(|key| {
+            votes.iter().any(|(signer, acceptance)| {
+                signer == *key && matches!(acceptance, Acceptance::Accept)
+            })
+        })
+        .count();
+    let missing_approvers = if approved {
+        Vec::new()
+    } else {
+        members
+            .iter()
+            .filter(|key| !voted(key))
+            .map(|key| key.to_string())
+            .collect()
+    };
+    ApprovalProgress {
+        approved,
+        required: members.len(),
+        received,
+        missing_approvers,
+    }
+}
+
+// Checks the votes recorded in an event against the governance in force when it was approved.
+// As in approval_progress, the quorum is the acceptance of every member. Each member counts once,
+// and the votes of keys that were not members at that version are not valid.
+pub fn quorum_check(governance: &Value, votes: Vec<(String, Acceptance)>) -> QuorumCheck {
+    let members: Vec<&str> = governance
+        .get("members")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|member| member.get("key")?.as_str())
+        .collect();
+    let valid_approvals = members
+        .iter()
+        .filter(|key| {
+            votes.iter().any(|(signer, acceptance)| {
+                signer == *key && matches!(acceptance, Acceptance::Accept)
+            })
+        })
+        .count();
+    QuorumCheck {
+        satisfied: valid_approvals == members.len(),
+        required: members.len(),
+        valid_approvals,
+    }
+}
+
+// Every schema of the given governances, as (governance_id, sn, properties) triples. Schemas
+// are sorted by governance and keep the order in which their governance declares them.
+pub fn schema_bundle(
+    governances: impl IntoIterator<Item = (String, u64, Value)>,
+) -> Vec<BundledSchema> {
+    let mut governances: Vec<(String, u64, Value)> = governances.into_iter().collect();
+    governances.sort_by(|a, b| a.0.cmp(&b.0));
+    governances
+        .into_iter()
+        .flat_map(|(governance_id, sn, governance)| {
+            let schemas = governance
+                .get("schemas")
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+            schemas.into_iter().filter_map(move |schema| {
+                Some(BundledSchema {
+                    governance_id: governance_id.clone(),
+                    governance_version: sn,
+                    schema_id: schema.get("id")?.as_str()?.to_owned(),
+                    schema: schema.get("content")?.clone(),
+                })
+            })
+        })
+        .collect()
+}
+
+// Member entries of the governances whose key is one of the node identities, in the order of
+// the governances and of their member lists
+pub fn memberships(
+    governances: impl IntoIterator<Item = (String, Value)>,
+    is_node_key: impl Fn(&str) -> bool,
+) -> Vec<Membership> {
+    let mut found = Vec::new();
+    for (governance_id, governance) in governances {
+        let members = governance.get("members").and_then(Value::as_array);
+        for member in members.into_iter().flatten() {
+            let Some(key) = member.get("key").and_then(Value::as_str) else {
+                continue;
+            };
+            if !is_node_key(key) {
+                continue;
+            }
+            found.push(Membership {
+                governance_id: governance_id.clone(),
+                member_id: member
+                    .get("id")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_owned(),
+                key: key.to_owned(),
+                description: member
+                    .get("description")
+                    .and_then(Value::as_str)
+                    .map(str::to_owned),
+                tags: member.get("tags").cloned().unwrap_or(Value::Null),
+            });
+        }
+    }
+    found
+}
+
+// Most recent items first, keeping at most `limit` of them
+pub fn latest_activity<T>(mut events: Vec<T>, limit: usize, timestamp_of: fn(&T) -> i64) -> Vec<T> {
+    events.sort_by_key(|event| std::cmp::Reverse(timestamp_of(event)));
+    events.truncate(limit);
+    events
+}
+
+// Sorts governances by the requested field. Ties on sn fall back to the governance id, so the
+// order is the same on every call
+pub fn sort_governances<T>(
+    governances: &mut [T],
+    order_by: GovernanceOrder,
+    order: SortOrder,
+    key_of: fn(&T) -> (String, u64),
+) {
+    governances.sort_by(|a, b| {
+        let ((a_id, a_sn), (b_id, b_sn)) = (key_of(a), key_of(b));
+        let ordering = match order_by {
+            GovernanceOrder::GovernanceId => a_id.cmp(&b_id),
+            GovernanceOrder::Sn => a_sn.cmp(&b_sn).then_with(|| a_id.cmp(&b_id)),
+        };
+        match order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::etag::aggregate_etag;
+
+    #[test]
+    fn test_governances_order_is_stable() {
+        let governances = vec![
+            ("Jb".to_string(), 3),
+            ("Ja".to_string(), 3),
+            ("Jc".to_string(), 0),
+        ];
+        let key_of: fn(&(String, u64)) -> (String, u64) = |governance| governance.clone();
+        let mut first = governances.clone();
+        sort_governances(&mut first, GovernanceOrder::Sn, SortOrder::Asc, key_of);
+        let mut second = governances.clone();
+        second.reverse();
+        sort_governances(&mut second, GovernanceOrder::Sn, SortOrder::Asc, key_of);
+        assert_eq!(first, second);
+        assert_eq!(first[0].0, "Jc");
+        assert_eq!(first[1].0, "Ja");
+        let mut descending = governances.clone();
+        sort_governances(
+            &mut descending,
+            GovernanceOrder::Sn,
+            SortOrder::Desc,
+            key_of,
+        );
+        first.reverse();
+        assert_eq!(descending, first);
+        let mut by_id = governances;
+        sort_governances(
+            &mut by_id,
+            GovernanceOrder::GovernanceId,
+            SortOrder::Asc,
+            key_of,
+        );
+        let ids: Vec<&str> = by_id
+            .iter()
+            .map(|governance| governance.0.as_str())
+            .collect();
+        assert_eq!(ids, vec!["Ja", "Jb", "Jc"]);
+    }
+
+    #[test]
+    fn test_schema_bundle_has_every_schema() {
+        let governance = |schemas: Vec<&str>| {
+            serde_json::json!({
+                "members": [],
+                "schemas": schemas
+                    .into_iter()
+                    .map(|id| serde_json::json!({ "id": id, "content": { "type": "object" } }))
+                    .collect::<Vec<Value>>()
+            })
+        };
+        let governances = vec![
+            ("governance2".to_owned(), 0, governance(vec!["Prueba"])),
+            (
+                "governance1".to_owned(),
+                3,
+                governance(vec!["Sensor", "Prueba"]),
+            ),
+        ];
+        let bundle = schema_bundle(governances.clone());
+        let schemas: Vec<(&str, u64, &str)> = bundle
+            .iter()
+            .map(|schema| {
+                (
+                    schema.governance_id.as_str(),
+                    schema.governance_version,
+                    schema.schema_id.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            schemas,
+            vec![
+                ("governance1", 3, "Sensor"),
+                ("governance1", 3, "Prueba"),
+                ("governance2", 0, "Prueba")
+            ]
+        );
+        // A schema change is a new event of its governance, so the ETag of the bundle changes
+        let etag_of = |governances: &Vec<(String, u64, Value)>| {
+            aggregate_etag(governances.iter().map(|(id, sn, _)| (id.clone(), *sn)))
+        };
+        let mut changed = governances.clone();
+        changed[0] = (
+            "governance2".to_owned(),
+            1,
+            governance(vec!["Prueba", "Sensor"]),
+        );
+        assert_eq!(schema_bundle(changed.clone()).len(), 4);
+        assert_ne!(etag_of(&governances), etag_of(&changed));
+    }
+
+    #[test]
+    fn test_only_governances_with_the_node_as_member() {
+        let governance = |keys: &[&str]| {
+            let members: Vec<Value> = keys
+                .iter()
+                .enumerate()
+                .map(|(index, key)| {
+                    serde_json::json!({
+                        "id": format!("Company{}", index),
+                        "key": key,
+                        "description": "Sede en España",
+                        "tags": { "role": "approver" }
+                    })
+                })
+                .collect();
+            serde_json::json!({ "members": members, "schemas": [] })
+        };
+        let governances = vec![
+            ("governance1".to_owned(), governance(&["other_key"])),
+            (
+                "governance2".to_owned(),
+                governance(&["other_key", "node_key"]),
+            ),
+            ("governance3".to_owned(), governance(&[])),
+        ];
+        let found = memberships(governances, |key| key == "node_key");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].governance_id, "governance2");
+        assert_eq!(found[0].member_id, "Company1");
+        assert_eq!(found[0].tags, serde_json::json!({ "role": "approver" }));
+    }
+
+    #[test]
+    fn test_schema_ui_hints_are_surfaced() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "x-ui": { "order": ["temperatura", "localizacion"] },
+            "properties": {
+                "localizacion": {
+                    "type": "string",
+                    "x-ui": { "widget": "map", "placeholder": "Ciudad" }
+                },
+                "temperatura": { "type": "integer" },
+                "activo": { "type": ["boolean", "null"] }
+            },
+            "required": ["localizacion"]
+        });
+        let ui = schema_ui("Prueba", &schema);
+        let fields: Vec<(&str, &str, &str, bool)> = ui
+            .fields
+            .iter()
+            .map(|f| {
+                (
+                    f.name.as_str(),
+                    f.field_type.as_str(),
+                    f.widget.as_str(),
+                    f.required,
+                )
+            })
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("temperatura", "integer", "number", false),
+                ("localizacion", "string", "map", true),
+                ("activo", "boolean", "checkbox", false),
+            ]
+        );
+        assert_eq!(
+            ui.fields[1].hints,
+            serde_json::json!({ "x-ui": { "widget": "map", "placeholder": "Ciudad" } })
+        );
+        // Without hints, required fields go first
+        let schema = serde_json::json!({
+            "properties": { "a": { "type": "string" }, "b": { "enum": [1, 2] } },
+            "required": ["b"]
+        });
+        let names: Vec<String> = schema_ui("Prueba", &schema)
+            .fields
+            .into_iter()
+            .map(|f| format!("{}:{}", f.name, f.widget))
+            .collect();
+        assert_eq!(names, vec!["b:select", "a:text"]);
+    }
+
+    #[test]
+    fn test_patch_adds_member() {
+        let properties = r#"{"members":[],"schemas":[]}"#;
+        let patch: Patch = serde_json::from_value(serde_json::json!([
+            {
+                "op": "add",
+                "path": "/members/-",
+                "value": { "id": "Company", "key": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w" }
+            }
+        ]))
+        .unwrap();
+        let governance = patch_governance(properties, &patch).unwrap();
+        assert_eq!(governance["members"][0]["id"], "Company");
+    }
+
+    #[test]
+    fn test_patch_breaking_governance_is_rejected() {
+        let properties = r#"{"members":[],"schemas":[]}"#;
+        let patch: Patch =
+            serde_json::from_value(serde_json::json!([{ "op": "remove", "path": "/members" }]))
+                .unwrap();
+        assert!(patch_governance(properties, &patch).is_err());
+    }
+
+    #[test]
+    fn test_activity_of_several_subjects_is_ordered_by_time() {
+        // (subject_id, timestamp) of the events of each governed subject
+        let events = vec![
+            ("subject1", 100),
+            ("subject1", 400),
+            ("subject2", 200),
+            ("subject2", 500),
+            ("subject3", 300),
+        ];
+        let activity = latest_activity(events, 4, |event| event.1);
+        assert_eq!(
+            activity,
+            vec![
+                ("subject2", 500),
+                ("subject1", 400),
+                ("subject3", 300),
+                ("subject2", 200)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_approver_stats() {
+        let governance = serde_json::json!({
+            "members": [
+                { "id": "Company", "key": "company_key" },
+                { "id": "Auditor", "key": "auditor_key" },
+                { "id": "Idle", "key": "idle_key" }
+            ],
+            "schemas": []
+        });
+        let stats = approver_stats(
+            &governance,
+            vec![
+                ("company_key".into(), Acceptance::Accept),
+                ("auditor_key".into(), Acceptance::Reject),
+                ("company_key".into(), Acceptance::Accept),
+                ("auditor_key".into(), Acceptance::Accept),
+                ("company_key".into(), Acceptance::Reject),
+                ("former_key".into(), Acceptance::Accept),
+            ],
+        );
+        let counts: Vec<(&str, u64, u64)> = stats
+            .iter()
+            .map(|s| (s.key.as_str(), s.approvals, s.rejections))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![
+                ("company_key", 2, 1),
+                ("auditor_key", 1, 1),
+                ("idle_key", 0, 0),
+                ("former_key", 1, 0),
+            ]
+        );
+        assert_eq!(stats[0].member.as_deref(), Some("Company"));
+        assert_eq!(stats[3].member, None);
+    }
+
+    #[test]
+    fn test_tightened_schema_flags_subject() {
+        let proposed = serde_json::json!({
+            "members": [],
+            "schemas": [{
+                "id": "prueba",
+                "content": {
+                    "type": "object",
+                    "required": ["a", "b"],
+                    "properties": { "a": {"type": "string"}, "b": {"type": "integer"} }
+                }
+            }]
+        });
+        let impacted = governance_impact(
+            &proposed,
+            vec![
+                (
+                    "valid".into(),
+                    "prueba".into(),
+                    r#"{"a":"69","b":1}"#.into(),
+                ),
+                ("invalid".into(), "prueba".into(), r#"{"a":"69"}"#.into()),
+            ],
+        );
+        assert_eq!(impacted.len(), 1);
+        assert_eq!(impacted[0].subject_id, "invalid");
+    }
+
+    #[test]
+    fn test_pending_event_lists_missing_approvers() {
+        let governance = serde_json::json!({
+            "members": [
+                { "id": "Company", "key": "company_key" },
+                { "id": "Auditor", "key": "auditor_key" },
+                { "id": "Idle", "key": "idle_key" }
+            ],
+            "schemas": []
+        });
+        let progress = approval_progress(
+            &governance,
+            false,
+            vec![
+                ("company_key".into(), Acceptance::Accept),
+                ("auditor_key".into(), Acceptance::Reject),
+                ("former_key".into(), Acceptance::Accept),
+            ],
+        );
+        assert_eq!(
+            progress,
+            ApprovalProgress {
+                approved: false,
+                required: 3,
+                received: 1,
+                missing_approvers: vec!["idle_key".into()],
+            }
+        );
+        let progress = approval_progress(&governance, true, vec![]);
+        assert!(progress.approved);
+        assert!(progress.missing_approvers.is_empty());
+    }
+
+    #[test]
+    fn test_quorum_of_the_governance_version() {
+        let governance = serde_json::json!({
+            "members": [
+                { "id": "Company", "key": "company_key" },
+                { "id": "Auditor", "key": "auditor_key" }
+            ],
+            "schemas": []
+        });
+        let check = quorum_check(
+            &governance,
+            vec![
+                ("company_key".into(), Acceptance::Accept),
+                ("auditor_key".into(), Acceptance::Accept),
+            ],
+        );
+        assert_eq!(
+            check,
+            QuorumCheck {
+                satisfied: true,
+                required: 2,
+                valid_approvals: 2,
+            }
+        );
+        // Repeated votes and votes of keys that were not members do not make up for a missing one
+        let check = quorum_check(
+            &governance,
+            vec![
+                ("company_key".into(), Acceptance::Accept),
+                ("company_key".into(), Acceptance::Accept),
+                ("former_key".into(), Acceptance::Accept),
+                ("auditor_key".into(), Acceptance::Reject),
+            ],
+        );
+        assert_eq!(
+            check,
+            QuorumCheck {
+                satisfied: false,
+                required: 2,
+                valid_approvals: 1,
+            }
+        );
+    }
+}
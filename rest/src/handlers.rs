@@ -1,14 +1,73 @@
-use commons::models::{approval_signature::Acceptance, event::Event};
+use commons::{
+    identifier::Derivable,
+    models::{
+        approval_signature::Acceptance, event::Event, event_request::EventRequestType,
+        state::SubjectData,
+    },
+};
+use futures::{StreamExt, TryStreamExt};
 use serde::Serialize;
 use warp::Rejection;
 
-use crate::bodys::PostEventRequestBody;
-use core::{ApiError, ApiModuleInterface, NodeAPI};
+use crate::bodys::{EventRequestTypeBody, PostEventRequestBody};
+use core::{
+    event_request::RequestPayload, ApiError, ApiModuleInterface, CreateRequest, NodeAPI, StateType,
+};
 
 use super::{
-    bodys::{PostEventBody, PostGovernanceBody, PostSubjectBody, PutVoteBody},
-    error::Error,
-    querys::{GetAllSubjectsQuery, GetEventsQuery, GetSignaturesQuery},
+    alias::resolve_alias,
+    audit::AuditLog,
+    bodys::{
+        Payload, PostEventBody, PostGovernanceBody, PostMaintenanceBody, PostSimulatedBatchBody,
+        PostSubjectBody, PutVoteBody, PutVotesBody,
+    },
+    catalog::RouteCatalog,
+    diagnostics::Diagnostics,
+    error::{Error, FieldError},
+    etag::{aggregate_etag, none_match, state_etag},
+    governance::{
+        approval_progress, approver_stats, find_schema, governance_impact, latest_activity,
+        memberships, patch_governance, quorum_check, schema_bundle, schema_ui, sort_governances,
+        validate_properties,
+    },
+    identity::NodeIdentity,
+    maintenance::Maintenance,
+    metrics::{record_api_error, variant_name},
+    prefer::ReturnPreference,
+    projection::{flatten_properties, parse_fields, project_event},
+    querys::{
+        ActivityQuery, AsOfQuery, CompareQuery, EventsFormat, GetAllGovernancesQuery,
+        GetAllSubjectsQuery, GetEventQuery, GetEventsQuery, GetEventsStreamQuery,
+        GetSignaturesQuery, GetSubjectQuery, GovernanceOrder, PendingRequestsQuery, ResolveQuery,
+        ResolvedRequestQuery, SearchSubjectsQuery, SimulatedBatchQuery, SnapshotQuery, SortOrder,
+        TransitionsQuery,
+    },
+    replay::{
+        apply_payload, event_payload, replay_states, simulate_payloads, sn_as_of, transitions,
+        CompactEncoder,
+    },
+    responses::{
+        ApprovalProgress, ApprovalSignature, BatchVoteResult, BundledSchema, Diagnostic,
+        GovernanceRevision, GovernanceVersion, KeyEncodings, LastUpdated, Membership,
+        NamespaceCount, PendingCount, QuorumCheck, RejectedRequest, RequestResolution,
+        RequestSignatures, RequestSummary, RequestTimeline, SchemaPeriod, SimulatedBatch,
+        StateAsOf, SubjectComparison, SubjectState, TimelineEntry, Transition, ValidationCheck,
+        ValidationReport,
+    },
+    search::property_matches,
+    settings::RestSettings,
+    shaping::{missing_sns, Shaping, MISSING_SNS_HEADER, OUT_OF_RANGE_HEADER, TOTAL_COUNT_HEADER},
+    snapshot::SubjectSnapshot,
+    stream::{
+        listing_page, page_of, paged_stream, subject_events, LISTING_BATCH, SSE_KEEP_ALIVE_INTERVAL,
+    },
+    validation::{
+        check_create_subject, check_external_request, check_governance_duplicates,
+        validate_create_request, validate_governance, validate_governance_payload,
+        validate_identifier, validate_key, validate_namespace, verify_signature,
+        GOVERNANCE_SCHEMA_ID,
+    },
+    votes::{check_vote, VoteCheck, VotesInFlight},
 };
 
 #[utoipa::path(
@@ -19,10 +78,12 @@ use super::{
     context_path = "/api",
     security(("api_key" = [])),
     params(
-        ("id" = String, Path, description = "Subject's unique id")
+        ("id" = String, Path, description = "Subject's unique id"),
+        ("include_event_count" = Option<bool>, Query, description = "Add the number of events of the subject as event_count"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a previous response in the same representation. The subject is only sent if a new event was applied since then"),
     ),
     responses(
-        (status = 200, description = "Subject Data successfully retrieved", body = SubjectData,
+        (status = 200, description = "Subject Data successfully retrieved, with an ETag taken from its sn, its state hash and the representation of the response", body = SubjectData,
         example = json!(
             {
                 "subject_id": "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
@@ -35,6 +96,7 @@ use super::{
                 "properties": "{\"localizacion\":\"España\",\"temperatura\":10}"
             }
         )),
+        (status = 304, description = "The subject has not changed since the ETag in If-None-Match"),
         (status = 400, description = "Bad Request"),
         (status = 401, description = "Unauthorized"),
         (status = 404, description = "Not Found"),
@@ -45,50 +107,263 @@ pub async fn get_subject_handler(
     id: String,
     node: NodeAPI,
     _header: String,
+    if_none_match: Option<String>,
+    parameters: GetSubjectQuery,
+    shaping: Shaping,
 ) -> Result<Box<dyn warp::Reply>, Rejection> {
     if id.is_empty() {
         return Err(warp::reject::custom(Error::RequestError(
             "Error in query parameter".to_owned(),
         )));
     }
-    let response = node.get_subject(id).await;
-    handle_data(response)
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let subject = match node.get_subject(id).await {
+        Ok(subject) => subject,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let state = match subject_state(&node, subject.clone()).await {
+        Ok(state) => state,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let include_event_count = parameters.include_event_count.unwrap_or(false);
+    let representation = format!(
+        "{};include_event_count={}",
+        shaping.representation(),
+        include_event_count
+    );
+    let etag = state_etag(state.sn, &state.state_hash, &representation);
+    if !none_match(if_none_match.as_deref(), &etag) {
+        return Ok(Box::new(warp::reply::with_header(
+            warp::http::StatusCode::NOT_MODIFIED,
+            warp::http::header::ETAG,
+            etag,
+        )));
+    }
+    let reply = if include_event_count {
+        // The chain of a subject has no gaps, its events go from 0 to its sn
+        let event_count = subject.sn + 1;
+        let mut data = serde_json::to_value(&subject).map_err(serialization_rejection)?;
+        data["event_count"] = event_count.into();
+        handle_data(Ok(data))?
+    } else {
+        handle_data(Ok(subject))?
+    };
+    Ok(Box::new(warp::reply::with_header(
+        reply,
+        warp::http::header::ETAG,
+        etag,
+    )))
 }
 
 #[utoipa::path(
     get,
-    path = "/subjects",
+    path = "/subjects/{id}/governance-version",
+    operation_id = "Get the Governance version of a Subject",
     tag = "Subjects",
-    operation_id = "Get All Subjects Data",
     context_path = "/api",
     security(("api_key" = [])),
     params(
-        ("from" = Option<usize>, Query, description = "Number of initial subject"),
-        ("quantity" = Option<usize>, Query, description = "Quantity of subjects requested")
+        ("id" = String, Path, description = "Subject's unique id")
+    ),
+    responses(
+        (status = 200, description = "Version of the governance the subject currently operates under, taken from its latest event", body = GovernanceVersion,
+        example = json!(
+            {
+                "governance_version": 0
+            }
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_governance_version_handler(
+    id: String,
+    node: NodeAPI,
+    _header: String,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let subject = match node.get_subject(id.clone()).await {
+        Ok(subject) => subject,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    // Only the latest event is read
+    let events = match node
+        .get_event_of_subject(id, Some(subject.sn as i64), Some(1))
+        .await
+    {
+        Ok(events) => events,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let version = GovernanceVersion::latest(events.iter().map(|event| {
+        (
+            event.event_content.sn,
+            event.event_content.metadata.governance_version,
+        )
+    }));
+    match version {
+        Some(version) => handle_data(Ok(version)),
+        None => Err(warp::reject::custom(Error::InternalServerError)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/subjects/{id}/last-updated",
+    operation_id = "Get the time a Subject was last updated",
+    tag = "Subjects",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Subject's unique id")
+    ),
+    responses(
+        (status = 200, description = "Sn and request timestamp of the latest event of the subject", body = LastUpdated,
+        example = json!(
+            {
+                "sn": 1,
+                "timestamp": 1671706794
+            }
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_last_updated_handler(
+    id: String,
+    node: NodeAPI,
+    _header: String,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let subject = match node.get_subject(id.clone()).await {
+        Ok(subject) => subject,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    // Only the latest event is read
+    let events = match node
+        .get_event_of_subject(id, Some(subject.sn as i64), Some(1))
+        .await
+    {
+        Ok(events) => events,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let last = LastUpdated::latest(events.iter().map(|event| {
+        (
+            event.event_content.sn,
+            event.event_content.event_request.timestamp,
+        )
+    }));
+    match last {
+        Some(last) => handle_data(Ok(last)),
+        None => Err(warp::reject::custom(Error::InternalServerError)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/subjects/{id}/as-of",
+    operation_id = "Get the state of a Subject as of a timestamp",
+    tag = "Subjects",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Subject's unique id"),
+        ("ts" = i64, Query, description = "Unix timestamp, in seconds"),
+    ),
+    responses(
+        (status = 200, description = "State replayed up to the highest sn whose request timestamp is at or before ts. Request timestamps do not always grow with the sn, so an event requested after ts is included when a later one was requested before it", body = StateAsOf,
+        example = json!(
+            {
+                "sn": 1,
+                "timestamp": 1671706794,
+                "properties": {
+                    "localizacion": "España",
+                    "temperatura": 10
+                }
+            }
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found, or the subject had no events by then"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_state_as_of_handler(
+    id: String,
+    node: NodeAPI,
+    _header: String,
+    parameters: AsOfQuery,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let events = match node.get_event_of_subject(id, None, None).await {
+        Ok(events) => events,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let requested = events.iter().map(|event| {
+        (
+            event.event_content.sn,
+            event.event_content.event_request.timestamp,
+        )
+    });
+    let Some(sn) = sn_as_of(requested, parameters.ts) else {
+        return Err(warp::reject::custom(Error::NotFound));
+    };
+    let history: Vec<&Event> = events
+        .iter()
+        .filter(|event| event.event_content.sn <= sn)
+        .collect();
+    let mut states = replay_states(history.iter().map(|event| event_payload(event)))
+        .map_err(|_| warp::reject::custom(Error::ExecutionError))?;
+    let (Some(properties), Some(event)) = (states.pop(), history.last()) else {
+        return Err(warp::reject::custom(Error::ExecutionError));
+    };
+    handle_data(Ok(StateAsOf {
+        sn,
+        timestamp: event.event_content.event_request.timestamp,
+        properties,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/subjects/{id}/transitions",
+    operation_id = "Get the state transitions of a Subject",
+    tag = "Subjects",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Subject's unique id"),
+        ("from" = Option<u64>, Query, description = "SN of the first transition. Defaults to 1, as the genesis event has no previous state. Past the last event an empty list is returned with the X-Out-Of-Range header"),
+        ("quantity" = Option<u64>, Query, description = "Quantity of transitions requested"),
     ),
     responses(
-        (status = 200, description = "Subjects Data successfully retrieved", body = [SubjectData],
+        (status = 200, description = "JSON Patch from the state left by the previous event to the one left by each event after the genesis. The subject is replayed once from its genesis up to the last transition of the page, so applying every patch in order to the state of the genesis gives the current state", body = [Transition],
         example = json!(
             [
                 {
-                    "subject_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
-                    "governance_id": "",
-                    "sn": 0,
-                    "public_key": "E2tlKVr6wA2GZKoSZi_dwIuz2TVUTCCDpOOwiE2SJbWc",
-                    "namespace": "",
-                    "schema_id": "",
-                    "owner": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
-                    "properties": "{\"members\":[{\"description\":\"Sede en España\",\"id\":\"Compañía1\",\"key\":\"EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w\",\"tags\":{}},{\"description\":\"Sede en Inglaterra\",\"id\":\"Compañía2\",\"key\":\"ECQnl-h1vEWmu-ZlPuweR3N1x6SUImyVdPrCLmnJJMyU\",\"tags\":{}}],\"schemas\":[{\"content\":{\"additionalProperties\":false,\"properties\":{\"localizacion\":{\"type\":\"string\"},\"temperatura\":{\"type\":\"integer\"}},\"required\":[\"temperatura\",\"localizacion\"],\"type\":\"object\"},\"id\":\"Prueba\",\"tags\":{}}]}"
-                },
-                {
-                    "subject_id": "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
-                    "governance_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
-                    "sn": 0,
-                    "public_key": "ELZ_b-kZzdPykcYuRNC2ZZe_2lCTCUoo60GXfR4cuXMw",
-                    "namespace": "namespace1",
-                    "schema_id": "Prueba",
-                    "owner": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
-                    "properties": "{\"localizacion\":\"España\",\"temperatura\":10}"
+                    "sn": 1,
+                    "patch": [
+                        { "op": "replace", "path": "/localizacion", "value": "Argentina" },
+                        { "op": "replace", "path": "/temperatura", "value": -3 }
+                    ]
                 }
             ]
         )),
@@ -98,207 +373,2252 @@ pub async fn get_subject_handler(
         (status = 500, description = "Internal Server Error"),
     )
 )]
-pub async fn get_all_subjects_handler(
+pub async fn get_transitions_handler(
+    id: String,
     node: NodeAPI,
     _header: String,
-    parameters: GetAllSubjectsQuery,
+    parameters: TransitionsQuery,
 ) -> Result<Box<dyn warp::Reply>, Rejection> {
-    fn convert_to_usize(data: Option<String>) -> Option<usize> {
-        if data.is_some() {
-            let tmp = data.unwrap();
-            return Some(tmp.parse::<usize>().unwrap());
-        }
-        None
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
     }
-    let data = node
-        .get_all_subjects("namespace1".into(), parameters.from, parameters.quantity)
-        .await;
-    handle_data(data)
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let subject = match node.get_subject(id.clone()).await {
+        Ok(subject) => subject,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let from = parameters.from.unwrap_or(1).max(1);
+    if from > subject.sn {
+        return Ok(out_of_range(handle_data(Ok(Vec::<Transition>::new()))?));
+    }
+    let last = match parameters.quantity {
+        None => subject.sn,
+        Some(0) => return handle_data(Ok(Vec::<Transition>::new())),
+        Some(quantity) => subject.sn.min(from.saturating_add(quantity - 1)),
+    };
+    let history = match node
+        .get_event_of_subject(id, Some(0), Some(last as i64 + 1))
+        .await
+    {
+        Ok(history) => history,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    // Every event up to the last transition is needed to replay the states
+    if history.len() as u64 != last + 1 {
+        return Err(warp::reject::custom(Error::ExecutionError));
+    }
+    let states = replay_states(history.iter().map(event_payload))
+        .map_err(|_| warp::reject::custom(Error::ExecutionError))?;
+    handle_data(Ok(transitions(&states, from)))
 }
 
 #[utoipa::path(
-    post,
-    path = "/subjects",
+    get,
+    path = "/subjects/compare",
+    operation_id = "Compare the properties of two Subjects",
     tag = "Subjects",
-    operation_id = "Create a new Subject",
     context_path = "/api",
     security(("api_key" = [])),
-    request_body(content = PostSubjectBody, content_type = "application/json", description = "Schema and governance specification of the new subject. It also must contain the initial payload"),
+    params(
+        ("a" = String, Query, description = "Subject whose properties are the origin of the patch"),
+        ("b" = String, Query, description = "Subject whose properties are the target of the patch"),
+    ),
     responses(
-        (status = 202, description = "Subject Created", body = Event,
+        (status = 200, description = "JSON Patch that turns the current properties of subject a into those of subject b. Comparing subjects of different schemas is allowed, but it is reported in the warnings", body = SubjectComparison,
         example = json!(
             {
-                "event_content": {
-                    "subject_id": "J4LS4VgGo3SDmC4F4hFFBkw8Kgv4ItpYgjUbw0gDxNk8",
-                    "event_request": {
-                        "request": {
-                            "Create": {
-                                "governance_id": "JF3q2MSpcds-jzhNYg3tNtT2nFU0eA9e85tKGUdDvJpo",
-                                "schema_id": "Prueba",
-                                "namespace": "namespace1",
-                                "payload": {
-                                    "Json": "{\"localizacion\":\"España\",\"temperatura\":10}"
-                                }
-                            }
-                        },
-                        "timestamp": 1671544386,
-                        "signature": {
-                            "content": {
-                                "signer": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
-                                "event_content_hash": "JX6KgyxQGGV0X81gsFU72klOBT39PS1R1cUEUIq8Ja0I",
-                                "timestamp": 1671544386
-                            },
-                            "signature": "SESDdbUuRkyomQYN47SEFl1ZdytNg-hH7QMyzs-v0gTUxrNbMihoL13tFTBmYzLEWhkoec0xP2l9sw-fo9cpHiAQ"
-                        },
-                        "approvals": []
-                    },
-                    "sn": 0,
-                    "previous_hash": "",
-                    "state_hash": "JrmBobio8phNFI3Fxd3pDo7NhyFi3OYYmRgds8Gpa1TY",
-                    "metadata": {
-                        "namespace": "namespace1",
-                        "governance_id": "JF3q2MSpcds-jzhNYg3tNtT2nFU0eA9e85tKGUdDvJpo",
-                        "governance_version": 0,
-                        "schema_id": "Prueba",
-                        "owner": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w"
-                    },
-                    "approved": true
-                },
-                "signature": {
-                    "content": {
-                        "signer": "ELm4bPbcZ8zVUWJf0aIACxHIiXP4cY1v-P2Evs0bE3kI",
-                        "event_content_hash": "J31pjM0RVGgGwfAa5Ptfir3ZSc_mjytEPSkXbl-1rczY",
-                        "timestamp": 1671544386
-                    },
-                    "signature": "SEMu7A5VnxaEFufuMBK39jAoLCSaeNVeEMP3RohSS4TsRZ_4l_zb802vweyok012q3LJbceMghZENVfCtHYJDoDA"
-                }
+                "patch": [
+                    { "op": "replace", "path": "/temperatura", "value": 15 }
+                ],
+                "warnings": []
             }
         )),
         (status = 400, description = "Bad Request"),
         (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
         (status = 500, description = "Internal Server Error"),
     )
 )]
-pub async fn post_subject_handler(
+pub async fn get_compare_subjects_handler(
     _header: String,
     node: NodeAPI,
-    body: PostSubjectBody,
+    parameters: CompareQuery,
 ) -> Result<Box<dyn warp::Reply>, Rejection> {
-    let payload = body.payload.into();
-    let data = node
-        .create_subject(body.governance_id, body.schema_id, body.namespace, payload)
-        .await;
-    handle_data(data)
+    if parameters.a.is_empty() || parameters.b.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("a", &parameters.a).map_err(validation_rejection)?;
+    validate_identifier("b", &parameters.b).map_err(validation_rejection)?;
+    let mut subjects = Vec::new();
+    for id in [parameters.a, parameters.b] {
+        let subject = match node.get_subject(id).await {
+            Ok(subject) => subject,
+            Err(error) => return handle_data::<()>(Err(error)),
+        };
+        let properties: serde_json::Value = serde_json::from_str(&subject.properties)
+            .map_err(|_| warp::reject::custom(Error::InternalServerError))?;
+        subjects.push((subject.schema_id, properties));
+    }
+    let (a, b) = (&subjects[0], &subjects[1]);
+    handle_data(Ok(SubjectComparison::new((&a.0, &a.1), (&b.0, &b.1))))
 }
 
 #[utoipa::path(
-    post,
-    path = "/requests",
-    tag = "Requests",
-    operation_id = "Create a new Event Request",
+    get,
+    path = "/subjects/{id}/properties/flat",
+    operation_id = "Get the flattened properties of a Subject",
+    tag = "Subjects",
     context_path = "/api",
     security(("api_key" = [])),
-    request_body(content = PostEventRequestBody, content_type = "application/json", description = "Event Request type and payload with the associated signature"),
+    params(
+        ("id" = String, Path, description = "Subject's unique id")
+    ),
     responses(
-        (status = 202, description = "Event Request Created", body = RequestData,
+        (status = 200, description = "Current properties of the subject as dotted keys with scalar values. Array items use index notation", body = Object,
         example = json!(
             {
-                "request": {
-                    "Create": {
-                        "governance_id": "",
-                        "schema_id": "",
-                        "namespace": "",
-                        "payload": {
-                            "Json": "{\"members\":[{\"description\":\"Sede en España\",\"id\":\"Compañía1\",\"key\":\"EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w\",\"tags\":{}},{\"description\":\"Sede en Inglaterra\",\"id\":\"Compañía2\",\"key\":\"ECQnl-h1vEWmu-ZlPuweR3N1x6SUImyVdPrCLmnJJMyU\",\"tags\":{}}],\"schemas\":[{\"content\":{\"additionalProperties\":false,\"properties\":{\"localizacion\":{\"type\":\"string\"},\"temperatura\":{\"type\":\"integer\"}},\"required\":[\"temperatura\",\"localizacion\"],\"type\":\"object\"},\"id\":\"Prueba\",\"tags\":{}}]}"
-                        }
+                "localizacion": "España",
+                "sensor.temp": 10,
+                "sensor.tags[0]": "exterior"
+            }
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_flat_properties_handler(
+    id: String,
+    node: NodeAPI,
+    _header: String,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let subject = match node.get_subject(id).await {
+        Ok(subject) => subject,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let properties: serde_json::Value = serde_json::from_str(&subject.properties)
+        .map_err(|_| warp::reject::custom(Error::InternalServerError))?;
+    handle_data(Ok(flatten_properties(&properties)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/subjects/{id}/schema-history",
+    operation_id = "Get the schemas used by a Subject over time",
+    tag = "Subjects",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Subject's unique id")
+    ),
+    responses(
+        (status = 200, description = "Schemas in the metadata of the subject events with the sn range where each one was used. Subjects that never changed schema have a single entry", body = [SchemaPeriod],
+        example = json!(
+            [
+                {
+                    "schema_id": "Prueba",
+                    "from_sn": 0,
+                    "to_sn": 4
+                }
+            ]
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_schema_history_handler(
+    id: String,
+    node: NodeAPI,
+    _header: String,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let data = node
+        .get_event_of_subject(id, None, None)
+        .await
+        .map(|events| {
+            SchemaPeriod::history(events.into_iter().map(|event| {
+                (
+                    event.event_content.sn,
+                    event.event_content.metadata.schema_id,
+                )
+            }))
+        });
+    handle_data(data)
+}
+
+#[utoipa::path(
+    get,
+    path = "/subjects/{id}/snapshot",
+    operation_id = "Get a Snapshot of a Subject",
+    tag = "Subjects",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Subject's unique id")
+    ),
+    responses(
+        (status = 200, description = "Subject data with every event of its chain, signatures included, with a checksum of its contents. The checksum detects damaged bundles, not forged ones", body = SubjectSnapshot,
+        example = json!(
+            {
+                "subject": {
+                    "subject_id": "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
+                    "governance_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+                    "sn": 0,
+                    "namespace": "namespace1",
+                    "schema_id": "Prueba",
+                    "owner": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                    "properties": "{\"localizacion\":\"España\",\"temperatura\":10}"
+                },
+                "events": [],
+                "checksum": "mB1tUdQmwLWM8s7vBxQ5JUrPqT-6yRT8GfWZK7A6gCk"
+            }
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_subject_snapshot_handler(
+    id: String,
+    node: NodeAPI,
+    _header: String,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let subject = match node.get_subject(id.clone()).await {
+        Ok(subject) => subject,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let events = match node.get_event_of_subject(id, None, None).await {
+        Ok(events) => events,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let subject = serde_json::to_value(&subject).map_err(serialization_rejection)?;
+    let events = events
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(serialization_rejection)?;
+    handle_data(Ok(SubjectSnapshot::new(subject, events)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/subjects",
+    tag = "Subjects",
+    operation_id = "Get All Subjects Data",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("from" = Option<usize>, Query, description = "Number of initial subject. Past the last subject an empty list is returned with the X-Out-Of-Range header, and out_of_range in the envelope metadata"),
+        ("quantity" = Option<usize>, Query, description = "Quantity of subjects requested"),
+        ("namespace" = Option<String>, Query, description = "Only subjects of this namespace. Defaults to every namespace"),
+        ("schema_id" = Option<String>, Query, description = "Only subjects with this schema. Combined with owner, subjects must match both"),
+        ("owner" = Option<String>, Query, description = "Only subjects of this owner. Combined with schema_id, subjects must match both"),
+        ("truncate" = Option<usize>, Query, description = "Characters of the properties of each item that are kept. Longer properties end with an ellipsis and their items are marked with truncated. Defaults to no truncation"),
+        ("count" = Option<bool>, Query, description = "Report the number of subjects of the whole listing in X-Total-Count and in total of the envelope metadata. The node can not count its subjects, so the whole listing is read. Defaults to false"),
+    ),
+    responses(
+        (status = 200, description = "Subjects Data successfully retrieved", body = [SubjectData], headers(("X-Total-Count" = usize, description = "Number of subjects of the whole listing, in the namespace if one was given. Only with count=true")),
+        example = json!(
+            [
+                {
+                    "subject_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+                    "governance_id": "",
+                    "sn": 0,
+                    "public_key": "E2tlKVr6wA2GZKoSZi_dwIuz2TVUTCCDpOOwiE2SJbWc",
+                    "namespace": "",
+                    "schema_id": "",
+                    "owner": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                    "properties": "{\"members\":[{\"description\":\"Sede en España\",\"id\":\"Compañía1\",\"key\":\"EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w\",\"tags\":{}},{\"description\":\"Sede en Inglaterra\",\"id\":\"Compañía2\",\"key\":\"ECQnl-h1vEWmu-ZlPuweR3N1x6SUImyVdPrCLmnJJMyU\",\"tags\":{}}],\"schemas\":[{\"content\":{\"additionalProperties\":false,\"properties\":{\"localizacion\":{\"type\":\"string\"},\"temperatura\":{\"type\":\"integer\"}},\"required\":[\"temperatura\",\"localizacion\"],\"type\":\"object\"},\"id\":\"Prueba\",\"tags\":{}}]}"
+                },
+                {
+                    "subject_id": "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
+                    "governance_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+                    "sn": 0,
+                    "public_key": "ELZ_b-kZzdPykcYuRNC2ZZe_2lCTCUoo60GXfR4cuXMw",
+                    "namespace": "namespace1",
+                    "schema_id": "Prueba",
+                    "owner": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                    "properties": "{\"localizacion\":\"España\",\"temperatura\":10}"
+                }
+            ]
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_all_subjects_handler(
+    node: NodeAPI,
+    _header: String,
+    parameters: GetAllSubjectsQuery,
+    shaping: Shaping,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let namespace = parameters.namespace.clone().unwrap_or_default();
+    let fetch = move |from: usize, quantity: usize| {
+        let node = node.clone();
+        let namespace = namespace.clone();
+        async move {
+            node.get_all_subjects(namespace, Some(from), Some(quantity))
+                .await
+        }
+    };
+    // Empty filters do not filter, and both must match when given
+    let schema_id = parameters
+        .schema_id
+        .clone()
+        .filter(|schema_id| !schema_id.is_empty());
+    let owner = parameters.owner.clone().filter(|owner| !owner.is_empty());
+    let filtered = schema_id.is_some() || owner.is_some();
+    let matches = move |subject: &SubjectData| {
+        schema_id
+            .as_ref()
+            .map_or(true, |wanted| *wanted == subject.schema_id)
+            && owner
+                .as_ref()
+                .map_or(true, |wanted| *wanted == subject.owner.to_str())
+    };
+    // Subjects are written as they are fetched instead of buffering the whole listing, unless
+    // the total is requested
+    let from = parameters.from.unwrap_or(0);
+    let page = listing_page(
+        fetch,
+        filtered.then_some(matches),
+        from,
+        parameters.quantity,
+        parameters.count.unwrap_or(false),
+    )
+    .await;
+    let (mut subjects, total) = match page {
+        Ok(page) => page,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    // Counted pages are already in memory, and only JSON is streamed, so the rest of pages are
+    // shaped and encoded as any other response
+    if total.is_some() || !shaping.streams() {
+        let page = match subjects.try_collect::<Vec<SubjectData>>().await {
+            Ok(page) => page,
+            Err(error) => return handle_data::<()>(Err(error)),
+        };
+        let past_the_end = page.is_empty() && from > 0;
+        let mut reply = handle_data(Ok(page))?;
+        if past_the_end {
+            reply = out_of_range(reply);
+        }
+        if let Some(total) = total {
+            reply = Box::new(warp::reply::with_header(
+                reply,
+                TOTAL_COUNT_HEADER,
+                total.to_string(),
+            ));
+        }
+        return Ok(reply);
+    }
+    // Failures are only reported with an error status before the body starts
+    let first = match subjects.next().await {
+        Some(Err(error)) => return handle_data::<()>(Err(error)),
+        None if from > 0 => {
+            let page: Vec<serde_json::Value> = Vec::new();
+            return Ok(out_of_range(handle_data(Ok(page))?));
+        }
+        first => first,
+    };
+    let subjects = futures::stream::iter(first).chain(subjects).map(|subject| {
+        subject
+            .map(|subject| serde_json::to_value(subject).unwrap_or(serde_json::Value::Null))
+            .map_err(|error| {
+                log::warn!("Subjects listing stopped: {:?}", error);
+                Error::ExecutionError
+            })
+    });
+    let mut response =
+        warp::reply::Response::new(warp::hyper::Body::wrap_stream(shaping.body(subjects)));
+    response.headers_mut().insert(
+        warp::http::header::CONTENT_TYPE,
+        warp::http::HeaderValue::from_static("application/json"),
+    );
+    Ok(Box::new(response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/subjects/search",
+    tag = "Subjects",
+    operation_id = "Search Subjects by property",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("key" = String, Query, description = "Property compared. A top level field of the properties, or a JSON pointer such as /sede/id for nested ones"),
+        ("op" = SearchOperator, Query, description = "Comparison with the value: eq, ne, lt, lte, gt and gte compare numbers, strings (alphabetically) and booleans; contains checks that a string property contains the value. Subjects without the property, or whose property can not be compared with the value, never match"),
+        ("value" = String, Query, description = "Value compared with the property, read with the type of the property. value=10 matches both 10 and \"10\""),
+        ("namespace" = Option<String>, Query, description = "Only subjects of this namespace. Defaults to every namespace"),
+        ("from" = Option<usize>, Query, description = "Number of initial subject among those that match"),
+        ("quantity" = Option<usize>, Query, description = "Quantity of subjects requested"),
+    ),
+    responses(
+        (status = 200, description = "Subjects whose properties match the search", body = [SubjectData],
+        example = json!(
+            [
+                {
+                    "subject_id": "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
+                    "governance_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+                    "sn": 0,
+                    "public_key": "ELZ_b-kZzdPykcYuRNC2ZZe_2lCTCUoo60GXfR4cuXMw",
+                    "namespace": "namespace1",
+                    "schema_id": "Prueba",
+                    "owner": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                    "properties": "{\"localizacion\":\"España\",\"temperatura\":-2}"
+                }
+            ]
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_search_subjects_handler(
+    node: NodeAPI,
+    _header: String,
+    parameters: SearchSubjectsQuery,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if parameters.key.is_empty() {
+        return Err(validation_rejection(vec![FieldError::new(
+            "key",
+            "must not be empty",
+        )]));
+    }
+    let namespace = parameters.namespace.clone().unwrap_or_default();
+    let fetch = move |from: usize, quantity: usize| {
+        let node = node.clone();
+        let namespace = namespace.clone();
+        async move {
+            node.get_all_subjects(namespace, Some(from), Some(quantity))
+                .await
+        }
+    };
+    // The node can not filter by properties, so every subject is read and matched here
+    let SearchSubjectsQuery {
+        key,
+        op,
+        value,
+        from,
+        quantity,
+        ..
+    } = parameters;
+    let matches = move |subject: &SubjectData| {
+        futures::future::ready(property_matches(&subject.properties, &key, op, &value))
+    };
+    let subjects = paged_stream(0, None, LISTING_BATCH, fetch).try_filter(matches);
+    let page = page_of(subjects, from.unwrap_or(0), quantity)
+        .try_collect::<Vec<SubjectData>>()
+        .await;
+    handle_data(page)
+}
+
+#[utoipa::path(
+    get,
+    path = "/snapshot",
+    tag = "Subjects",
+    operation_id = "Get the latest state of every Subject",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("governance_id" = Option<String>, Query, description = "Only subjects of this governance"),
+        ("schema_id" = Option<String>, Query, description = "Only subjects with this schema"),
+    ),
+    responses(
+        (status = 200, description = "Current sn, state hash and properties of each tracked subject, once each. In JSON the list is streamed as it is read, so a failure after the first subject ends it early", body = [SubjectState],
+        example = json!(
+            [
+                {
+                    "subject_id": "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
+                    "sn": 1,
+                    "state_hash": "JMqLbPz7VY1pjuj9-n0qT0UuOGH_TpQVRaVEOHSaE_5Y",
+                    "properties": "{\"localizacion\":\"Argentina\",\"temperatura\":-3}"
+                }
+            ]
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_snapshot_handler(
+    _header: String,
+    node: NodeAPI,
+    parameters: SnapshotQuery,
+    shaping: Shaping,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let listing = node.clone();
+    let subjects = paged_stream(0, None, LISTING_BATCH, move |from, quantity| {
+        let node = listing.clone();
+        async move {
+            node.get_all_subjects(String::from(""), Some(from), Some(quantity))
+                .await
+        }
+    })
+    .try_filter(move |subject| {
+        let governance_id = subject.governance_id.to_str();
+        futures::future::ready(
+            parameters
+                .governance_id
+                .as_ref()
+                .map_or(true, |wanted| *wanted == governance_id)
+                && parameters
+                    .schema_id
+                    .as_ref()
+                    .map_or(true, |wanted| *wanted == subject.schema_id),
+        )
+    })
+    .and_then(move |subject| {
+        let node = node.clone();
+        async move { subject_state(&node, subject).await }
+    });
+    let mut states = Box::pin(subjects);
+    // Only JSON is streamed, other content types are encoded from the whole listing
+    if !shaping.streams() {
+        let states = states.try_collect::<Vec<SubjectState>>().await;
+        return handle_data(states);
+    }
+    // Failures are only reported with an error status before the body starts
+    let first = match states.next().await {
+        Some(Err(error)) => return handle_data::<()>(Err(error)),
+        first => first,
+    };
+    let states = futures::stream::iter(first).chain(states).map(|state| {
+        state
+            .map(|state| serde_json::to_value(state).unwrap_or(serde_json::Value::Null))
+            .map_err(|error| {
+                log::warn!("Snapshot stopped: {:?}", error);
+                Error::ExecutionError
+            })
+    });
+    let mut response =
+        warp::reply::Response::new(warp::hyper::Body::wrap_stream(shaping.body(states)));
+    response.headers_mut().insert(
+        warp::http::header::CONTENT_TYPE,
+        warp::http::HeaderValue::from_static("application/json"),
+    );
+    Ok(Box::new(response))
+}
+
+// Completes a subject with the state hash of its latest event
+async fn subject_state(node: &NodeAPI, subject: SubjectData) -> Result<SubjectState, ApiError> {
+    let mut events = node
+        .get_event_of_subject(
+            subject.subject_id.to_str(),
+            Some(subject.sn as i64),
+            Some(1),
+        )
+        .await?;
+    let state_hash = events
+        .pop()
+        .map(|event| event.event_content.state_hash.to_str())
+        .unwrap_or_default();
+    Ok(SubjectState {
+        subject_id: subject.subject_id.to_str(),
+        sn: subject.sn,
+        state_hash,
+        properties: subject.properties,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/namespaces",
+    tag = "Subjects",
+    operation_id = "Get the Namespaces in use",
+    context_path = "/api",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Distinct namespaces of the tracked subjects with the number of subjects in each one. Subjects without namespace are counted under the empty namespace", body = [NamespaceCount],
+        example = json!(
+            [
+                { "namespace": "", "subjects": 1 },
+                { "namespace": "namespace1", "subjects": 4 }
+            ]
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_namespaces_handler(
+    _header: String,
+    node: NodeAPI,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let subjects = match node.get_all_subjects(String::from(""), None, None).await {
+        Ok(subjects) => subjects,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let namespaces = subjects.into_iter().map(|subject| subject.namespace);
+    handle_data(Ok(NamespaceCount::count(namespaces)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/subjects/owned",
+    tag = "Subjects",
+    operation_id = "Get the Subjects owned by the node",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("from" = Option<usize>, Query, description = "Number of initial subject"),
+        ("quantity" = Option<usize>, Query, description = "Quantity of subjects requested"),
+        ("namespace" = Option<String>, Query, description = "Only subjects of this namespace. Defaults to every namespace"),
+        ("truncate" = Option<usize>, Query, description = "Characters of the properties of each item that are kept. Longer properties end with an ellipsis and their items are marked with truncated. Defaults to no truncation"),
+    ),
+    responses(
+        (status = 200, description = "Subjects whose owner is the controller of this node. Subjects that the node only tracks are left out", body = [SubjectData],
+        example = json!(
+            [
+                {
+                    "subject_id": "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
+                    "governance_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+                    "sn": 0,
+                    "public_key": "ELZ_b-kZzdPykcYuRNC2ZZe_2lCTCUoo60GXfR4cuXMw",
+                    "namespace": "namespace1",
+                    "schema_id": "Prueba",
+                    "owner": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                    "properties": "{\"localizacion\":\"España\",\"temperatura\":10}"
+                }
+            ]
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_owned_subjects_handler(
+    _header: String,
+    node: NodeAPI,
+    identity: NodeIdentity,
+    parameters: GetAllSubjectsQuery,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let namespace = parameters.namespace.unwrap_or_default();
+    let subjects = match node.get_all_subjects(namespace, None, None).await {
+        Ok(subjects) => subjects,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let owned = identity.owned(
+        subjects,
+        |subject| subject.owner.to_str(),
+        parameters.from.unwrap_or(0),
+        parameters.quantity,
+    );
+    handle_data(Ok(owned))
+}
+
+#[utoipa::path(
+    post,
+    path = "/subjects",
+    tag = "Subjects",
+    operation_id = "Create a new Subject",
+    context_path = "/api",
+    security(("api_key" = [])),
+    request_body(content = PostSubjectBody, content_type = "application/json", description = "Schema and governance specification of the new subject. It also must contain the initial payload"),
+    params(
+        ("Prefer" = Option<String>, Header, description = "return=minimal answers with just the request and subject ids, return=representation (default) with the whole request. The applied preference is echoed in Preference-Applied"),
+    ),
+    responses(
+        (status = 201, description = "Subject Created. Location points at the new subject", body = Event,
+        example = json!(
+            {
+                "event_content": {
+                    "subject_id": "J4LS4VgGo3SDmC4F4hFFBkw8Kgv4ItpYgjUbw0gDxNk8",
+                    "event_request": {
+                        "request": {
+                            "Create": {
+                                "governance_id": "JF3q2MSpcds-jzhNYg3tNtT2nFU0eA9e85tKGUdDvJpo",
+                                "schema_id": "Prueba",
+                                "namespace": "namespace1",
+                                "payload": {
+                                    "Json": "{\"localizacion\":\"España\",\"temperatura\":10}"
+                                }
+                            }
+                        },
+                        "timestamp": 1671544386,
+                        "signature": {
+                            "content": {
+                                "signer": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                                "event_content_hash": "JX6KgyxQGGV0X81gsFU72klOBT39PS1R1cUEUIq8Ja0I",
+                                "timestamp": 1671544386
+                            },
+                            "signature": "SESDdbUuRkyomQYN47SEFl1ZdytNg-hH7QMyzs-v0gTUxrNbMihoL13tFTBmYzLEWhkoec0xP2l9sw-fo9cpHiAQ"
+                        },
+                        "approvals": []
+                    },
+                    "sn": 0,
+                    "previous_hash": "",
+                    "state_hash": "JrmBobio8phNFI3Fxd3pDo7NhyFi3OYYmRgds8Gpa1TY",
+                    "metadata": {
+                        "namespace": "namespace1",
+                        "governance_id": "JF3q2MSpcds-jzhNYg3tNtT2nFU0eA9e85tKGUdDvJpo",
+                        "governance_version": 0,
+                        "schema_id": "Prueba",
+                        "owner": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w"
+                    },
+                    "approved": true
+                },
+                "signature": {
+                    "content": {
+                        "signer": "ELm4bPbcZ8zVUWJf0aIACxHIiXP4cY1v-P2Evs0bE3kI",
+                        "event_content_hash": "J31pjM0RVGgGwfAa5Ptfir3ZSc_mjytEPSkXbl-1rczY",
+                        "timestamp": 1671544386
+                    },
+                    "signature": "SEMu7A5VnxaEFufuMBK39jAoLCSaeNVeEMP3RohSS4TsRZ_4l_zb802vweyok012q3LJbceMghZENVfCtHYJDoDA"
+                }
+            }
+        )),
+        (status = 202, description = "Subject created pending approval", body = Event),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "The schema, or the governance for the API KEY, is not allowed in this node"),
+        (status = 422, description = "The payload does not match its schema, or a new governance repeats schema ids, member ids or member keys"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn post_subject_handler(
+    api_key: String,
+    node: NodeAPI,
+    settings: RestSettings,
+    audit: AuditLog,
+    prefer: Option<String>,
+    body: PostSubjectBody,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let target = body.governance_id.clone();
+    audit
+        .audited(
+            "post_subject",
+            &api_key,
+            &target,
+            post_subject(&api_key, node, settings, prefer, body),
+        )
+        .await
+}
+
+async fn post_subject(
+    api_key: &str,
+    node: NodeAPI,
+    settings: RestSettings,
+    prefer: Option<String>,
+    body: PostSubjectBody,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    validate_create_request(&body.governance_id, &body.schema_id, &body.payload)
+        .map_err(validation_rejection)?;
+    validate_namespace(&body.namespace, &settings.namespace_pattern())
+        .map_err(validation_rejection)?;
+    if body.schema_id == GOVERNANCE_SCHEMA_ID {
+        check_unique_governance_ids(&body.payload)?;
+    }
+    check_schema_allowed(&settings, &body.schema_id)?;
+    check_governance_allowed(&settings, api_key, &body.governance_id)?;
+    check_payload_schema(&node, &body.governance_id, &body.schema_id, &body.payload).await?;
+    let payload = body.payload.into();
+    let data = node
+        .create_subject(body.governance_id, body.schema_id, body.namespace, payload)
+        .await;
+    handle_created(data, ReturnPreference::from_header(prefer))
+}
+
+#[utoipa::path(
+    post,
+    path = "/requests",
+    tag = "Requests",
+    operation_id = "Create a new Event Request",
+    context_path = "/api",
+    security(("api_key" = [])),
+    request_body(content = PostEventRequestBody, content_type = "application/json", description = "Event Request type and payload with the associated signature"),
+    params(
+        ("Prefer" = Option<String>, Header, description = "return=minimal answers with just the request and subject ids, return=representation (default) with the whole request. The applied preference is echoed in Preference-Applied"),
+    ),
+    responses(
+        (status = 201, description = "Subject Created by a Create request, which is never voted. Location points at the new subject", body = RequestData,
+        example = json!(
+            {
+                "request": {
+                    "Create": {
+                        "governance_id": "",
+                        "schema_id": "",
+                        "namespace": "",
+                        "payload": {
+                            "Json": "{\"members\":[{\"description\":\"Sede en España\",\"id\":\"Compañía1\",\"key\":\"EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w\",\"tags\":{}},{\"description\":\"Sede en Inglaterra\",\"id\":\"Compañía2\",\"key\":\"ECQnl-h1vEWmu-ZlPuweR3N1x6SUImyVdPrCLmnJJMyU\",\"tags\":{}}],\"schemas\":[{\"content\":{\"additionalProperties\":false,\"properties\":{\"localizacion\":{\"type\":\"string\"},\"temperatura\":{\"type\":\"integer\"}},\"required\":[\"temperatura\",\"localizacion\"],\"type\":\"object\"},\"id\":\"Prueba\",\"tags\":{}}]}"
+                        }
+                    }
+                },
+                "request_id": "JpxalqMTQcDcLG3dwb8uvcrstJo6pmFEzUwhzi0nGPOA",
+                "timestamp": 1671705355,
+                "subject_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+                "sn": 0
+            }
+        )),
+        (status = 202, description = "Event Request Created, pending approval", body = RequestData),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "The schema, or the governance for the API KEY, is not allowed in this node, or the node can not sign the request. The code of the problem tells which"),
+        (status = 404, description = "The node does not have the subject or the governance of the request"),
+        (status = 422, description = "The payload does not match its schema, or a new governance repeats schema ids, member ids or member keys"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn post_event_request_handler(
+    api_key: String,
+    node: NodeAPI,
+    settings: RestSettings,
+    audit: AuditLog,
+    prefer: Option<String>,
+    body: PostEventRequestBody,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let target = match &body.request {
+        EventRequestTypeBody::Create(request) => request.governance_id.clone(),
+        EventRequestTypeBody::State(request) => request.subject_id.clone(),
+    };
+    audit
+        .audited(
+            "post_event_request",
+            &api_key,
+            &target,
+            post_event_request(&api_key, node, settings, prefer, body),
+        )
+        .await
+}
+
+async fn post_event_request(
+    api_key: &str,
+    node: NodeAPI,
+    settings: RestSettings,
+    prefer: Option<String>,
+    body: PostEventRequestBody,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    match &body.request {
+        EventRequestTypeBody::Create(request) => {
+            validate_create_request(&request.governance_id, &request.schema_id, &request.payload)
+                .map_err(validation_rejection)?;
+            validate_namespace(&request.namespace, &settings.namespace_pattern())
+                .map_err(validation_rejection)?;
+            if request.schema_id == GOVERNANCE_SCHEMA_ID {
+                check_unique_governance_ids(&request.payload)?;
+            }
+            check_schema_allowed(&settings, &request.schema_id)?;
+            check_governance_allowed(&settings, api_key, &request.governance_id)?;
+            check_payload_schema(
+                &node,
+                &request.governance_id,
+                &request.schema_id,
+                &request.payload,
+            )
+            .await?;
+        }
+        EventRequestTypeBody::State(request) => {
+            if let Ok(subject) = node.get_subject(request.subject_id.clone()).await {
+                // Governances are their own governance
+                let governance_id = if subject.governance_id.digest.is_empty() {
+                    subject.subject_id.to_str()
+                } else {
+                    subject.governance_id.to_str()
+                };
+                check_governance_allowed(&settings, api_key, &governance_id)?;
+                check_payload_schema(
+                    &node,
+                    &subject.governance_id.to_str(),
+                    &subject.schema_id,
+                    &request.payload,
+                )
+                .await?;
+            }
+        }
+    }
+    let data;
+    if body.signature.is_none() && body.timestamp.is_none() {
+        data = node.create_request(body.request.into()).await;
+    } else if body.signature.is_some() && body.timestamp.is_some() {
+        if let Ok(external_request) = body.try_into() {
+            data = node.external_request(external_request).await;
+        } else {
+            data = Err(ApiError::InvalidParameters);
+        }
+    } else {
+        data = Err(ApiError::InvalidParameters);
+    }
+    log::info!("data: {:?}", data);
+    handle_created(data, ReturnPreference::from_header(prefer))
+}
+
+#[utoipa::path(
+    post,
+    path = "/requests/validate",
+    tag = "Requests",
+    operation_id = "Validate an external Event Request without submitting it",
+    context_path = "/api",
+    security(("api_key" = [])),
+    request_body(content = PostEventRequestBody, content_type = "application/json", description = "Signed external Event Request, as it would be sent to POST /requests"),
+    responses(
+        (status = 200, description = "Result of each check of the submission. Nothing is created", body = ValidationReport,
+        example = json!(
+            {
+                "valid": false,
+                "checks": [
+                    { "check": "request", "passed": true, "message": null },
+                    { "check": "timestamp", "passed": true, "message": null },
+                    { "check": "signature", "passed": false, "message": "does not match the signer" },
+                    { "check": "schema", "passed": true, "message": null }
+                ]
+            }
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn post_validate_request_handler(
+    _header: String,
+    node: NodeAPI,
+    body: PostEventRequestBody,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let now = chrono::Utc::now().timestamp();
+    let mut checks = check_external_request(&body, now, verify_signature);
+    if let EventRequestTypeBody::State(request) = &body.request {
+        let schema = state_schema_check(&node, &request.subject_id, &request.payload).await;
+        checks.push(ValidationCheck::new("schema", schema));
+    }
+    handle_data(Ok(ValidationReport::new(checks)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/subjects/validate",
+    tag = "Subjects",
+    operation_id = "Validate the creation of a Subject without creating it",
+    context_path = "/api",
+    security(("api_key" = [])),
+    request_body(content = PostSubjectBody, content_type = "application/json", description = "Subject as it would be created"),
+    responses(
+        (status = 200, description = "Result of each check of the creation: request, namespace, permissions, governance, schema and payload. Checks that depend on a failed one are not run. Nothing is created", body = ValidationReport,
+        example = json!(
+            {
+                "valid": false,
+                "checks": [
+                    { "check": "request", "passed": true, "message": null },
+                    { "check": "namespace", "passed": false, "message": "namespace must match the pattern ^[A-Za-z0-9_/-]*$" },
+                    { "check": "permissions", "passed": true, "message": null },
+                    { "check": "governance", "passed": true, "message": null },
+                    { "check": "schema", "passed": true, "message": null },
+                    { "check": "payload", "passed": false, "message": "\"10\" is not of type \"integer\"" }
+                ]
+            }
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn post_validate_subject_handler(
+    api_key: String,
+    node: NodeAPI,
+    settings: RestSettings,
+    body: PostSubjectBody,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let governance = match node.get_subject(body.governance_id.clone()).await {
+        Ok(governance) if governance.governance_id.digest.is_empty() => {
+            serde_json::from_str::<serde_json::Value>(&governance.properties).ok()
+        }
+        _ => None,
+    };
+    let checks = check_create_subject(&body, &settings, &api_key, governance.as_ref());
+    handle_data(Ok(ValidationReport::new(checks)))
+}
+
+// #[utoipa::path(
+//     post,
+//     path = "/requests/external",
+//     tag = "Requests",
+//     operation_id = "Send an Event Request to make and external invokation",
+//     context_path = "/api",
+//     security(("api_key" = [])),
+//     request_body(content = ExternalEventRequestBody, content_type = "application/json", description = "Event Request"),
+//     responses(
+//         (status = 202, description = "Event Request Created", body = RequestData),
+//         (status = 400, description = "Bad Request"),
+//         (status = 401, description = "Unauthorized"),
+//         (status = 500, description = "Internal Server Error"),
+//     )
+// )]
+// pub async fn post_external_request_handler(
+//     _header: String,
+//     node: NodeAPI,
+//     body: ExternalEventRequestBody,
+// ) -> Result<Box<dyn warp::Reply>, Rejection> {
+//     let data = node.external_request(body.into()).await;
+//     handle_data(data)
+// }
+
+#[utoipa::path(
+    get,
+    path = "/approvals",
+    tag = "Approvals",
+    operation_id = "Get all the pending requests for Approval",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("summary" = Option<bool>, Query, description = "Return only the request id, subject id, schema id, timestamp and signer of each request. Defaults to false"),
+    ),
+    responses(
+        (status = 200, description = "All pending requests. With summary, a list of RequestSummary instead", body =  [EventRequest],
+        example = json!(
+            [
+                {
+                    "request": {
+                        "State": {
+                            "subject_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+                            "payload": {
+                                "Json": "{\"members\":[{\"description\":\"Sede en España\",\"id\":\"Compañía1\",\"key\":\"EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w\",\"tags\":{}}],\"schemas\":[{\"content\":{\"additionalProperties\":false,\"properties\":{\"localizacion\":{\"type\":\"string\"},\"temperatura\":{\"type\":\"integer\"}},\"required\":[\"temperatura\",\"localizacion\"],\"type\":\"object\"},\"id\":\"Prueba\",\"tags\":{}}]}"
+                            }
+                        }
+                    },
+                    "timestamp": 1671709394,
+                    "signature": {
+                        "content": {
+                            "signer": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                            "event_content_hash": "JhEnzFVF1a-u-rH34cix2A_OXgcfesM6HGOyk7wdrGHk",
+                            "timestamp": 1671709394
+                        },
+                        "signature": "SEnUJq3Y1lbmijKzrc0kuLu-FgMTCyo5PWfDrbi_80bspghCny8Yuvifsmdqq0TjfTUS7sEwmOLir1W_1zeIVyDQ"
+                    },
+                    "approvals": []
+                }
+            ]
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_pending_requests_handler(
+    node: NodeAPI,
+    _header: String,
+    parameters: PendingRequestsQuery,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let data = node.get_pending_requests().await;
+    if !parameters.summary.unwrap_or(false) {
+        return handle_data(data);
+    }
+    let requests = match data {
+        Ok(requests) => requests,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    // The schema of the subjects modified by a request is only known by the node
+    let subjects = match node.get_all_subjects(String::from(""), None, None).await {
+        Ok(subjects) => subjects,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let summaries: Vec<RequestSummary> = requests
+        .into_iter()
+        .map(|request| {
+            let (subject_id, schema_id) = match &request.request {
+                EventRequestType::Create(create) => (None, Some(create.schema_id.clone())),
+                EventRequestType::State(state) => {
+                    let subject_id = state.subject_id.to_str();
+                    let schema_id = subjects
+                        .iter()
+                        .find(|subject| subject.subject_id.to_str() == subject_id)
+                        .map(|subject| subject.schema_id.clone());
+                    (Some(subject_id), schema_id)
+                }
+            };
+            RequestSummary {
+                request_id: request.signature.content.event_content_hash.to_str(),
+                subject_id,
+                schema_id,
+                timestamp: request.timestamp,
+                signer: request.signature.content.signer.to_str(),
+            }
+        })
+        .collect();
+    handle_data(Ok(summaries))
+}
+
+#[utoipa::path(
+    get,
+    path = "/approvals/count",
+    tag = "Approvals",
+    operation_id = "Count the pending requests for Approval",
+    context_path = "/api",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Number of pending requests, for clients that poll it instead of the whole list", body = PendingCount,
+        example = json!(
+            {
+                "count": 1
+            }
+        )),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_pending_count_handler(
+    node: NodeAPI,
+    _header: String,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    // The node does not count its pending requests, so the list is read but not sent
+    match node.get_pending_requests().await {
+        Ok(requests) => handle_data(Ok(PendingCount {
+            count: requests.len(),
+        })),
+        Err(error) => handle_data::<()>(Err(error)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/approvals/{id}",
+    tag = "Approvals",
+    operation_id = "Get a specific pending request for Approval",
+    context_path = "/api",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "The pending request", body = EventRequest,
+        example = json!(
+            {
+                "request": {
+                    "State": {
+                        "subject_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+                        "payload": {
+                            "Json": "{\"members\":[{\"description\":\"Sede en España\",\"id\":\"Compañía1\",\"key\":\"EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w\",\"tags\":{}}],\"schemas\":[{\"content\":{\"additionalProperties\":false,\"properties\":{\"localizacion\":{\"type\":\"string\"},\"temperatura\":{\"type\":\"integer\"}},\"required\":[\"temperatura\",\"localizacion\"],\"type\":\"object\"},\"id\":\"Prueba\",\"tags\":{}}]}"
+                        }
+                    }
+                },
+                "timestamp": 1671709394,
+                "signature": {
+                    "content": {
+                        "signer": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                        "event_content_hash": "JhEnzFVF1a-u-rH34cix2A_OXgcfesM6HGOyk7wdrGHk",
+                        "timestamp": 1671709394
+                    },
+                    "signature": "SEnUJq3Y1lbmijKzrc0kuLu-FgMTCyo5PWfDrbi_80bspghCny8Yuvifsmdqq0TjfTUS7sEwmOLir1W_1zeIVyDQ"
+                },
+                "approvals": []
+            }
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_single_request_handler(
+    id: String,
+    node: NodeAPI,
+    _header: String,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let data = node.get_single_request(id).await;
+    handle_data(data)
+}
+
+#[utoipa::path(
+    get,
+    path = "/requests/{id}/timeline",
+    tag = "Requests",
+    operation_id = "Get the approval timeline of a request",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Request's unique id"),
+        ("subject_id" = Option<String>, Query, description = "Subject of the event produced by the request. Needed, with sn, once the request is resolved"),
+        ("sn" = Option<u64>, Query, description = "SN of the event produced by the request"),
+    ),
+    responses(
+        (status = 200, description = "Votes of the request in chronological order", body = RequestTimeline,
+        example = json!(
+            {
+                "request_id": "JhEnzFVF1a-u-rH34cix2A_OXgcfesM6HGOyk7wdrGHk",
+                "votes": [
+                    {
+                        "signer": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                        "acceptance": "Accept",
+                        "timestamp": 1671709401
+                    }
+                ],
+                "resolution": "Approved"
+            }
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_request_timeline_handler(
+    id: String,
+    node: NodeAPI,
+    _header: String,
+    parameters: ResolvedRequestQuery,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let (request, resolution) = match node.get_single_request(id.clone()).await {
+        Ok(request) => (request, RequestResolution::Pending),
+        Err(ApiError::NotFound(_)) => {
+            // The request is no longer pending, so its votes live in the event it produced
+            let Some(event) = find_request_event(&node, &id, parameters).await? else {
+                return Err(warp::reject::custom(Error::NotFound));
+            };
+            let resolution = if event.event_content.approved {
+                RequestResolution::Approved
+            } else {
+                RequestResolution::Rejected
+            };
+            (event.event_content.event_request, resolution)
+        }
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let votes = request
+        .approvals
+        .into_iter()
+        .map(|approval| TimelineEntry {
+            signer: approval.signature.content.signer.to_str(),
+            acceptance: approval.content.approval_type,
+            timestamp: approval.signature.content.timestamp,
+        })
+        .collect();
+    handle_data(Ok(RequestTimeline::new(id, votes, resolution)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/requests/{id}/signatures",
+    operation_id = "Get the approval Signatures of a resolved Request",
+    tag = "Requests",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Request's unique id"),
+        ("subject_id" = Option<String>, Query, description = "Subject of the event produced by the request. Needed, with sn, once the request is resolved"),
+        ("sn" = Option<u64>, Query, description = "SN of the event produced by the request"),
+    ),
+    responses(
+        (status = 200, description = "Approval signatures of the quorum that resolved the request. For approved requests they are the acceptances, for rejected ones the rejections", body = RequestSignatures,
+        example = json!(
+            {
+                "request_id": "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
+                "resolution": "Approved",
+                "signatures": [
+                    {
+                        "signer": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                        "acceptance": "Accept",
+                        "timestamp": 1671544841,
+                        "signature": "SE51jtptGbj2T5ov0O6_ANQ3X8XJBAO3S9nDZPh6azuirFzIj0LV6tVtir2LEav9rR4tb5bDCgpDvDWn5sUT5AAg"
+                    }
+                ]
+            }
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 409, description = "The request is still pending"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_request_signatures_handler(
+    id: String,
+    node: NodeAPI,
+    _header: String,
+    parameters: ResolvedRequestQuery,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    match node.get_single_request(id.clone()).await {
+        Ok(_) => {
+            return Err(warp::reject::custom(Error::Conflict(format!(
+                "Request {} is still pending, it has no quorum yet",
+                id
+            ))))
+        }
+        Err(ApiError::NotFound(_)) => {}
+        Err(error) => return handle_data::<()>(Err(error)),
+    }
+    let Some(event) = find_request_event(&node, &id, parameters).await? else {
+        return Err(warp::reject::custom(Error::NotFound));
+    };
+    let signatures = event
+        .event_content
+        .event_request
+        .approvals
+        .into_iter()
+        .map(|approval| ApprovalSignature {
+            signer: approval.signature.content.signer.to_str(),
+            acceptance: approval.content.approval_type,
+            timestamp: approval.signature.content.timestamp,
+            signature: approval.signature.signature.to_str(),
+        })
+        .collect();
+    handle_data(Ok(RequestSignatures::new(
+        id,
+        event.event_content.approved,
+        signatures,
+    )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/requests/{id}/payload",
+    operation_id = "Get the payload of a Request",
+    tag = "Requests",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Request's unique id"),
+        ("subject_id" = Option<String>, Query, description = "Subject of the event produced by the request. Needed, with sn, once the request is resolved"),
+        ("sn" = Option<u64>, Query, description = "SN of the event produced by the request"),
+    ),
+    responses(
+        (status = 200, description = "Decoded payload of the request, pending or resolved, in the format of the request bodies so it can be modified and sent again", body = Payload,
+        example = json!(
+            {
+                "Json": {
+                    "localizacion": "España",
+                    "temperatura": 10
+                }
+            }
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_request_payload_handler(
+    id: String,
+    node: NodeAPI,
+    _header: String,
+    parameters: ResolvedRequestQuery,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let request = match node.get_single_request(id.clone()).await {
+        Ok(request) => request,
+        Err(ApiError::NotFound(_)) => {
+            let Some(event) = find_request_event(&node, &id, parameters).await? else {
+                return Err(warp::reject::custom(Error::NotFound));
+            };
+            event.event_content.event_request
+        }
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let payload = match &request.request {
+        EventRequestType::Create(request) => &request.payload,
+        EventRequestType::State(request) => &request.payload,
+    };
+    let payload = Payload::try_from(payload).map_err(|error| {
+        log::error!("Payload of request {} could not be decoded: {}", id, error);
+        warp::reject::custom(Error::InternalServerError)
+    })?;
+    handle_data(Ok(payload))
+}
+
+#[utoipa::path(
+    put,
+    path = "/approvals/{id}",
+    operation_id = "Set your Aprroval for a request",
+    tag = "Approvals",
+    context_path = "/api",
+    security(("api_key" = [])),
+    request_body(content = PutVoteBody, content_type = "application/json", description = "Vote of the user for an existing request"),
+    params(
+        ("id" = String, Path, description = "Request's unique id"),
+    ),
+    responses(
+        (status = 200, description = "Request successfully voted. Repeating the same vote returns the current state of the request",
+        example = json!(
+            Option::<String>::None;
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 409, description = "A different vote was already cast for the request"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn put_approval_handler(
+    request_id: String,
+    api_key: String,
+    node: NodeAPI,
+    votes: VotesInFlight,
+    identity: NodeIdentity,
+    audit: AuditLog,
+    body: PutVoteBody,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let target = request_id.clone();
+    audit
+        .audited(
+            "put_approval",
+            &api_key,
+            &target,
+            put_approval(request_id, node, votes, identity, body),
+        )
+        .await
+}
+
+async fn put_approval(
+    request_id: String,
+    node: NodeAPI,
+    votes: VotesInFlight,
+    identity: NodeIdentity,
+    body: PutVoteBody,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    validate_identifier("id", &request_id).map_err(validation_rejection)?;
+    // Another vote for the request may still be on its way to the node
+    match votes.claim(&request_id, &body).await {
+        VoteCheck::Repeated => return handle_data(node.get_single_request(request_id).await),
+        VoteCheck::Conflict => return Err(vote_conflict()),
+        VoteCheck::New => {}
+    }
+    let result = cast_vote(&request_id, &node, &identity, body).await;
+    votes.release(&request_id).await;
+    result
+}
+
+// Sends the vote to the node unless it already recorded one of this node for the request
+async fn cast_vote(
+    request_id: &str,
+    node: &NodeAPI,
+    identity: &NodeIdentity,
+    body: PutVoteBody,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let request = match node.get_single_request(request_id.to_owned()).await {
+        Ok(request) => request,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let approvals = request.approvals.iter().map(|approval| {
+        (
+            approval.signature.content.signer.to_str(),
+            approval.content.approval_type.clone(),
+        )
+    });
+    match check_vote(approvals, identity, &body) {
+        VoteCheck::Repeated => return handle_data(Ok(request)),
+        VoteCheck::Conflict => return Err(vote_conflict()),
+        VoteCheck::New => {}
+    }
+    let acceptance = match body {
+        PutVoteBody::Accept => Acceptance::Accept,
+        PutVoteBody::Reject => Acceptance::Reject,
+    };
+    handle_data(
+        node.approval_request(request_id.to_owned(), acceptance)
+            .await,
+    )
+}
+
+fn vote_conflict() -> Rejection {
+    warp::reject::custom(Error::Conflict(String::from(
+        "The vote of a request can not be changed",
+    )))
+}
+
+#[utoipa::path(
+    put,
+    path = "/approvals",
+    operation_id = "Set your Approval for a batch of requests",
+    tag = "Approvals",
+    context_path = "/api",
+    security(("api_key" = [])),
+    request_body(content = PutVotesBody, content_type = "application/json", description = "Votes of the user for existing requests"),
+    responses(
+        (status = 200, description = "Votes cast. Each one has the status that PUT /approvals/{id} would answer, so some may have failed", body = [BatchVoteResult],
+        example = json!(
+            [
+                {
+                    "request_id": "JhEnzFVF1a-u-rH34cix2A_OXgcfesM6HGOyk7wdrGHk",
+                    "status": 200
+                },
+                {
+                    "request_id": "J7nteIKy2WcAZE5l_N24A1ORR5YQzZxE5DzyfUdxKxz4",
+                    "status": 409,
+                    "error": "The vote of a request can not be changed"
+                }
+            ]
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn put_approvals_handler(
+    api_key: String,
+    node: NodeAPI,
+    votes: VotesInFlight,
+    identity: NodeIdentity,
+    audit: AuditLog,
+    body: PutVotesBody,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let mut results = Vec::with_capacity(body.votes.len());
+    // A failed vote does not stop the rest of the batch
+    for vote in body.votes {
+        let result = put_approval_handler(
+            vote.request_id.clone(),
+            api_key.clone(),
+            node.clone(),
+            votes.clone(),
+            identity.clone(),
+            audit.clone(),
+            vote.vote.into(),
+        )
+        .await;
+        results.push(vote_result(vote.request_id, result));
+    }
+    handle_data(Ok(results))
+}
+
+fn vote_result(
+    request_id: String,
+    result: Result<Box<dyn warp::Reply>, Rejection>,
+) -> BatchVoteResult {
+    let (status, error) = match result {
+        Ok(reply) => (warp::Reply::into_response(reply).status().as_u16(), None),
+        Err(rejection) => match rejection.find::<Error>() {
+            Some(error) => {
+                let problem = error.problem();
+                let detail = match problem.errors {
+                    Some(errors) => errors
+                        .iter()
+                        .map(|error| format!("{} {}", error.field, error.message))
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                    None => problem.detail,
+                };
+                (problem.status, Some(detail))
+            }
+            None => (500, Some(format!("{:?}", rejection))),
+        },
+    };
+    BatchVoteResult {
+        request_id,
+        status,
+        error,
+    }
+}
+#[utoipa::path(
+    get,
+    path = "/governances/{id}",
+    operation_id = "Get Governance Data",
+    tag = "Governances",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Governance's unique id")
+    ),
+    responses(
+        (status = 200, description = "Subject Data successfully retrieved", body = SubjectData, 
+            example = json!(
+                {
+                    "subject_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+                    "governance_id": "",
+                    "sn": 0,
+                    "public_key": "E2tlKVr6wA2GZKoSZi_dwIuz2TVUTCCDpOOwiE2SJbWc",
+                    "namespace": "",
+                    "schema_id": "",
+                    "owner": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                    "properties": "{\"members\":[{\"description\":\"Sede en España\",\"id\":\"Compañía1\",\"key\":\"EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w\",\"tags\":{}},{\"description\":\"Sede en Inglaterra\",\"id\":\"Compañía2\",\"key\":\"ECQnl-h1vEWmu-ZlPuweR3N1x6SUImyVdPrCLmnJJMyU\",\"tags\":{}}],\"schemas\":[{\"content\":{\"additionalProperties\":false,\"properties\":{\"localizacion\":{\"type\":\"string\"},\"temperatura\":{\"type\":\"integer\"}},\"required\":[\"temperatura\",\"localizacion\"],\"type\":\"object\"},\"id\":\"Prueba\",\"tags\":{}}]}"
+                }
+            )
+        ),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_governance_handler(
+    id: String,
+    node: NodeAPI,
+    _header: String,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let mut response = node.get_subject(id).await;
+    if response.is_ok() && !response.as_ref().unwrap().governance_id.digest.is_empty() {
+        response = Err(ApiError::NotFound(String::from(
+            "This ID does not belong to a governance",
+        )));
+    }
+    handle_data(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/governances/{id}/versions",
+    operation_id = "Get the version history of a Governance",
+    tag = "Governances",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Governance's unique id")
+    ),
+    responses(
+        (status = 200, description = "Versions of the governance from its genesis, each one with the event that introduced it", body = [GovernanceRevision]),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_governance_versions_handler(
+    id: String,
+    node: NodeAPI,
+    _header: String,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let governance = match node.get_subject(id.clone()).await {
+        Ok(governance) => governance,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    if !governance.governance_id.digest.is_empty() {
+        return handle_data::<()>(Err(ApiError::NotFound(String::from(
+            "This ID does not belong to a governance",
+        ))));
+    }
+    let events = node
+        .get_event_of_subject(id, Some(0), Some(governance.sn as i64 + 1))
+        .await;
+    handle_data(events.map(|events| {
+        events
+            .into_iter()
+            .map(GovernanceRevision::from)
+            .collect::<Vec<_>>()
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/governances",
+    operation_id = "Get all Governances data",
+    tag = "Governances",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a previous response. The list is only sent if it changed since then"),
+        ("order_by" = Option<GovernanceOrder>, Query, description = "governance_id (default) or sn. Governances with the same sn are sorted by id"),
+        ("order" = Option<SortOrder>, Query, description = "asc (default) or desc"),
+        ("truncate" = Option<usize>, Query, description = "Characters of the properties of each item that are kept. Longer properties end with an ellipsis and their items are marked with truncated. Defaults to no truncation"),
+    ),
+    responses(
+        (status = 200, description = "Subjets Data successfully retrieved", body = [RequestPayload],
+        example = json!(
+            [
+                {
+                    "subject_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+                    "governance_id": "",
+                    "sn": 0,
+                    "public_key": "E2tlKVr6wA2GZKoSZi_dwIuz2TVUTCCDpOOwiE2SJbWc",
+                    "namespace": "",
+                    "schema_id": "",
+                    "owner": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                    "properties": "{\"members\":[{\"description\":\"Sede en España\",\"id\":\"Compañía1\",\"key\":\"EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w\",\"tags\":{}},{\"description\":\"Sede en Inglaterra\",\"id\":\"Compañía2\",\"key\":\"ECQnl-h1vEWmu-ZlPuweR3N1x6SUImyVdPrCLmnJJMyU\",\"tags\":{}}],\"schemas\":[{\"content\":{\"additionalProperties\":false,\"properties\":{\"localizacion\":{\"type\":\"string\"},\"temperatura\":{\"type\":\"integer\"}},\"required\":[\"temperatura\",\"localizacion\"],\"type\":\"object\"},\"id\":\"Prueba\",\"tags\":{}}]}"
+                }
+            ]
+        )),
+        (status = 304, description = "The governances did not change since the given ETag"),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_all_governances_handler(
+    _header: String,
+    node: NodeAPI,
+    if_none_match: Option<String>,
+    parameters: GetAllGovernancesQuery,
+    shaping: Shaping,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let mut governances = match node.get_all_governances().await {
+        Ok(governances) => governances,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let order_by = parameters.order_by.unwrap_or(GovernanceOrder::GovernanceId);
+    let order = parameters.order.unwrap_or(SortOrder::Asc);
+    sort_governances(&mut governances, order_by, order, |governance| {
+        (governance.subject_id.to_str(), governance.sn)
+    });
+    // The order is part of the representation, as the same governances sorted otherwise are
+    // other bytes
+    let representation = format!(
+        "{};order_by={:?};order={:?}",
+        shaping.representation(),
+        order_by,
+        order
+    );
+    let etag = aggregate_etag(
+        governances
+            .iter()
+            .map(|governance| (governance.subject_id.to_str(), governance.sn)),
+        &representation,
+    );
+    if !none_match(if_none_match.as_deref(), &etag) {
+        return Ok(Box::new(warp::reply::with_header(
+            warp::http::StatusCode::NOT_MODIFIED,
+            warp::http::header::ETAG,
+            etag,
+        )));
+    }
+    let reply = handle_data(Ok(governances))?;
+    Ok(Box::new(warp::reply::with_header(
+        reply,
+        warp::http::header::ETAG,
+        etag,
+    )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/schemas/bundle",
+    operation_id = "Get every Schema of every Governance",
+    tag = "Governances",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a previous response. The bundle is only sent if a governance changed since then"),
+    ),
+    responses(
+        (status = 200, description = "Schemas of all the governances, with the governance version they were taken from. The ETag header changes with any new governance event", body = [BundledSchema],
+        example = json!(
+            [
+                {
+                    "governance_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+                    "governance_version": 0,
+                    "schema_id": "Prueba",
+                    "schema": {
+                        "additionalProperties": false,
+                        "properties": {
+                            "localizacion": { "type": "string" },
+                            "temperatura": { "type": "integer" }
+                        },
+                        "required": ["temperatura", "localizacion"],
+                        "type": "object"
+                    }
+                }
+            ]
+        )),
+        (status = 304, description = "No governance changed since the given ETag"),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_schema_bundle_handler(
+    _header: String,
+    node: NodeAPI,
+    if_none_match: Option<String>,
+    shaping: Shaping,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let governances = match node.get_all_governances().await {
+        Ok(governances) => governances,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let etag = aggregate_etag(
+        governances
+            .iter()
+            .map(|governance| (governance.subject_id.to_str(), governance.sn)),
+        &shaping.representation(),
+    );
+    if !none_match(if_none_match.as_deref(), &etag) {
+        return Ok(Box::new(warp::reply::with_header(
+            warp::http::StatusCode::NOT_MODIFIED,
+            warp::http::header::ETAG,
+            etag,
+        )));
+    }
+    let bundle = schema_bundle(governances.into_iter().filter_map(|governance| {
+        let properties = serde_json::from_str(&governance.properties).ok()?;
+        Some((governance.subject_id.to_str(), governance.sn, properties))
+    }));
+    let reply = handle_data(Ok(bundle))?;
+    Ok(Box::new(warp::reply::with_header(
+        reply,
+        warp::http::header::ETAG,
+        etag,
+    )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/governances/member-of",
+    tag = "Governances",
+    operation_id = "Get the Governances the node is a member of",
+    context_path = "/api",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Member entries of the governances whose key is one of the node identities, with their description and tags", body = [Membership],
+        example = json!(
+            [
+                {
+                    "governance_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+                    "member_id": "Compañía1",
+                    "key": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                    "description": "Sede en España",
+                    "tags": {}
+                }
+            ]
+        )),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_member_of_handler(
+    _header: String,
+    node: NodeAPI,
+    identity: NodeIdentity,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let governances = match node.get_all_governances().await {
+        Ok(governances) => governances,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let governances = governances.into_iter().filter_map(|governance| {
+        let properties = serde_json::from_str(&governance.properties).ok()?;
+        Some((governance.subject_id.to_str(), properties))
+    });
+    handle_data(Ok(memberships(governances, |key| identity.owns(key))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/governances",
+    tag = "Governances",
+    operation_id = "Create a new Governance",
+    context_path = "/api",
+    security(("api_key" = [])),
+    request_body(content = PostGovernanceBody, content_type = "application/json", description = "Payload of governance, with members and schemas specification"),
+    params(
+        ("Prefer" = Option<String>, Header, description = "return=minimal answers with just the request and subject ids, return=representation (default) with the whole request. The applied preference is echoed in Preference-Applied"),
+    ),
+    responses(
+        (status = 201, description = "Governance Created. Location points at the new governance", body = String,  example = json!("\"JE-MDb4J-hwyTW8z6TU32rzacz27so3eBNt88m8qoRSY\"")),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 422, description = "The governance repeats schema ids, member ids or member keys"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn post_governance_handler(
+    api_key: String,
+    node: NodeAPI,
+    audit: AuditLog,
+    prefer: Option<String>,
+    body: PostGovernanceBody,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    audit
+        .audited(
+            "post_governance",
+            &api_key,
+            "",
+            post_governance(node, prefer, body),
+        )
+        .await
+}
+
+async fn post_governance(
+    node: NodeAPI,
+    prefer: Option<String>,
+    body: PostGovernanceBody,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    validate_governance_payload(&body.payload).map_err(validation_rejection)?;
+    check_unique_governance_ids(&body.payload)?;
+    let payload = body.payload.into();
+    let data = node.create_governance(payload).await;
+    handle_created(data, ReturnPreference::from_header(prefer))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/governances/{id}",
+    tag = "Governances",
+    operation_id = "Update a Governance with a JSON Patch",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Governance's unique id"),
+    ),
+    request_body(content = Object, content_type = "application/json", description = "RFC 6902 JSON Patch applied to the current governance properties"),
+    responses(
+        (status = 202, description = "Event Request Created", body = RequestData),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 422, description = "The patched governance is not valid"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn patch_governance_handler(
+    id: String,
+    api_key: String,
+    node: NodeAPI,
+    audit: AuditLog,
+    body: json_patch::Patch,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let target = id.clone();
+    audit
+        .audited(
+            "patch_governance",
+            &api_key,
+            &target,
+            apply_governance_patch(id, node, body),
+        )
+        .await
+}
+
+async fn apply_governance_patch(
+    id: String,
+    node: NodeAPI,
+    body: json_patch::Patch,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let governance = match node.get_subject(id.clone()).await {
+        Ok(governance) if governance.governance_id.digest.is_empty() => governance,
+        Ok(_) => return Err(warp::reject::custom(Error::NotFound)),
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    patch_governance(&governance.properties, &body)
+        .map_err(|errors| warp::reject::custom(Error::Unprocessable(errors)))?;
+    let request = CreateRequest::State(StateType {
+        subject_id: id,
+        payload: RequestPayload::JsonPatch(serde_json::to_string(&body).unwrap()),
+    });
+    let data = node.create_request(request).await;
+    handle_data(data)
+}
+
+#[utoipa::path(
+    post,
+    path = "/governances/{id}/impact",
+    tag = "Governances",
+    operation_id = "Preview the impact of a new governance version",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Governance's unique id"),
+    ),
+    request_body(content = PostGovernanceBody, content_type = "application/json", description = "Proposed payload of the governance. The properties of every subject of the governance are validated against its schemas, so the cost of this operation grows with the number of subjects"),
+    responses(
+        (status = 200, description = "Subjects that would no longer validate", body = [ImpactedSubject],
+        example = json!(
+            [
+                {
+                    "subject_id": "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
+                    "schema_id": "Prueba",
+                    "reason": "\"localizacion\" is a required property"
+                }
+            ]
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn post_governance_impact_handler(
+    id: String,
+    _header: String,
+    node: NodeAPI,
+    body: PostGovernanceBody,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    validate_governance_payload(&body.payload).map_err(validation_rejection)?;
+    let governance = node.get_subject(id.clone()).await;
+    if governance.is_ok() && !governance.as_ref().unwrap().governance_id.digest.is_empty() {
+        return Err(warp::reject::custom(Error::NotFound));
+    }
+    if let Err(error) = governance {
+        return handle_data::<()>(Err(error));
+    }
+    let Payload::Json(proposed) = body.payload else {
+        return Err(warp::reject::custom(Error::InvalidParameters));
+    };
+    let subjects = match node.get_all_subjects(String::from(""), None, None).await {
+        Ok(subjects) => subjects,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let governed = subjects
+        .into_iter()
+        .filter(|subject| subject.governance_id.to_str() == id)
+        .map(|subject| {
+            (
+                subject.subject_id.to_str(),
+                subject.schema_id,
+                subject.properties,
+            )
+        })
+        .collect();
+    handle_data(Ok(governance_impact(&proposed, governed)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/governances/{id}/activity",
+    operation_id = "Get the latest Events of the Subjects of a Governance",
+    context_path = "/api",
+    tag = "Governances",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Governance's unique id"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of events returned. Defaults to 20"),
+    ),
+    responses(
+        (status = 200, description = "Most recent events of the subjects governed by the governance, newest first", body = [Event]),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_governance_activity_handler(
+    id: String,
+    _header: String,
+    node: NodeAPI,
+    parameters: ActivityQuery,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    match node.get_subject(id.clone()).await {
+        Ok(governance) if governance.governance_id.digest.is_empty() => {}
+        Ok(_) => return Err(warp::reject::custom(Error::NotFound)),
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let subjects = match node.get_all_subjects(String::from(""), None, None).await {
+        Ok(subjects) => subjects,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let mut events = Vec::new();
+    for subject in subjects {
+        if subject.governance_id.to_str() != id {
+            continue;
+        }
+        match node
+            .get_event_of_subject(subject.subject_id.to_str(), None, None)
+            .await
+        {
+            Ok(subject_events) => events.extend(subject_events),
+            Err(error) => return handle_data::<()>(Err(error)),
+        }
+    }
+    let activity = latest_activity(events, parameters.limit.unwrap_or(20), |event| {
+        event.event_content.event_request.timestamp
+    });
+    handle_data(Ok(activity))
+}
+
+#[utoipa::path(
+    get,
+    path = "/requests/rejected",
+    operation_id = "Get the recently rejected Requests",
+    context_path = "/api",
+    tag = "Requests",
+    security(("api_key" = [])),
+    params(
+        ("limit" = Option<usize>, Query, description = "Maximum number of requests returned. Defaults to 20"),
+    ),
+    responses(
+        (status = 200, description = "Requests whose event was not approved, newest first, with the approvers that rejected them. The node does not record a rejection reason yet, so it is always null", body = [RejectedRequest],
+        example = json!(
+            [
+                {
+                    "request_id": "JEwhXGrDOJ7bQlEgvGMEHDRUKKRk7eJZjLJxCvFjl4vo",
+                    "subject_id": "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
+                    "sn": 3,
+                    "timestamp": 1671705820,
+                    "rejected_by": ["ECQnl-h1vEWmu-ZlPuweR3N1x6SUImyVdPrCLmnJJMyU"],
+                    "reason": null
+                }
+            ]
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_rejected_requests_handler(
+    _header: String,
+    node: NodeAPI,
+    parameters: ActivityQuery,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let subjects = match node.get_all_subjects(String::from(""), None, None).await {
+        Ok(subjects) => subjects,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let mut rejected = Vec::new();
+    for subject in subjects {
+        let events = match node
+            .get_event_of_subject(subject.subject_id.to_str(), None, None)
+            .await
+        {
+            Ok(events) => events,
+            Err(error) => return handle_data::<()>(Err(error)),
+        };
+        for event in events {
+            if event.event_content.approved {
+                continue;
+            }
+            let votes = event
+                .event_content
+                .event_request
+                .approvals
+                .iter()
+                .map(|approval| {
+                    (
+                        approval.signature.content.signer.to_str(),
+                        approval.content.approval_type.clone(),
+                    )
+                })
+                .collect();
+            rejected.push(RejectedRequest::new(
+                request_id_of(&event),
+                subject.subject_id.to_str(),
+                event.event_content.sn,
+                event.event_content.event_request.timestamp,
+                votes,
+            ));
+        }
+    }
+    let rejected = latest_activity(rejected, parameters.limit.unwrap_or(20), |request| {
+        request.timestamp
+    });
+    handle_data(Ok(rejected))
+}
+
+#[utoipa::path(
+    get,
+    path = "/governances/{id}/schemas/{schema_id}/ui",
+    operation_id = "Get the form layout of a Schema",
+    tag = "Governances",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Governance's unique id"),
+        ("schema_id" = String, Path, description = "Id of the schema in the governance"),
+    ),
+    responses(
+        (status = 200, description = "Fields of the schema in layout order with their type and widget. The order and widgets come from the x-ui extension of the schema when present, otherwise required fields go first and widgets are derived from the types. Every x- keyword of a field is returned in its hints", body = SchemaUi,
+        example = json!(
+            {
+                "schema_id": "Prueba",
+                "fields": [
+                    {
+                        "name": "localizacion",
+                        "field_type": "string",
+                        "required": true,
+                        "widget": "text",
+                        "hints": {}
+                    },
+                    {
+                        "name": "temperatura",
+                        "field_type": "integer",
+                        "required": true,
+                        "widget": "number",
+                        "hints": {}
                     }
-                },
-                "request_id": "JpxalqMTQcDcLG3dwb8uvcrstJo6pmFEzUwhzi0nGPOA",
-                "timestamp": 1671705355,
-                "subject_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
-                "sn": 0
+                ]
             }
         )),
         (status = 400, description = "Bad Request"),
         (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
         (status = 500, description = "Internal Server Error"),
     )
 )]
-pub async fn post_event_request_handler(
+pub async fn get_schema_ui_handler(
+    id: String,
+    schema_id: String,
     _header: String,
     node: NodeAPI,
-    body: PostEventRequestBody,
 ) -> Result<Box<dyn warp::Reply>, Rejection> {
-    let data;
-    if body.signature.is_none() && body.timestamp.is_none() {
-        data = node.create_request(body.request.into()).await;
-    } else if body.signature.is_some() && body.timestamp.is_some() {
-        if let Ok(external_request) = body.try_into() {
-            data = node.external_request(external_request).await;
-        } else {
-            data = Err(ApiError::InvalidParameters);
-        }
-    } else {
-        data = Err(ApiError::InvalidParameters);
-    }
-    log::info!("data: {:?}", data);
-    handle_data(data)
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let governance = match node.get_subject(id).await {
+        Ok(governance) if governance.governance_id.digest.is_empty() => governance,
+        Ok(_) => return Err(warp::reject::custom(Error::NotFound)),
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let properties: serde_json::Value = serde_json::from_str(&governance.properties)
+        .map_err(|_| warp::reject::custom(Error::InternalServerError))?;
+    let Some(schema) = find_schema(&properties, &schema_id) else {
+        return Err(warp::reject::custom(Error::NotFound));
+    };
+    handle_data(Ok(schema_ui(&schema_id, schema)))
 }
 
-// #[utoipa::path(
-//     post,
-//     path = "/requests/external",
-//     tag = "Requests",
-//     operation_id = "Send an Event Request to make and external invokation",
-//     context_path = "/api",
-//     security(("api_key" = [])),
-//     request_body(content = ExternalEventRequestBody, content_type = "application/json", description = "Event Request"),
-//     responses(
-//         (status = 202, description = "Event Request Created", body = RequestData),
-//         (status = 400, description = "Bad Request"),
-//         (status = 401, description = "Unauthorized"),
-//         (status = 500, description = "Internal Server Error"),
-//     )
-// )]
-// pub async fn post_external_request_handler(
-//     _header: String,
-//     node: NodeAPI,
-//     body: ExternalEventRequestBody,
-// ) -> Result<Box<dyn warp::Reply>, Rejection> {
-//     let data = node.external_request(body.into()).await;
-//     handle_data(data)
-// }
-
 #[utoipa::path(
     get,
-    path = "/approvals",
-    tag = "Approvals",
-    operation_id = "Get all the pending requests for Approval",
+    path = "/governances/{id}/approvers/stats",
+    operation_id = "Get approval statistics per approver of a Governance",
     context_path = "/api",
+    tag = "Governances",
     security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Governance's unique id"),
+    ),
     responses(
-        (status = 200, description = "All pending requests", body =  [EventRequest],
+        (status = 200, description = "Votes cast by each approver in the resolved requests of the governance and its subjects. Members without votes are included", body = [ApproverStats],
         example = json!(
             [
                 {
-                    "request": {
-                        "State": {
-                            "subject_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
-                            "payload": {
-                                "Json": "{\"members\":[{\"description\":\"Sede en España\",\"id\":\"Compañía1\",\"key\":\"EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w\",\"tags\":{}}],\"schemas\":[{\"content\":{\"additionalProperties\":false,\"properties\":{\"localizacion\":{\"type\":\"string\"},\"temperatura\":{\"type\":\"integer\"}},\"required\":[\"temperatura\",\"localizacion\"],\"type\":\"object\"},\"id\":\"Prueba\",\"tags\":{}}]}"
-                            }
-                        }
-                    },
-                    "timestamp": 1671709394,
-                    "signature": {
-                        "content": {
-                            "signer": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
-                            "event_content_hash": "JhEnzFVF1a-u-rH34cix2A_OXgcfesM6HGOyk7wdrGHk",
-                            "timestamp": 1671709394
-                        },
-                        "signature": "SEnUJq3Y1lbmijKzrc0kuLu-FgMTCyo5PWfDrbi_80bspghCny8Yuvifsmdqq0TjfTUS7sEwmOLir1W_1zeIVyDQ"
-                    },
-                    "approvals": []
+                    "key": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                    "member": "Company",
+                    "approvals": 12,
+                    "rejections": 1
                 }
             ]
         )),
@@ -308,211 +2628,239 @@ pub async fn post_event_request_handler(
         (status = 500, description = "Internal Server Error"),
     )
 )]
-pub async fn get_pending_requests_handler(
-    node: NodeAPI,
+pub async fn get_approver_stats_handler(
+    id: String,
     _header: String,
+    node: NodeAPI,
 ) -> Result<Box<dyn warp::Reply>, Rejection> {
-    let data = node.get_pending_requests().await;
-    handle_data(data)
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let governance = match node.get_subject(id.clone()).await {
+        Ok(governance) if governance.governance_id.digest.is_empty() => governance,
+        Ok(_) => return Err(warp::reject::custom(Error::NotFound)),
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let properties: serde_json::Value = serde_json::from_str(&governance.properties)
+        .map_err(|_| warp::reject::custom(Error::InternalServerError))?;
+    let subjects = match node.get_all_subjects(String::from(""), None, None).await {
+        Ok(subjects) => subjects,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    // Requests of the governance itself are resolved by its members too
+    let subject_ids = subjects
+        .into_iter()
+        .filter(|subject| subject.governance_id.to_str() == id)
+        .map(|subject| subject.subject_id.to_str())
+        .chain(std::iter::once(id));
+    let mut votes = Vec::new();
+    for subject_id in subject_ids {
+        let events = match node.get_event_of_subject(subject_id, None, None).await {
+            Ok(events) => events,
+            Err(error) => return handle_data::<()>(Err(error)),
+        };
+        for event in events {
+            votes.extend(
+                event
+                    .event_content
+                    .event_request
+                    .approvals
+                    .into_iter()
+                    .map(|approval| {
+                        (
+                            approval.signature.content.signer.to_str(),
+                            approval.content.approval_type,
+                        )
+                    }),
+            );
+        }
+    }
+    handle_data(Ok(approver_stats(&properties, votes)))
 }
 
 #[utoipa::path(
-    get,
-    path = "/approvals/{id}",
-    tag = "Approvals",
-    operation_id = "Get a specific pending request for Approval",
+    post,
+    path = "/admin/maintenance",
+    operation_id = "Toggle the maintenance mode",
     context_path = "/api",
+    tag = "Admin",
     security(("api_key" = [])),
+    request_body(content = PostMaintenanceBody, content_type = "application/json", description = "Whether writes are blocked and the seconds clients should wait before retrying them"),
     responses(
-        (status = 200, description = "The pending request", body = EventRequest,
+        (status = 200, description = "Maintenance mode updated. While enabled, writes and approvals are answered with 503 and reads keep working", body = MaintenanceStatus,
         example = json!(
             {
-                "request": {
-                    "State": {
-                        "subject_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
-                        "payload": {
-                            "Json": "{\"members\":[{\"description\":\"Sede en España\",\"id\":\"Compañía1\",\"key\":\"EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w\",\"tags\":{}}],\"schemas\":[{\"content\":{\"additionalProperties\":false,\"properties\":{\"localizacion\":{\"type\":\"string\"},\"temperatura\":{\"type\":\"integer\"}},\"required\":[\"temperatura\",\"localizacion\"],\"type\":\"object\"},\"id\":\"Prueba\",\"tags\":{}}]}"
-                        }
-                    }
-                },
-                "timestamp": 1671709394,
-                "signature": {
-                    "content": {
-                        "signer": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
-                        "event_content_hash": "JhEnzFVF1a-u-rH34cix2A_OXgcfesM6HGOyk7wdrGHk",
-                        "timestamp": 1671709394
-                    },
-                    "signature": "SEnUJq3Y1lbmijKzrc0kuLu-FgMTCyo5PWfDrbi_80bspghCny8Yuvifsmdqq0TjfTUS7sEwmOLir1W_1zeIVyDQ"
-                },
-                "approvals": []
+                "enabled": true,
+                "retry_after": 60
             }
         )),
         (status = 400, description = "Bad Request"),
         (status = 401, description = "Unauthorized"),
-        (status = 404, description = "Not Found"),
-        (status = 500, description = "Internal Server Error"),
     )
 )]
-pub async fn get_single_request_handler(
-    id: String,
-    node: NodeAPI,
-    _header: String,
+pub async fn post_maintenance_handler(
+    api_key: String,
+    maintenance: Maintenance,
+    audit: AuditLog,
+    body: PostMaintenanceBody,
 ) -> Result<Box<dyn warp::Reply>, Rejection> {
-    let data = node.get_single_request(id).await;
-    handle_data(data)
+    audit
+        .audited("post_maintenance", &api_key, "maintenance", async move {
+            handle_data(Ok(maintenance.set(body.enabled, body.retry_after)))
+        })
+        .await
 }
 
 #[utoipa::path(
-    put,
-    path = "/approvals{id}",
-    operation_id = "Set your Aprroval for a request",
-    tag = "Approvals",
+    get,
+    path = "/identities/{key}/encodings",
+    operation_id = "Get the encodings of a public key",
     context_path = "/api",
+    tag = "Identities",
     security(("api_key" = [])),
-    request_body(content = PutVoteBody, content_type = "application/json", description = "Vote of the user for an existing request"),
     params(
-        ("id" = String, Path, description = "Request's unique id"),
+        ("key" = String, Path, description = "Public key, as used by the node"),
     ),
     responses(
-        (status = 200, description = "Request successfully voted",
+        (status = 200, description = "Bytes of the public key in the encodings used by other tools", body = KeyEncodings,
         example = json!(
-            Option::<String>::None;
+            {
+                "key": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                "base58": "2SsBvVawEPYbEfWZqEDtsmu5pDkYpXifYjssMXQctGDH",
+                "base64": "FXv0jBIr6BtoqFMR7G/JBSuozRc2jZnu5VGUH2gy6+w=",
+                "hex": "157bf48c122be81b68a85311ec6fc9052ba8cd17368d99eee551941f6832ebec"
+            }
         )),
         (status = 400, description = "Bad Request"),
         (status = 401, description = "Unauthorized"),
-        (status = 404, description = "Not Found"),
-        (status = 500, description = "Internal Server Error"),
     )
 )]
-pub async fn put_approval_handler(
-    request_id: String,
+pub async fn get_key_encodings_handler(
+    key: String,
     _header: String,
-    node: NodeAPI,
-    body: PutVoteBody,
 ) -> Result<Box<dyn warp::Reply>, Rejection> {
-    let acceptance = match body {
-        PutVoteBody::Accept => Acceptance::Accept,
-        PutVoteBody::Reject => Acceptance::Reject,
-    };
-    let data = node.approval_request(request_id, acceptance).await;
-    handle_data(data)
+    let identifier = validate_key("key", &key).map_err(validation_rejection)?;
+    handle_data(Ok(KeyEncodings::new(&key, &identifier.public_key)))
 }
+
 #[utoipa::path(
     get,
-    path = "/governances/{id}",
-    operation_id = "Get Governance Data",
-    tag = "Governances",
+    path = "/admin/diagnostics",
+    operation_id = "Get the last internal errors",
     context_path = "/api",
+    tag = "Admin",
     security(("api_key" = [])),
-    params(
-        ("id" = String, Path, description = "Governance's unique id")
-    ),
     responses(
-        (status = 200, description = "Subject Data successfully retrieved", body = SubjectData, 
-            example = json!(
+        (status = 200, description = "Last internal errors of the API, most recent first. Only the error messages are kept, not the bodies of the requests", body = [Diagnostic],
+        example = json!(
+            [
                 {
-                    "subject_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
-                    "governance_id": "",
-                    "sn": 0,
-                    "public_key": "E2tlKVr6wA2GZKoSZi_dwIuz2TVUTCCDpOOwiE2SJbWc",
-                    "namespace": "",
-                    "schema_id": "",
-                    "owner": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
-                    "properties": "{\"members\":[{\"description\":\"Sede en España\",\"id\":\"Compañía1\",\"key\":\"EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w\",\"tags\":{}},{\"description\":\"Sede en Inglaterra\",\"id\":\"Compañía2\",\"key\":\"ECQnl-h1vEWmu-ZlPuweR3N1x6SUImyVdPrCLmnJJMyU\",\"tags\":{}}],\"schemas\":[{\"content\":{\"additionalProperties\":false,\"properties\":{\"localizacion\":{\"type\":\"string\"},\"temperatura\":{\"type\":\"integer\"}},\"required\":[\"temperatura\",\"localizacion\"],\"type\":\"object\"},\"id\":\"Prueba\",\"tags\":{}}]}"
+                    "timestamp": 1671705355,
+                    "method": "GET",
+                    "route": "/api/subjects/JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
+                    "correlation_id": "1671705355-1",
+                    "error": "Internal Server Error"
                 }
-            )
-        ),
-        (status = 400, description = "Bad Request"),
+            ]
+        )),
         (status = 401, description = "Unauthorized"),
-        (status = 404, description = "Not Found"),
-        (status = 500, description = "Internal Server Error"),
     )
 )]
-pub async fn get_governance_handler(
-    id: String,
-    node: NodeAPI,
+pub async fn get_diagnostics_handler(
     _header: String,
+    diagnostics: Diagnostics,
 ) -> Result<Box<dyn warp::Reply>, Rejection> {
-    if id.is_empty() {
-        return Err(warp::reject::custom(Error::RequestError(
-            "Error in query parameter".to_owned(),
-        )));
-    }
-    let mut response = node.get_subject(id).await;
-    if response.is_ok() && !response.as_ref().unwrap().governance_id.digest.is_empty() {
-        response = Err(ApiError::NotFound(String::from(
-            "This ID does not belong to a governance",
-        )));
-    }
-    handle_data(response)
+    handle_data(Ok(diagnostics.recent()))
 }
 
 #[utoipa::path(
     get,
-    path = "/governances",
-    operation_id = "Get all Governances data",
-    tag = "Governances",
+    path = "/routes",
+    operation_id = "List the routes of the API",
     context_path = "/api",
+    tag = "Routes",
     security(("api_key" = [])),
     responses(
-        (status = 200, description = "Subjets Data successfully retrieved", body = [RequestPayload],
+        (status = 200, description = "Every route served with its methods and whether it requires the API key", body = [RouteInfo],
         example = json!(
             [
                 {
-                    "subject_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
-                    "governance_id": "",
-                    "sn": 0,
-                    "public_key": "E2tlKVr6wA2GZKoSZi_dwIuz2TVUTCCDpOOwiE2SJbWc",
-                    "namespace": "",
-                    "schema_id": "",
-                    "owner": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
-                    "properties": "{\"members\":[{\"description\":\"Sede en España\",\"id\":\"Compañía1\",\"key\":\"EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w\",\"tags\":{}},{\"description\":\"Sede en Inglaterra\",\"id\":\"Compañía2\",\"key\":\"ECQnl-h1vEWmu-ZlPuweR3N1x6SUImyVdPrCLmnJJMyU\",\"tags\":{}}],\"schemas\":[{\"content\":{\"additionalProperties\":false,\"properties\":{\"localizacion\":{\"type\":\"string\"},\"temperatura\":{\"type\":\"integer\"}},\"required\":[\"temperatura\",\"localizacion\"],\"type\":\"object\"},\"id\":\"Prueba\",\"tags\":{}}]}"
+                    "path": "/api/subjects/{id}",
+                    "methods": ["GET"],
+                    "auth": true
                 }
             ]
         )),
-        (status = 400, description = "Bad Request"),
         (status = 401, description = "Unauthorized"),
-        (status = 500, description = "Internal Server Error"),
     )
 )]
-pub async fn get_all_governances_handler(
+pub async fn get_routes_handler(
     _header: String,
-    node: NodeAPI,
+    catalog: RouteCatalog,
 ) -> Result<Box<dyn warp::Reply>, Rejection> {
-    fn convert_to_usize(data: Option<String>) -> Option<usize> {
-        if data.is_some() {
-            let tmp = data.unwrap();
-            return Some(tmp.parse::<usize>().unwrap());
-        }
-        None
-    }
-    let data = node.get_all_governances().await;
-    handle_data(data)
+    handle_data(Ok(catalog.routes()))
 }
 
 #[utoipa::path(
-    post,
-    path = "/governances",
-    tag = "Governances",
-    operation_id = "Create a new Governance",
+    get,
+    path = "/resolve",
+    operation_id = "Resolve an alias",
     context_path = "/api",
+    tag = "Subjects",
     security(("api_key" = [])),
-    request_body(content = PostGovernanceBody, content_type = "application/json", description = "Payload of governance, with members and schemas specification"),
+    params(
+        ("name" = String, Query, description = "Alias to look up. Subjects match by their name or tag property and members by their id in a governance"),
+    ),
     responses(
-        (status = 202, description = "Governance Created", body = String,  example = json!("\"JE-MDb4J-hwyTW8z6TU32rzacz27so3eBNt88m8qoRSY\"")),
+        (status = 200, description = "Every member and subject known by the alias. Aliases are not unique, so several matches may be returned and it is up to the client to choose between them", body = [ResolvedAlias],
+        example = json!(
+            [
+                {
+                    "kind": "Member",
+                    "id": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                    "governance_id": "JGSPR6FL-vE7iZxWMd17o09qn7NeTqlcImDVWmijXczw"
+                }
+            ]
+        )),
         (status = 400, description = "Bad Request"),
         (status = 401, description = "Unauthorized"),
+        (status = 404, description = "No member or subject is known by the alias"),
         (status = 500, description = "Internal Server Error"),
     )
 )]
-pub async fn post_governance_handler(
+pub async fn get_resolve_alias_handler(
     _header: String,
     node: NodeAPI,
-    body: PostGovernanceBody,
+    parameters: ResolveQuery,
 ) -> Result<Box<dyn warp::Reply>, Rejection> {
-    let payload = body.payload.into();
-    let data = node.create_governance(payload).await;
-    handle_data(data)
+    if parameters.name.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    let subjects = match node.get_all_subjects(String::from(""), None, None).await {
+        Ok(subjects) => subjects,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let subjects = subjects
+        .into_iter()
+        .map(|subject| {
+            let is_governance = subject.governance_id.digest.is_empty();
+            (
+                subject.subject_id.to_str(),
+                is_governance,
+                subject.properties,
+            )
+        })
+        .collect();
+    let resolved = resolve_alias(&parameters.name, subjects);
+    if resolved.is_empty() {
+        return Err(warp::reject::custom(Error::NotFound));
+    }
+    handle_data(Ok(resolved))
 }
 
 #[utoipa::path(
@@ -524,8 +2872,12 @@ pub async fn post_governance_handler(
     security(("api_key" = [])),
     params(
         ("id" = String, Path, description = "Subject's unique id"),
-        ("from" = Option<usize>, Query, description = "Initial SN"),
-        ("quantity" = Option<usize>, Query, description = "Quantity of events requested"),
+        ("from" = Option<usize>, Query, description = "Initial SN. Past the last event an empty list is returned with the X-Out-Of-Range header, and out_of_range in the envelope metadata"),
+        ("quantity" = Option<usize>, Query, description = "Quantity of events requested. If some events of the range are not stored in the node, the available ones are returned with the X-Missing-Sns header listing the rest, and missing_sns in the envelope metadata"),
+        ("tail" = Option<usize>, Query, description = "Quantity of most recent events requested, returned in ascending order unless order is desc. Can not be combined with from or quantity"),
+        ("order" = Option<SortOrder>, Query, description = "asc (default) returns the events by ascending sn, desc newest first. With desc, from is the sn of the first event returned and the rest count downward from it"),
+        ("fields" = Option<String>, Query, description = "Comma separated event fields to return: subject_id, sn, previous_hash, state_hash, metadata, approved, request, timestamp, approvals, signature. Unknown fields are ignored. JSON Pointers (starting with /) are applied afterwards to each projected event, as in every other route"),
+        ("with_state" = Option<bool>, Query, description = "Add to each event the properties of the subject after applying it. The subject is replayed from its genesis up to the last returned event, so the cost grows with the sn of the page and not with its size"),
     ),
     responses(
         (status = 200, description = "Subjects Data successfully retrieved", body = [Event],
@@ -621,29 +2973,223 @@ pub async fn post_governance_handler(
                         "signature": "SEDcHW5nM7HPsQJyHQkAVaV5NkTuwT2fJL_T9r0HmqbgT3Wt7AMTFpjJNunlCSa-dEosItNu5P9k05vAE9064TBg"
                     }
                 }
-            ]
-        )),
+            ]
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_events_of_subject_handler(
+    id: String,
+    node: NodeAPI,
+    _header: String,
+    parameters: GetEventsQuery,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let subject = match node.get_subject(id.clone()).await {
+        Ok(subject) => subject,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let descending = parameters.order == Some(SortOrder::Desc);
+    let (from, quantity) = match parameters.tail {
+        // The node returns ascending ranges, so the descending one is fetched and then reversed
+        None if descending => {
+            let start = match parameters.from {
+                Some(from) if from < 0 => {
+                    return Err(warp::reject::custom(Error::RequestError(
+                        "from can not be negative".to_owned(),
+                    )));
+                }
+                Some(from) => from.min(subject.sn as i64),
+                None => subject.sn as i64,
+            };
+            let quantity = parameters
+                .quantity
+                .map_or(start + 1, |quantity| quantity.clamp(0, start + 1));
+            (Some(start + 1 - quantity), Some(quantity))
+        }
+        None => (parameters.from, parameters.quantity),
+        Some(_) if parameters.from.is_some() || parameters.quantity.is_some() => {
+            return Err(warp::reject::custom(Error::RequestError(
+                "tail can not be combined with from or quantity".to_owned(),
+            )));
+        }
+        Some(tail) if tail < 1 => {
+            return Err(warp::reject::custom(Error::RequestError(
+                "tail must be greater than 0".to_owned(),
+            )));
+        }
+        Some(tail) => {
+            let count = subject.sn as i64 + 1;
+            (Some((count - tail).max(0)), Some(tail.min(count)))
+        }
+    };
+    let data = node.get_event_of_subject(id.clone(), from, quantity).await;
+    let mut events = match data {
+        Ok(events) => events,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let from = from.unwrap_or(0);
+    if events.is_empty() && from > 0 && from as u64 > subject.sn {
+        return Ok(out_of_range(handle_data(Ok(events))?));
+    }
+    // Sns of the requested range that are not stored, as some events may have been pruned
+    let last = match quantity {
+        None => Some(subject.sn),
+        Some(quantity) if quantity > 0 => Some(subject.sn.min((from + quantity - 1) as u64)),
+        Some(_) => None,
+    };
+    let missing = match last {
+        Some(last) if from >= 0 => missing_sns(
+            events.iter().map(|event| event.event_content.sn),
+            from as u64,
+            last,
+        ),
+        _ => Vec::new(),
+    };
+    if descending {
+        events.reverse();
+    }
+    let states = if parameters.with_state.unwrap_or(false) {
+        Some(resulting_states(&node, &id, &events).await?)
+    } else {
+        None
+    };
+    let reply = match (parse_fields(&parameters.fields), states) {
+        (None, None) => handle_data::<Vec<Event>>(Ok(events))?,
+        (fields, states) => {
+            let shaped: Vec<serde_json::Value> = events
+                .iter()
+                .enumerate()
+                .map(|(index, event)| {
+                    let mut value = serde_json::to_value(event).unwrap();
+                    if let Some(fields) = &fields {
+                        value = project_event(&value, fields);
+                    }
+                    if let (Some(states), serde_json::Value::Object(map)) = (&states, &mut value) {
+                        map.insert("properties".to_owned(), states[index].clone());
+                    }
+                    value
+                })
+                .collect();
+            handle_data(Ok(shaped))?
+        }
+    };
+    if missing.is_empty() {
+        return Ok(reply);
+    }
+    let missing: Vec<String> = missing.iter().map(u64::to_string).collect();
+    Ok(Box::new(warp::reply::with_header(
+        reply,
+        MISSING_SNS_HEADER,
+        missing.join(","),
+    )))
+}
+
+// Properties of a subject after each of the given events. The subject is replayed from its
+// genesis up to the last of them
+async fn resulting_states(
+    node: &NodeAPI,
+    id: &str,
+    events: &[Event],
+) -> Result<Vec<serde_json::Value>, Rejection> {
+    let Some(last) = events.iter().map(|event| event.event_content.sn).max() else {
+        return Ok(Vec::new());
+    };
+    let history = node
+        .get_event_of_subject(id.to_owned(), Some(0), Some(last as i64 + 1))
+        .await
+        .map_err(|_| warp::reject::custom(Error::ExecutionError))?;
+    let states = replay_states(history.iter().map(event_payload))
+        .map_err(|_| warp::reject::custom(Error::ExecutionError))?;
+    events
+        .iter()
+        .map(|event| {
+            states
+                .get(event.event_content.sn as usize)
+                .cloned()
+                .ok_or_else(|| warp::reject::custom(Error::ExecutionError))
+        })
+        .collect()
+}
+
+#[utoipa::path(
+    get,
+    path = "/subjects/{id}/events/stream",
+    operation_id = "Stream the Events of a Subject",
+    context_path = "/api",
+    tag = "Events",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Subject's unique id"),
+        ("from" = Option<u64>, Query, description = "SN of the first event to replay. Defaults to 0"),
+        ("format" = Option<EventsFormat>, Query, description = "full (default) sends whole Events. compact sends the request type, signer, timestamp and the base64 CBOR encoded JSON Patch from the previous state, so clients must apply the diffs in order"),
+    ),
+    responses(
+        (status = 200, description = "Server-Sent Events stream. Each message contains an Event (or CompactEvent) and uses its sn as id. Historical events are sent first, followed by new events as they are produced. A keep-alive comment is sent every 15 seconds without events", content_type = "text/event-stream", body = Event),
         (status = 400, description = "Bad Request"),
         (status = 401, description = "Unauthorized"),
         (status = 404, description = "Not Found"),
         (status = 500, description = "Internal Server Error"),
     )
 )]
-pub async fn get_events_of_subject_handler(
+pub async fn get_events_stream_handler(
     id: String,
     node: NodeAPI,
     _header: String,
-    parameters: GetEventsQuery,
+    parameters: GetEventsStreamQuery,
 ) -> Result<Box<dyn warp::Reply>, Rejection> {
     if id.is_empty() {
         return Err(warp::reject::custom(Error::RequestError(
             "Error in query parameter".to_owned(),
         )));
     }
-    let data = node
-        .get_event_of_subject(id, parameters.from, parameters.quantity)
-        .await;
-    handle_data::<Vec<Event>>(data)
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    if let Err(error) = node.get_subject(id.clone()).await {
+        return handle_data::<()>(Err(error));
+    }
+    let from = parameters.from.unwrap_or(0);
+    if parameters.format == Some(EventsFormat::Compact) {
+        // Diffs need the replayed state, so the subject is always read from its genesis
+        let mut encoder = CompactEncoder::new(from);
+        let events = subject_events(node, id, 0)
+            .map(move |event| encoder.encode(&event))
+            .take_while(|compact| futures::future::ready(compact.is_ok()))
+            .filter_map(|compact| futures::future::ready(compact.unwrap()))
+            .map(|compact| {
+                warp::sse::Event::default()
+                    .id(compact.sn.to_string())
+                    .json_data(&compact)
+            });
+        return Ok(Box::new(sse_reply(events)));
+    }
+    let events = subject_events(node, id, from).map(|event| {
+        warp::sse::Event::default()
+            .id(event.event_content.sn.to_string())
+            .json_data(&event)
+    });
+    Ok(Box::new(sse_reply(events)))
+}
+
+// Idle streams send a comment periodically so proxies do not close them. Once the client
+// disconnects the stream is dropped, and with it the polling of the node
+fn sse_reply<S>(events: S) -> impl warp::Reply
+where
+    S: futures::TryStream<Ok = warp::sse::Event> + Send + 'static,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    warp::sse::reply(
+        warp::sse::keep_alive()
+            .interval(SSE_KEEP_ALIVE_INTERVAL)
+            .stream(events),
+    )
 }
 
 // #[utoipa::path(
@@ -823,11 +3369,116 @@ pub async fn post_event_simulated_handler(
             "Error in query parameter".to_owned(),
         )));
     }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
     let payload = body.payload.into();
     let data = node.simulate_event(id, payload).await;
     handle_data(data)
 }
 
+#[utoipa::path(
+    post,
+    path = "/subjects/{id}/events/simulated/batch",
+    operation_id = "Simulate a sequence of Events and get the resulting Subject data",
+    tag = "Events",
+    security(("api_key" = [])),
+    context_path = "/api",
+    params(
+        ("id" = String, Path, description = "Subject's unique id"),
+        ("include_states" = Option<bool>, Query, description = "Also return the properties left by each payload"),
+    ),
+    request_body(content = PostSimulatedBatchBody, content_type = "application/json", description = "Payloads applied in order, each one to the state left by the previous one. Nothing is stored"),
+    responses(
+        (status = 200, description = "Subject as it would be after every payload", body = SimulatedBatch,
+        example = json!(
+            {
+                "subject": {
+                    "subject_id": "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
+                    "governance_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+                    "sn": 3,
+                    "public_key": "ELZ_b-kZzdPykcYuRNC2ZZe_2lCTCUoo60GXfR4cuXMw",
+                    "namespace": "namespace1",
+                    "schema_id": "Prueba",
+                    "owner": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                    "properties": "{\"localizacion\":\"Argentina\",\"temperatura\":-3}"
+                }
+            }
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 422, description = "A payload can not be applied or leaves an invalid state. The field names its position as payloads[i]"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn post_simulated_batch_handler(
+    id: String,
+    node: NodeAPI,
+    _header: String,
+    parameters: SimulatedBatchQuery,
+    body: PostSimulatedBatchBody,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    if body.payloads.is_empty() {
+        return Err(validation_rejection(vec![FieldError::new(
+            "payloads",
+            "must not be empty",
+        )]));
+    }
+    let mut subject = match node.get_subject(id).await {
+        Ok(subject) => subject,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let state: serde_json::Value = serde_json::from_str(&subject.properties)
+        .map_err(|_| warp::reject::custom(Error::ExecutionError))?;
+    let is_governance = subject.governance_id.digest.is_empty();
+    // Subjects whose schema can not be found are only checked to accept the payloads
+    let schema = if is_governance {
+        None
+    } else {
+        match node.get_subject(subject.governance_id.to_str()).await {
+            Ok(governance) => serde_json::from_str::<serde_json::Value>(&governance.properties)
+                .ok()
+                .and_then(|governance| find_schema(&governance, &subject.schema_id).cloned()),
+            Err(_) => None,
+        }
+    };
+    let validate = |state: &serde_json::Value| -> Result<(), String> {
+        if is_governance {
+            return validate_governance(state).map_err(|errors| {
+                errors
+                    .iter()
+                    .map(|error| format!("{} {}", error.field, error.message))
+                    .collect::<Vec<String>>()
+                    .join("; ")
+            });
+        }
+        match &schema {
+            Some(schema) => validate_properties(schema, &state.to_string()),
+            None => Ok(()),
+        }
+    };
+    let payloads: Vec<RequestPayload> = body.payloads.into_iter().map(Into::into).collect();
+    let states = simulate_payloads(state, &payloads, validate).map_err(|(index, reason)| {
+        warp::reject::custom(Error::Unprocessable(vec![FieldError::new(
+            &format!("payloads[{}]", index),
+            &reason,
+        )]))
+    })?;
+    subject.sn += states.len() as u64;
+    if let Some(last) = states.last() {
+        subject.properties = last.to_string();
+    }
+    handle_data(Ok(SimulatedBatch {
+        subject,
+        states: parameters.include_states.unwrap_or(false).then_some(states),
+    }))
+}
+
 #[utoipa::path(
     get,
     path = "/subjects/{id}/events/{sn}",
@@ -838,6 +3489,7 @@ pub async fn post_event_simulated_handler(
     params(
         ("id" = String, Path, description = "Subject's unique id"),
         ("sn" = u64, Path, description = "Event sn"),
+        ("fields" = Option<String>, Query, description = "Comma separated event fields to return. Unknown fields are ignored. JSON Pointers (starting with /) are applied afterwards to the projected event, as in every other route"),
     ),
     responses(
         (status = 200, description = "Subjects Data successfully retrieved", body = Event,
@@ -898,6 +3550,7 @@ pub async fn get_event_handler(
     sn: u64,
     node: NodeAPI,
     _header: String,
+    parameters: GetEventQuery,
 ) -> Result<Box<dyn warp::Reply>, Rejection> {
     // TODO: Analyze if an alternative method is necessary
     if id.is_empty() {
@@ -905,6 +3558,7 @@ pub async fn get_event_handler(
             "Error in query parameter".to_owned(),
         )));
     }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
     let response = node
         .get_event_of_subject(id, Some(sn as i64), Some(1))
         .await;
@@ -912,12 +3566,186 @@ pub async fn get_event_handler(
         let Some(event) = response.unwrap().pop() else {
             return Err(warp::reject::custom(Error::NotFound));
         };
+        if let Some(fields) = parse_fields(&parameters.fields) {
+            let event = serde_json::to_value(&event).unwrap();
+            return handle_data(Ok(project_event(&event, &fields)));
+        }
         handle_data::<Event>(Ok(event))
     } else {
         handle_data::<Vec<Event>>(response)
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/subjects/{id}/events/{sn}/approval-progress",
+    operation_id = "Get the approval progress of an Event",
+    tag = "Events",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Subject's unique id"),
+        ("sn" = u64, Path, description = "Event sn"),
+    ),
+    responses(
+        (status = 200, description = "Acceptances required and received by the event. Every member of the governance is an approver, so the missing approvers are the members that have not voted yet. Approved events are reported with approved set and no missing approvers", body = ApprovalProgress,
+        example = json!(
+            {
+                "approved": false,
+                "required": 2,
+                "received": 1,
+                "missing_approvers": ["ECQnl-h1vEWmu-ZlPuweR3N1x6SUImyVdPrCLmnJJMyU"]
+            }
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_approval_progress_handler(
+    id: String,
+    sn: u64,
+    _header: String,
+    node: NodeAPI,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let subject = match node.get_subject(id.clone()).await {
+        Ok(subject) => subject,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let event = match node
+        .get_event_of_subject(id.clone(), Some(sn as i64), Some(1))
+        .await
+    {
+        Ok(mut events) => match events.pop() {
+            Some(event) if event.event_content.sn == sn => event,
+            _ => return Err(warp::reject::custom(Error::NotFound)),
+        },
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    // Requests of a governance are resolved by its own members
+    let governance_id = if subject.governance_id.digest.is_empty() {
+        id
+    } else {
+        subject.governance_id.to_str()
+    };
+    let governance = match node.get_subject(governance_id).await {
+        Ok(governance) => governance,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let properties: serde_json::Value = serde_json::from_str(&governance.properties)
+        .map_err(|_| warp::reject::custom(Error::InternalServerError))?;
+    let approved = event.event_content.approved;
+    // While the request is pending its votes keep arriving, so they are taken from it
+    let approvals = match node.get_single_request(request_id_of(&event)).await {
+        Ok(request) if !approved => request.approvals,
+        _ => event.event_content.event_request.approvals,
+    };
+    let votes = approvals
+        .into_iter()
+        .map(|approval| {
+            (
+                approval.signature.content.signer.to_str(),
+                approval.content.approval_type,
+            )
+        })
+        .collect();
+    handle_data(Ok(approval_progress(&properties, approved, votes)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/subjects/{id}/events/{sn}/verify-quorum",
+    operation_id = "Verify the quorum of an Event",
+    tag = "Events",
+    context_path = "/api",
+    security(("api_key" = [])),
+    params(
+        ("id" = String, Path, description = "Subject's unique id"),
+        ("sn" = u64, Path, description = "Event sn"),
+    ),
+    responses(
+        (status = 200, description = "Whether the approvals recorded in the event satisfy the quorum of the governance at the governance_version of the event. Every member at that version must accept, and only the acceptances of those members are valid", body = QuorumCheck,
+        example = json!(
+            {
+                "satisfied": true,
+                "required": 2,
+                "valid_approvals": 2
+            }
+        )),
+        (status = 400, description = "Bad Request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Not Found"),
+        (status = 500, description = "Internal Server Error"),
+    )
+)]
+pub async fn get_verify_quorum_handler(
+    id: String,
+    sn: u64,
+    _header: String,
+    node: NodeAPI,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if id.is_empty() {
+        return Err(warp::reject::custom(Error::RequestError(
+            "Error in query parameter".to_owned(),
+        )));
+    }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
+    let subject = match node.get_subject(id.clone()).await {
+        Ok(subject) => subject,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let event = match node
+        .get_event_of_subject(id.clone(), Some(sn as i64), Some(1))
+        .await
+    {
+        Ok(mut events) => match events.pop() {
+            Some(event) if event.event_content.sn == sn => event,
+            _ => return Err(warp::reject::custom(Error::NotFound)),
+        },
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    // Requests of a governance are resolved by its own members
+    let governance_id = if subject.governance_id.digest.is_empty() {
+        id
+    } else {
+        subject.governance_id.to_str()
+    };
+    // The governance version is the sn of the governance, so it is replayed up to it
+    let version = event.event_content.metadata.governance_version;
+    let history = match node
+        .get_event_of_subject(governance_id, Some(0), Some(version as i64 + 1))
+        .await
+    {
+        Ok(history) => history,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let states = replay_states(history.iter().map(event_payload))
+        .map_err(|_| warp::reject::custom(Error::ExecutionError))?;
+    let Some(governance) = states.get(version as usize) else {
+        return Err(warp::reject::custom(Error::ExecutionError));
+    };
+    let votes = event
+        .event_content
+        .event_request
+        .approvals
+        .into_iter()
+        .map(|approval| {
+            (
+                approval.signature.content.signer.to_str(),
+                approval.content.approval_type,
+            )
+        })
+        .collect();
+    handle_data(Ok(quorum_check(governance, votes)))
+}
+
 #[utoipa::path(
     get,
     path = "/subjects/{id}/events/{sn}/signatures",
@@ -963,6 +3791,7 @@ pub async fn get_signatures_handler(
             "Error in query parameter".to_owned(),
         )));
     }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
     let data = node
         .get_signatures(id, sn, parameters.from, parameters.quantity)
         .await;
@@ -1009,6 +3838,7 @@ pub async fn get_event_properties_handler(
             "Error in query parameter".to_owned(),
         )));
     }
+    validate_identifier("id", &id).map_err(validation_rejection)?;
     let data = node
         .get_event_of_subject(id, Some(sn as i64), Some(1))
         .await;
@@ -1022,15 +3852,394 @@ pub async fn get_event_properties_handler(
     }
 }
 
+fn request_id_of(event: &Event) -> String {
+    event
+        .event_content
+        .event_request
+        .signature
+        .content
+        .event_content_hash
+        .to_str()
+}
+
+// Reads the event produced by a resolved request from the subject and sn given by the caller.
+// The node does not index events by request, so without them the request is not found.
+async fn find_request_event(
+    node: &NodeAPI,
+    request_id: &str,
+    parameters: ResolvedRequestQuery,
+) -> Result<Option<Event>, Rejection> {
+    let (Some(subject_id), Some(sn)) = (parameters.subject_id, parameters.sn) else {
+        return Ok(None);
+    };
+    validate_identifier("subject_id", &subject_id).map_err(validation_rejection)?;
+    let events = match node
+        .get_event_of_subject(subject_id, Some(sn as i64), Some(1))
+        .await
+    {
+        Ok(events) => events,
+        Err(ApiError::NotFound(_)) => return Ok(None),
+        Err(_) => return Err(warp::reject::custom(Error::ExecutionError)),
+    };
+    Ok(events
+        .into_iter()
+        .find(|event| event.event_content.sn == sn && request_id_of(event) == request_id))
+}
+
+// A governance payload whose ids repeat is well formed, but can not be used
+fn check_unique_governance_ids(payload: &Payload) -> Result<(), Rejection> {
+    let Payload::Json(governance) = payload else {
+        return Ok(());
+    };
+    check_governance_duplicates(governance)
+        .map_err(|errors| warp::reject::custom(Error::Unprocessable(errors)))
+}
+
+// Validates a full state payload against the schema registered in its governance.
+// Payloads that can not be checked here (JSON Patch, unknown governance) are left to the node.
+async fn check_payload_schema(
+    node: &NodeAPI,
+    governance_id: &str,
+    schema_id: &str,
+    payload: &Payload,
+) -> Result<(), Rejection> {
+    if governance_id.is_empty() || schema_id == GOVERNANCE_SCHEMA_ID {
+        return Ok(());
+    }
+    let Payload::Json(state) = payload else {
+        return Ok(());
+    };
+    let Ok(governance) = node.get_subject(governance_id.to_owned()).await else {
+        return Ok(());
+    };
+    let Ok(governance) = serde_json::from_str::<serde_json::Value>(&governance.properties) else {
+        return Ok(());
+    };
+    let Some(schema) = find_schema(&governance, schema_id) else {
+        return Err(warp::reject::custom(Error::Unprocessable(vec![
+            FieldError::new("schema_id", "is not defined in the governance"),
+        ])));
+    };
+    validate_properties(schema, &state.to_string()).map_err(|reason| {
+        warp::reject::custom(Error::Unprocessable(vec![FieldError::new(
+            "payload", &reason,
+        )]))
+    })
+}
+
+// Checks the state a request would produce against the schema of its subject
+async fn state_schema_check(
+    node: &NodeAPI,
+    subject_id: &str,
+    payload: &Payload,
+) -> Result<(), String> {
+    let subject = node
+        .get_subject(subject_id.to_owned())
+        .await
+        .map_err(|_| format!("subject {} not found", subject_id))?;
+    let mut state: serde_json::Value =
+        serde_json::from_str(&subject.properties).map_err(|e| e.to_string())?;
+    apply_payload(&mut state, &payload.clone().into())?;
+    if subject.governance_id.digest.is_empty() {
+        return validate_governance(&state).map_err(|errors| {
+            errors
+                .iter()
+                .map(|error| format!("{} {}", error.field, error.message))
+                .collect::<Vec<String>>()
+                .join("; ")
+        });
+    }
+    let governance = node
+        .get_subject(subject.governance_id.to_str())
+        .await
+        .map_err(|_| "governance of the subject not found".to_owned())?;
+    let governance: serde_json::Value =
+        serde_json::from_str(&governance.properties).map_err(|e| e.to_string())?;
+    let schema = find_schema(&governance, &subject.schema_id).ok_or_else(|| {
+        format!(
+            "schema {} is not defined in the governance",
+            subject.schema_id
+        )
+    })?;
+    validate_properties(schema, &state.to_string())
+}
+
+fn check_schema_allowed(settings: &RestSettings, schema_id: &str) -> Result<(), Rejection> {
+    if settings.is_schema_allowed(schema_id) {
+        Ok(())
+    } else {
+        Err(warp::reject::custom(Error::Forbidden(format!(
+            "Schema {} is not allowed",
+            schema_id
+        ))))
+    }
+}
+
+fn check_governance_allowed(
+    settings: &RestSettings,
+    api_key: &str,
+    governance_id: &str,
+) -> Result<(), Rejection> {
+    if settings.is_governance_allowed(api_key, governance_id) {
+        Ok(())
+    } else {
+        Err(warp::reject::custom(Error::Forbidden(format!(
+            "Governance {} is not allowed",
+            governance_id
+        ))))
+    }
+}
+
+fn validation_rejection(errors: Vec<FieldError>) -> Rejection {
+    warp::reject::custom(Error::ValidationErrors(errors))
+}
+
+// Answers a write honoring the return preference of the client. Without one the full
+// object is returned, as handle_data does. Writes that created a subject without waiting for
+// approval are answered with 201 and its Location, the rest with 202
+fn handle_created<T: Serialize>(
+    data: Result<T, ApiError>,
+    preference: Option<ReturnPreference>,
+) -> Result<Box<dyn warp::Reply>, Rejection> {
+    let data = match data {
+        Ok(data) => serde_json::to_value(data).map_err(serialization_rejection)?,
+        Err(error) => return handle_data::<()>(Err(error)),
+    };
+    let mut response = match preference {
+        Some(preference) => preference.reply(&data).map_err(serialization_rejection)?,
+        None => warp::Reply::into_response(warp::reply::json(&data)),
+    };
+    let location = created_subject(&data)
+        .and_then(|id| warp::http::HeaderValue::from_str(&format!("/api/subjects/{}", id)).ok());
+    match location {
+        Some(location) => {
+            *response.status_mut() = warp::http::StatusCode::CREATED;
+            response
+                .headers_mut()
+                .insert(warp::http::header::LOCATION, location);
+        }
+        None => *response.status_mut() = warp::http::StatusCode::ACCEPTED,
+    }
+    Ok(Box::new(response))
+}
+
+// Subject created by a write that needed no approval. Creation requests are never voted, while
+// events tell whether they were approved. Governances are answered with just their id
+fn created_subject(data: &serde_json::Value) -> Option<&str> {
+    if let Some(governance_id) = data.as_str() {
+        return Some(governance_id);
+    }
+    match data.get("event_content") {
+        Some(content) if content.get("approved")?.as_bool()? => content.get("subject_id")?.as_str(),
+        Some(_) => None,
+        None => {
+            data.get("request")?.get("Create")?;
+            data.get("subject_id")?.as_str()
+        }
+    }
+}
+
+// Marks the empty page of a listing requested past its end
+fn out_of_range(reply: Box<dyn warp::Reply>) -> Box<dyn warp::Reply> {
+    Box::new(warp::reply::with_header(reply, OUT_OF_RANGE_HEADER, "true"))
+}
+
+fn serialization_rejection(error: serde_json::Error) -> Rejection {
+    log::error!("Node response could not be serialized: {}", error);
+    warp::reject::custom(Error::SerializationError(error.to_string()))
+}
+
 fn handle_data<T: Serialize>(data: Result<T, ApiError>) -> Result<Box<dyn warp::Reply>, Rejection> {
+    if let Err(error) = &data {
+        record_api_error(error);
+    }
     match data {
-        Ok(data) => return Ok(Box::new(warp::reply::json(&data))),
+        // Serialized here, as warp::reply::json would hide the failure behind an empty 500
+        Ok(data) => match serde_json::to_vec(&data) {
+            Ok(body) => {
+                let mut response = warp::reply::Response::new(body.into());
+                response.headers_mut().insert(
+                    warp::http::header::CONTENT_TYPE,
+                    warp::http::HeaderValue::from_static("application/json"),
+                );
+                Ok(Box::new(response))
+            }
+            Err(error) => Err(serialization_rejection(error)),
+        },
         Err(ApiError::InvalidParameters) => Err(warp::reject::custom(Error::InvalidParameters)),
         Err(ApiError::NotFound(_data)) => Err(warp::reject::custom(Error::NotFound)),
-        Err(ApiError::EventCreationError { source }) => match source {
-            _ => Err(warp::reject::custom(Error::NotEnoughPermissions)), // TODO: Add the rest of the cases
-        },
+        Err(ApiError::EventCreationError { source }) => {
+            let detail = format!("{:?}", source);
+            let variant = variant_name(&detail);
+            Err(warp::reject::custom(event_creation_error(&variant, detail)))
+        }
         Err(ApiError::VoteNotNeeded(msg)) => Err(warp::reject::custom(Error::RequestError(msg))),
         _ => Err(warp::reject::custom(Error::ExecutionError)),
     }
 }
+
+// The source of EventCreationError is an error of taple-core, whose variants are told apart by
+// their name, as the metrics name the errors of the node. The name is also the code of the
+// problem. Sources of no known kind keep being answered as not enough permissions
+fn event_creation_error(variant: &str, detail: String) -> Error {
+    let error = if variant.ends_with("NotOwned")
+        || variant.contains("Signer")
+        || variant.contains("Unauthorized")
+    {
+        Error::Forbidden(detail)
+    } else if variant.ends_with("NotFound") {
+        Error::NotFound
+    } else if variant.starts_with("Invalid") || variant.contains("Validation") {
+        Error::RequestError(detail)
+    } else {
+        Error::NotEnoughPermissions
+    };
+    Error::Coded {
+        code: kebab_case(variant),
+        error: Box::new(error),
+    }
+}
+
+// "SchemaValidationError" as "schema-validation-error". Acronyms are kept as one word, so
+// "InvalidJSONPatch" is "invalid-json-patch"
+fn kebab_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut kebab = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1);
+        let starts_word = c.is_uppercase()
+            && match previous {
+                None => false,
+                Some(previous) if previous.is_uppercase() => {
+                    next.map_or(false, |next| next.is_lowercase())
+                }
+                Some(_) => true,
+            };
+        if starts_word {
+            kebab.push('-');
+        }
+        kebab.extend(c.to_lowercase());
+    }
+    kebab
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+    use warp::{
+        http::{header::LOCATION, StatusCode},
+        Reply,
+    };
+
+    #[test]
+    fn test_event_creation_sources_keep_their_status() {
+        let sources = [
+            (
+                "SchemaValidationError(\"temperatura\")",
+                400,
+                "schema-validation-error",
+            ),
+            ("InvalidJSONPatch", 400, "invalid-json-patch"),
+            ("GovernanceNotFound(\"J7Bg\")", 404, "governance-not-found"),
+            ("SubjectNotFound", 404, "subject-not-found"),
+            ("SubjectNotOwned(\"J1\")", 403, "subject-not-owned"),
+            ("InvalidSigner { signer: \"E1\" }", 403, "invalid-signer"),
+            ("ChannelClosed", 401, "channel-closed"),
+        ];
+        for (source, status, code) in sources {
+            let error = event_creation_error(&variant_name(source), source.to_owned());
+            assert_eq!(error.status().as_u16(), status, "{}", source);
+            assert_eq!(error.problem().code.as_deref(), Some(code));
+        }
+    }
+
+    #[test]
+    fn test_unserializable_node_response() {
+        // JSON objects can not have non string keys
+        let stub: Result<HashMap<(u64, u64), u64>, ApiError> = Ok(HashMap::from([((0, 1), 2)]));
+        let Err(rejection) = handle_data(stub) else {
+            panic!("unserializable data was answered");
+        };
+        assert!(matches!(
+            rejection.find::<Error>(),
+            Some(Error::SerializationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_only_writes_completed_without_approval_are_created() {
+        let answer = |data: serde_json::Value| {
+            let Ok(reply) = handle_created(Ok(data), None) else {
+                panic!("write was rejected");
+            };
+            reply.into_response()
+        };
+        let subject_id = "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0";
+        let request = |request: serde_json::Value| {
+            serde_json::json!({
+                "request": request,
+                "request_id": "JpxalqMTQcDcLG3dwb8uvcrstJo6pmFEzUwhzi0nGPOA",
+                "timestamp": 1671705355,
+                "subject_id": subject_id,
+                "sn": 0
+            })
+        };
+        let location = format!("/api/subjects/{}", subject_id);
+
+        let response = answer(request(
+            serde_json::json!({ "Create": { "schema_id": "" } }),
+        ));
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[LOCATION], location);
+        let event = |approved: bool| {
+            serde_json::json!({
+                "event_content": { "subject_id": subject_id, "sn": 0, "approved": approved }
+            })
+        };
+        let response = answer(event(true));
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[LOCATION], location);
+        let response = answer(serde_json::json!(subject_id));
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        // Pending approval
+        let response = answer(request(
+            serde_json::json!({ "State": { "subject_id": subject_id } }),
+        ));
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert!(response.headers().get(LOCATION).is_none());
+        let response = answer(event(false));
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert!(response.headers().get(LOCATION).is_none());
+    }
+
+    #[test]
+    fn test_failed_votes_keep_their_status() {
+        let request_id = "JhEnzFVF1a-u-rH34cix2A_OXgcfesM6HGOyk7wdrGHk";
+        let voted = vote_result(request_id.into(), Ok(Box::new(warp::reply())));
+        assert_eq!(
+            voted,
+            BatchVoteResult {
+                request_id: request_id.into(),
+                status: 200,
+                error: None,
+            }
+        );
+        let conflict = Error::Conflict(String::from("The vote of a request can not be changed"));
+        let changed = vote_result(request_id.into(), Err(warp::reject::custom(conflict)));
+        assert_eq!(changed.status, 409);
+        assert_eq!(
+            changed.error.as_deref(),
+            Some("The vote of a request can not be changed")
+        );
+        let errors = vec![FieldError::new("id", "is not a valid identifier")];
+        let invalid = vote_result(String::from("J1"), Err(validation_rejection(errors)));
+        assert_eq!(invalid.status, 400);
+        assert_eq!(
+            invalid.error.as_deref(),
+            Some("id is not a valid identifier")
+        );
+    }
+}
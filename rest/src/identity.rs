@@ -0,0 +1,97 @@
+use std::sync::{Arc, RwLock};
+
+// Identities controlled by this node. Subjects owned by any of them are managed by the node,
+// the rest are only tracked. They are set once at startup and read by the requests, never
+// while holding the lock across an await.
+#[derive(Debug, Clone, Default)]
+pub struct NodeIdentity {
+    controllers: Arc<RwLock<Vec<String>>>,
+}
+
+impl NodeIdentity {
+    pub fn new(controllers: Vec<String>) -> Self {
+        Self {
+            controllers: Arc::new(RwLock::new(controllers)),
+        }
+    }
+
+    // The controller is only known once the node has started, after the routes are built
+    pub fn set_controllers(&self, controllers: Vec<String>) {
+        *self.controllers.write().unwrap() = controllers;
+    }
+
+    pub fn owns(&self, owner: &str) -> bool {
+        self.controllers
+            .read()
+            .unwrap()
+            .iter()
+            .any(|controller| controller == owner)
+    }
+
+    // Page of the items owned by the node, keeping the order in which they are given
+    pub fn owned<T>(
+        &self,
+        items: Vec<T>,
+        owner_of: fn(&T) -> String,
+        from: usize,
+        quantity: Option<usize>,
+    ) -> Vec<T> {
+        items
+            .into_iter()
+            .filter(|item| self.owns(&owner_of(item)))
+            .skip(from)
+            .take(quantity.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_only_owned_subjects_are_listed() {
+        let identity = NodeIdentity::new(vec!["node_key".into()]);
+        // (subject_id, owner)
+        let subjects = vec![
+            ("subject1", "node_key"),
+            ("subject2", "other_key"),
+            ("subject3", "node_key"),
+            ("subject4", "other_key"),
+            ("subject5", "node_key"),
+        ];
+        let owner_of = |subject: &(&str, &str)| subject.1.to_owned();
+        let owned = identity.owned(subjects.clone(), owner_of, 0, None);
+        assert_eq!(
+            owned.iter().map(|s| s.0).collect::<Vec<_>>(),
+            vec!["subject1", "subject3", "subject5"]
+        );
+        let page = identity.owned(subjects, owner_of, 1, Some(1));
+        assert_eq!(page, vec![("subject3", "node_key")]);
+    }
+
+    #[test]
+    fn test_controllers_set_while_requests_read_them() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let identity = NodeIdentity::default();
+            let tasks: Vec<_> = (0..200)
+                .map(|i| {
+                    let identity = identity.clone();
+                    tokio::spawn(async move {
+                        if i == 100 {
+                            identity.set_controllers(vec!["node_key".into()]);
+                        }
+                        identity.owns("node_key")
+                    })
+                })
+                .collect();
+            // Readers neither block the controller from being set nor poison the lock
+            for task in futures::future::join_all(tasks).await {
+                task.unwrap();
+            }
+            assert!(identity.owns("node_key"));
+            assert!(!identity.owns("other_key"));
+        });
+    }
+}
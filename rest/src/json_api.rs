@@ -0,0 +1,161 @@
+use hyper::body::HttpBody;
+use serde_json::{json, Value};
+use warp::{
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderValue, StatusCode,
+    },
+    reply::Response,
+};
+
+use super::settings::{JSON_API_CONTENT_TYPE, JSON_CONTENT_TYPE};
+
+enum ResourceKind {
+    Subject,
+    Event,
+}
+
+// Routes whose responses have a JSON:API form, by the kind of resource they return
+fn resource_kind(path: &str) -> Option<ResourceKind> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["api", "subjects" | "governances"] | ["api", "subjects" | "governances", _] => {
+            Some(ResourceKind::Subject)
+        }
+        ["api", "subjects", _, "events"] => Some(ResourceKind::Event),
+        ["api", "subjects", _, "events", sn] if sn.parse::<u64>().is_ok() => {
+            Some(ResourceKind::Event)
+        }
+        _ => None,
+    }
+}
+
+// Governances are the subjects without governance, so only the rest are related to one
+fn subject_resource(subject: &Value) -> Option<Value> {
+    let mut attributes = subject.as_object()?.clone();
+    let id = attributes.remove("subject_id")?;
+    let governance_id = attributes
+        .get("governance_id")
+        .and_then(Value::as_str)
+        .unwrap_or("")
+        .to_owned();
+    if governance_id.is_empty() {
+        return Some(json!({ "type": "governances", "id": id, "attributes": attributes }));
+    }
+    Some(json!({
+        "type": "subjects",
+        "id": id,
+        "attributes": attributes,
+        "relationships": {
+            "governance": { "data": { "type": "governances", "id": governance_id } }
+        }
+    }))
+}
+
+// Events are identified by their subject and sn, as in their route
+fn event_resource(event: &Value) -> Option<Value> {
+    let content = event.get("event_content")?;
+    let subject_id = content.get("subject_id")?.as_str()?;
+    let sn = content.get("sn")?.as_u64()?;
+    Some(json!({
+        "type": "events",
+        "id": format!("{}/{}", subject_id, sn),
+        "attributes": event,
+        "relationships": {
+            "subject": { "data": { "type": "subjects", "id": subject_id } }
+        }
+    }))
+}
+
+// JSON:API document with the data of a response. None if the route has no JSON:API form or the
+// data is not a whole resource, as when some of its fields were left out
+pub fn json_api_document(path: &str, data: &Value) -> Option<Value> {
+    let resource = match resource_kind(path)? {
+        ResourceKind::Subject => subject_resource,
+        ResourceKind::Event => event_resource,
+    };
+    let data = match data {
+        Value::Array(items) => Value::Array(items.iter().map(resource).collect::<Option<_>>()?),
+        item => resource(item)?,
+    };
+    Some(json!({ "data": data }))
+}
+
+// Converts a successful JSON response to a JSON:API document. Responses without a JSON:API form
+// are sent as plain JSON.
+pub async fn encode_json_api(response: Response, path: &str) -> Response {
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .map(|value| value.as_bytes().starts_with(JSON_CONTENT_TYPE.as_bytes()))
+        .unwrap_or(false);
+    if !response.status().is_success() || !is_json || response.body().size_hint().exact().is_none()
+    {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(error) => {
+            log::error!("Response body could not be read: {}", error);
+            let mut response = Response::new(String::from("Internal Server Error").into());
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            return response;
+        }
+    };
+    let document = serde_json::from_slice::<Value>(&body)
+        .ok()
+        .and_then(|data| json_api_document(path, &data));
+    let Some(document) = document else {
+        return Response::from_parts(parts, body.into());
+    };
+    let body = serde_json::to_vec(&document).expect("JSON values are always serializable");
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(JSON_API_CONTENT_TYPE),
+    );
+    Response::from_parts(parts, body.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_subject_is_related_to_its_governance() {
+        let subject = json!({
+            "subject_id": "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
+            "governance_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+            "sn": 1,
+            "schema_id": "prueba",
+            "properties": "{\"a\":\"70\"}"
+        });
+        let document = json_api_document(
+            "/api/subjects/JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
+            &subject,
+        )
+        .unwrap();
+        assert_eq!(document["data"]["type"], "subjects");
+        assert_eq!(
+            document["data"]["id"],
+            "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc"
+        );
+        assert_eq!(document["data"]["attributes"]["sn"], 1);
+        assert!(document["data"]["attributes"].get("subject_id").is_none());
+        assert_eq!(
+            document["data"]["relationships"]["governance"]["data"],
+            json!({ "type": "governances", "id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0" })
+        );
+        // Governances have no governance
+        let governance = json!([{
+            "subject_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+            "governance_id": ""
+        }]);
+        let document = json_api_document("/api/governances", &governance).unwrap();
+        assert_eq!(document["data"][0]["type"], "governances");
+        assert!(document["data"][0].get("relationships").is_none());
+        // Routes that do not return resources keep their plain JSON
+        assert!(json_api_document("/api/namespaces", &json!([])).is_none());
+    }
+}
@@ -1,5 +1,34 @@
+pub mod alias;
+pub mod api_keys;
+pub mod audit;
+pub mod body_log;
 pub mod bodys;
+pub mod catalog;
+pub mod concurrency;
+pub mod deadline;
+pub mod diagnostics;
+pub mod encoding;
 pub mod error;
+pub mod etag;
+pub mod governance;
 pub mod handlers;
+pub mod identity;
+pub mod json_api;
+pub mod maintenance;
+pub mod metrics;
+pub mod negotiation;
+pub mod prefer;
+pub mod projection;
 pub mod querys;
+pub mod read_timeout;
+pub mod replay;
+pub mod responses;
 pub mod routes;
+pub mod search;
+pub mod settings;
+pub mod shaping;
+pub mod snapshot;
+pub mod startup;
+pub mod stream;
+pub mod validation;
+pub mod votes;
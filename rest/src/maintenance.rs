@@ -0,0 +1,93 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+
+use super::{error::Error, responses::MaintenanceStatus};
+
+// Seconds clients are told to wait when no other value is given
+pub const DEFAULT_RETRY_AFTER: u64 = 60;
+
+// Runtime switch that blocks writes while the node is under maintenance. It is read by every
+// write, so it uses atomics instead of a lock.
+#[derive(Debug, Clone)]
+pub struct Maintenance {
+    enabled: Arc<AtomicBool>,
+    retry_after: Arc<AtomicU64>,
+}
+
+impl Default for Maintenance {
+    fn default() -> Self {
+        Self {
+            enabled: Arc::new(AtomicBool::new(false)),
+            retry_after: Arc::new(AtomicU64::new(DEFAULT_RETRY_AFTER)),
+        }
+    }
+}
+
+impl Maintenance {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&self, enabled: bool, retry_after: Option<u64>) -> MaintenanceStatus {
+        self.retry_after
+            .store(retry_after.unwrap_or(DEFAULT_RETRY_AFTER), Ordering::SeqCst);
+        self.enabled.store(enabled, Ordering::SeqCst);
+        self.status()
+    }
+
+    pub fn status(&self) -> MaintenanceStatus {
+        MaintenanceStatus {
+            enabled: self.enabled.load(Ordering::SeqCst),
+            retry_after: self.retry_after.load(Ordering::SeqCst),
+        }
+    }
+
+    pub fn check_writable(&self) -> Result<(), Error> {
+        let status = self.status();
+        if status.enabled {
+            Err(Error::ServiceUnavailable(status.retry_after))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_switches_leave_the_last_status() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let maintenance = Maintenance::new();
+            let tasks: Vec<_> = (0..200)
+                .map(|i| {
+                    let maintenance = maintenance.clone();
+                    tokio::spawn(async move {
+                        if i % 20 == 0 {
+                            maintenance.set(i % 40 == 0, Some(30));
+                        }
+                        match maintenance.check_writable() {
+                            Ok(()) => true,
+                            Err(Error::ServiceUnavailable(retry_after)) => {
+                                retry_after == 30 || retry_after == DEFAULT_RETRY_AFTER
+                            }
+                            Err(_) => false,
+                        }
+                    })
+                })
+                .collect();
+            for task in futures::future::join_all(tasks).await {
+                assert!(task.unwrap());
+            }
+            maintenance.set(true, None);
+            assert!(matches!(
+                maintenance.check_writable(),
+                Err(Error::ServiceUnavailable(DEFAULT_RETRY_AFTER))
+            ));
+        });
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Debug, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use warp::http::Method;
+
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+// Upper bounds in seconds of the buckets of the handler durations, as the Prometheus defaults
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+// Label of the requests that do not match any documented route, so unknown paths can not grow
+// the number of series
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+// Errors of the node turned into rejections, by variant. They are counted where the node
+// responses are handled, which has no access to the state of the routes, so they are kept for
+// the whole process
+static API_ERRORS: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+
+pub fn record_api_error(error: &impl Debug) {
+    let name = variant_name(&format!("{:?}", error));
+    *API_ERRORS.lock().unwrap().entry(name).or_default() += 1;
+}
+
+// The Debug output of an enum starts with the name of its variant
+pub fn variant_name(debug: &str) -> String {
+    debug
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .next()
+        .unwrap_or_default()
+        .to_owned()
+}
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    // Not cumulative, they are added up when rendered
+    buckets: [u64; DURATION_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(bucket) = DURATION_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Series {
+    // By route, method and status
+    requests: BTreeMap<(String, String, u16), u64>,
+    // By route and method
+    durations: BTreeMap<(String, String), Histogram>,
+}
+
+// Counters and durations of the requests answered by the API, served in the Prometheus text
+// format by GET /metrics. Routes are labelled by their documented path, not by the requested one.
+// Every request updates the series under a blocking lock, which is never held across an await
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    series: Arc<Mutex<Series>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, method: &Method, route: &str, status: u16, duration: Duration) {
+        let mut series = self.series.lock().unwrap();
+        *series
+            .requests
+            .entry((route.to_owned(), method.to_string(), status))
+            .or_default() += 1;
+        series
+            .durations
+            .entry((route.to_owned(), method.to_string()))
+            .or_default()
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn render(&self) -> String {
+        let series = self.series.lock().unwrap();
+        let mut output = String::new();
+        output.push_str("# HELP taple_requests_total Requests answered by the API\n");
+        output.push_str("# TYPE taple_requests_total counter\n");
+        for ((path, method, status), count) in &series.requests {
+            let _ = writeln!(
+                output,
+                "taple_requests_total{{path=\"{}\",method=\"{}\",status=\"{}\"}} {}",
+                escape(path),
+                method,
+                status,
+                count
+            );
+        }
+        output.push_str(
+            "# HELP taple_request_duration_seconds Time taken to answer the requests to the API\n",
+        );
+        output.push_str("# TYPE taple_request_duration_seconds histogram\n");
+        for ((path, method), histogram) in &series.durations {
+            let labels = format!("path=\"{}\",method=\"{}\"", escape(path), method);
+            let mut cumulative = 0;
+            for (bound, count) in DURATION_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    output,
+                    "taple_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, cumulative
+                );
+            }
+            let _ = writeln!(
+                output,
+                "taple_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, histogram.count
+            );
+            let _ = writeln!(
+                output,
+                "taple_request_duration_seconds_sum{{{}}} {}",
+                labels, histogram.sum
+            );
+            let _ = writeln!(
+                output,
+                "taple_request_duration_seconds_count{{{}}} {}",
+                labels, histogram.count
+            );
+        }
+        output.push_str(
+            "# HELP taple_api_errors_total Errors of the node answered as rejections, by variant\n",
+        );
+        output.push_str("# TYPE taple_api_errors_total counter\n");
+        for (variant, count) in API_ERRORS.lock().unwrap().iter() {
+            let _ = writeln!(
+                output,
+                "taple_api_errors_total{{variant=\"{}\"}} {}",
+                escape(variant),
+                count
+            );
+        }
+        output
+    }
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    enum StubError {
+        NotFound(String),
+        Internal { source: String },
+    }
+
+    #[test]
+    fn test_requests_are_counted_by_route() {
+        let metrics = Metrics::new();
+        let route = "/api/subjects/{id}";
+        metrics.record(&Method::GET, route, 200, Duration::from_millis(3));
+        metrics.record(&Method::GET, route, 200, Duration::from_millis(30));
+        metrics.record(&Method::GET, route, 404, Duration::from_secs(20));
+        let output = metrics.render();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines.contains(
+            &"taple_requests_total{path=\"/api/subjects/{id}\",method=\"GET\",status=\"200\"} 2"
+        ));
+        assert!(lines.contains(
+            &"taple_requests_total{path=\"/api/subjects/{id}\",method=\"GET\",status=\"404\"} 1"
+        ));
+        let bucket = |le: &str, count: u64| {
+            format!(
+                "taple_request_duration_seconds_bucket{{path=\"/api/subjects/{{id}}\",method=\"GET\",le=\"{}\"}} {}",
+                le, count
+            )
+        };
+        // Buckets are cumulative, and only +Inf counts the requests above the last bound
+        assert!(lines.contains(&bucket("0.005", 1).as_str()));
+        assert!(lines.contains(&bucket("0.05", 2).as_str()));
+        assert!(lines.contains(&bucket("10", 2).as_str()));
+        assert!(lines.contains(&bucket("+Inf", 3).as_str()));
+        assert!(lines.contains(
+            &"taple_request_duration_seconds_count{path=\"/api/subjects/{id}\",method=\"GET\"} 3"
+        ));
+    }
+
+    #[test]
+    fn test_concurrent_requests_are_all_counted() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let metrics = Metrics::new();
+            let route = "/api/approvals";
+            let tasks: Vec<_> = (0..400)
+                .map(|i| {
+                    let metrics = metrics.clone();
+                    tokio::spawn(async move {
+                        metrics.record(&Method::GET, route, 200, Duration::from_millis(1));
+                        // Scrapes are served while the requests are recorded
+                        if i % 10 == 0 {
+                            metrics.render();
+                        }
+                    })
+                })
+                .collect();
+            for task in futures::future::join_all(tasks).await {
+                task.unwrap();
+            }
+            let output = metrics.render();
+            let lines: Vec<&str> = output.lines().collect();
+            assert!(lines.contains(
+                &"taple_requests_total{path=\"/api/approvals\",method=\"GET\",status=\"200\"} 400"
+            ));
+            assert!(lines.contains(
+                &"taple_request_duration_seconds_count{path=\"/api/approvals\",method=\"GET\"} 400"
+            ));
+        });
+    }
+
+    #[test]
+    fn test_api_errors_are_counted_by_variant() {
+        assert_eq!(variant_name("NotFound(\"J1\")"), "NotFound");
+        assert_eq!(variant_name("EventCreationError { source: .. }"), "EventCreationError");
+        assert_eq!(variant_name("InvalidParameters"), "InvalidParameters");
+        record_api_error(&StubError::Internal {
+            source: String::from("Database not available"),
+        });
+        record_api_error(&StubError::NotFound(String::from("J1")));
+        let output = Metrics::new().render();
+        // Other tests may record errors of the same process
+        assert!(output.contains("taple_api_errors_total{variant=\"Internal\"} "));
+        assert!(output.contains("taple_api_errors_total{variant=\"NotFound\"} "));
+    }
+}
@@ -0,0 +1,274 @@
+use hyper::body::HttpBody;
+use serde_json::Value;
+use warp::{
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE, VARY},
+        HeaderValue, StatusCode,
+    },
+    reply::Response,
+};
+
+use super::{
+    error::Error,
+    settings::{CBOR_CONTENT_TYPE, JSON_CONTENT_TYPE, MSGPACK_CONTENT_TYPE},
+};
+
+// Picks the content type of the response from the Accept header, following the quality
+// values of the client. Clients that do not send the header get the first offered type
+pub fn negotiate(accept: Option<&str>, offered: &[String]) -> Result<String, Error> {
+    let not_acceptable = || {
+        Error::NotAcceptable(format!(
+            "None of the accepted content types is available. Available: {}",
+            offered.join(", ")
+        ))
+    };
+    let accept = accept.map(str::trim).unwrap_or("");
+    if accept.is_empty() {
+        return offered.first().cloned().ok_or_else(not_acceptable);
+    }
+    let mut ranges: Vec<(String, f32)> = accept
+        .split(',')
+        .map(|range| {
+            let mut params = range.split(';');
+            let media = params.next().unwrap_or("").trim().to_lowercase();
+            let quality = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|quality| quality.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (media, quality)
+        })
+        .collect();
+    // Stable, so ranges with the same quality keep the order of the client
+    ranges.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    let refused: Vec<&str> = ranges
+        .iter()
+        .filter(|(_, quality)| *quality <= 0.0)
+        .map(|(media, _)| media.as_str())
+        .collect();
+    let available = |content_type: &&String| !refused.contains(&content_type.as_str());
+    ranges
+        .iter()
+        .filter(|(_, quality)| *quality > 0.0)
+        .find_map(|(media, _)| match media.as_str() {
+            "*/*" => offered.iter().find(available),
+            "application/*" => offered
+                .iter()
+                .filter(available)
+                .find(|content_type| content_type.starts_with("application/")),
+            media => offered.iter().find(|content_type| *content_type == media),
+        })
+        .cloned()
+        .ok_or_else(not_acceptable)
+}
+
+// Converts a successful JSON response to the negotiated content type. Errors are left in their
+// own format. Streamed responses are left too, as they would have to be buffered whole; the
+// listings only stream their items when JSON is negotiated.
+pub async fn encode_response(response: Response, content_type: &str) -> Response {
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .map(|value| value.as_bytes().starts_with(JSON_CONTENT_TYPE.as_bytes()))
+        .unwrap_or(false);
+    if content_type == JSON_CONTENT_TYPE
+        || !response.status().is_success()
+        || !is_json
+        || response.body().size_hint().exact().is_none()
+    {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(error) => {
+            log::error!("Response body could not be read: {}", error);
+            let mut response = Response::new(String::from("Internal Server Error").into());
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            return response;
+        }
+    };
+    let Ok(data) = serde_json::from_slice::<Value>(&body) else {
+        return Response::from_parts(parts, body.into());
+    };
+    let body = match content_type {
+        MSGPACK_CONTENT_TYPE => rmp_serde::to_vec_named(&data).map_err(|e| e.to_string()),
+        CBOR_CONTENT_TYPE => serde_cbor::to_vec(&data).map_err(|e| e.to_string()),
+        _ => return Response::from_parts(parts, body.into()),
+    };
+    let body = body.expect("JSON values are always serializable");
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_str(content_type).expect("Content types are valid header values"),
+    );
+    Response::from_parts(parts, body.into())
+}
+
+// Every negotiated response depends on the Accept header of the request, so caches must not
+// serve the representation chosen for one client to another
+pub fn vary_on_accept(mut response: Response) -> Response {
+    response
+        .headers_mut()
+        .append(VARY, HeaderValue::from_static("accept"));
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use commons::models::{event::Event, signature::Signature, state::SubjectData};
+    use serde::{de::DeserializeOwned, Serialize};
+
+    fn offered(content_types: &[&str]) -> Vec<String> {
+        content_types.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn test_content_type_follows_the_client_preference() {
+        let all = offered(&[JSON_CONTENT_TYPE, MSGPACK_CONTENT_TYPE, CBOR_CONTENT_TYPE]);
+        assert_eq!(negotiate(None, &all).unwrap(), JSON_CONTENT_TYPE);
+        assert_eq!(
+            negotiate(Some("application/cbor"), &all).unwrap(),
+            CBOR_CONTENT_TYPE
+        );
+        assert_eq!(
+            negotiate(Some("application/json;q=0.5, application/msgpack"), &all).unwrap(),
+            MSGPACK_CONTENT_TYPE
+        );
+        assert_eq!(
+            negotiate(Some("text/html, */*;q=0.8"), &all).unwrap(),
+            JSON_CONTENT_TYPE
+        );
+        assert_eq!(
+            negotiate(Some("application/json;q=0, */*"), &all).unwrap(),
+            MSGPACK_CONTENT_TYPE
+        );
+        let json_only = offered(&[JSON_CONTENT_TYPE]);
+        assert!(matches!(
+            negotiate(Some("application/cbor"), &json_only),
+            Err(Error::NotAcceptable(_))
+        ));
+        assert_eq!(
+            negotiate(Some("application/cbor, application/json;q=0.1"), &json_only).unwrap(),
+            JSON_CONTENT_TYPE
+        );
+    }
+
+    #[test]
+    fn test_json_responses_are_converted() {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                let subjects = serde_json::json!([{ "subject_id": "subject1", "sn": 0 }]);
+                let json = || {
+                    let mut response = Response::new(serde_json::to_vec(&subjects).unwrap().into());
+                    response
+                        .headers_mut()
+                        .insert(CONTENT_TYPE, HeaderValue::from_static(JSON_CONTENT_TYPE));
+                    response
+                };
+                let response = encode_response(json(), CBOR_CONTENT_TYPE).await;
+                assert_eq!(response.headers()[CONTENT_TYPE], CBOR_CONTENT_TYPE);
+                let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+                assert_eq!(serde_cbor::from_slice::<Value>(&body).unwrap(), subjects);
+                let response = encode_response(json(), MSGPACK_CONTENT_TYPE).await;
+                let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+                assert_eq!(rmp_serde::from_slice::<Value>(&body).unwrap(), subjects);
+                // Errors keep their own body
+                let not_found = encode_response(
+                    warp::Reply::into_response(StatusCode::NOT_FOUND),
+                    CBOR_CONTENT_TYPE,
+                )
+                .await;
+                assert_eq!(not_found.status(), StatusCode::NOT_FOUND);
+            });
+    }
+
+    // Node models sent as JSON or CBOR are decoded back to the same model
+    async fn assert_round_trip<T: Serialize + DeserializeOwned>(model: Value) {
+        let data: T = serde_json::from_value(model.clone()).unwrap();
+        for content_type in [JSON_CONTENT_TYPE, CBOR_CONTENT_TYPE] {
+            let mut response = Response::new(serde_json::to_vec(&data).unwrap().into());
+            response
+                .headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static(JSON_CONTENT_TYPE));
+            let response = encode_response(response, content_type).await;
+            assert_eq!(response.headers()[CONTENT_TYPE], content_type);
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            let decoded: T = if content_type == CBOR_CONTENT_TYPE {
+                serde_cbor::from_slice(&body).unwrap()
+            } else {
+                serde_json::from_slice(&body).unwrap()
+            };
+            assert_eq!(serde_json::to_value(decoded).unwrap(), model);
+        }
+    }
+
+    #[test]
+    fn test_node_models_round_trip() {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                let signature = serde_json::json!({
+                    "content": {
+                        "signer": "ELm4bPbcZ8zVUWJf0aIACxHIiXP4cY1v-P2Evs0bE3kI",
+                        "event_content_hash": "J31pjM0RVGgGwfAa5Ptfir3ZSc_mjytEPSkXbl-1rczY",
+                        "timestamp": 1671544386
+                    },
+                    "signature": "SEMu7A5VnxaEFufuMBK39jAoLCSaeNVeEMP3RohSS4TsRZ_4l_zb802vweyok012q3LJbceMghZENVfCtHYJDoDA"
+                });
+                assert_round_trip::<Signature>(signature.clone()).await;
+                assert_round_trip::<SubjectData>(serde_json::json!({
+                    "subject_id": "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
+                    "governance_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+                    "sn": 0,
+                    "public_key": "ELZ_b-kZzdPykcYuRNC2ZZe_2lCTCUoo60GXfR4cuXMw",
+                    "namespace": "namespace1",
+                    "schema_id": "Prueba",
+                    "owner": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                    "properties": "{\"localizacion\":\"España\",\"temperatura\":10}"
+                }))
+                .await;
+                assert_round_trip::<Event>(serde_json::json!({
+                    "event_content": {
+                        "subject_id": "J4LS4VgGo3SDmC4F4hFFBkw8Kgv4ItpYgjUbw0gDxNk8",
+                        "event_request": {
+                            "request": {
+                                "Create": {
+                                    "governance_id": "JF3q2MSpcds-jzhNYg3tNtT2nFU0eA9e85tKGUdDvJpo",
+                                    "schema_id": "Prueba",
+                                    "namespace": "namespace1",
+                                    "payload": {
+                                        "Json": "{\"localizacion\":\"España\",\"temperatura\":10}"
+                                    }
+                                }
+                            },
+                            "timestamp": 1671544386,
+                            "signature": {
+                                "content": {
+                                    "signer": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                                    "event_content_hash": "JX6KgyxQGGV0X81gsFU72klOBT39PS1R1cUEUIq8Ja0I",
+                                    "timestamp": 1671544386
+                                },
+                                "signature": "SESDdbUuRkyomQYN47SEFl1ZdytNg-hH7QMyzs-v0gTUxrNbMihoL13tFTBmYzLEWhkoec0xP2l9sw-fo9cpHiAQ"
+                            },
+                            "approvals": []
+                        },
+                        "sn": 0,
+                        "previous_hash": "",
+                        "state_hash": "JrmBobio8phNFI3Fxd3pDo7NhyFi3OYYmRgds8Gpa1TY",
+                        "metadata": {
+                            "namespace": "namespace1",
+                            "governance_id": "JF3q2MSpcds-jzhNYg3tNtT2nFU0eA9e85tKGUdDvJpo",
+                            "governance_version": 0,
+                            "schema_id": "Prueba",
+                            "owner": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w"
+                        },
+                        "approved": true
+                    },
+                    "signature": signature
+                }))
+                .await;
+            });
+    }
+}
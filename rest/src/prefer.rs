@@ -0,0 +1,105 @@
+use serde::Serialize;
+use serde_json::Value;
+use warp::{reply::Response, Reply};
+
+// Fields kept in the body of a write answered with return=minimal
+const MINIMAL_FIELDS: [&str; 2] = ["request_id", "subject_id"];
+
+// Return preference of RFC 7240 sent by clients in the Prefer header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReturnPreference {
+    Minimal,
+    Representation,
+}
+
+impl ReturnPreference {
+    pub fn from_header(prefer: Option<String>) -> Option<Self> {
+        prefer?
+            .split(',')
+            .map(|preference| preference.trim().to_lowercase())
+            .find_map(|preference| match preference.as_str() {
+                "return=minimal" => Some(Self::Minimal),
+                "return=representation" => Some(Self::Representation),
+                _ => None,
+            })
+    }
+
+    fn header_value(&self) -> &'static str {
+        match self {
+            Self::Minimal => "return=minimal",
+            Self::Representation => "return=representation",
+        }
+    }
+
+    pub fn reply<T: Serialize>(&self, data: &T) -> Result<Response, serde_json::Error> {
+        let mut body = serde_json::to_value(data)?;
+        if let (Self::Minimal, Value::Object(fields)) = (self, &mut body) {
+            fields.retain(|field, _| MINIMAL_FIELDS.contains(&field.as_str()));
+        }
+        Ok(warp::reply::with_header(
+            warp::reply::json(&body),
+            "Preference-Applied",
+            self.header_value(),
+        )
+        .into_response())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn request_data() -> Value {
+        serde_json::json!({
+            "request": { "Create": { "governance_id": "", "schema_id": "", "namespace": "" } },
+            "request_id": "JpxalqMTQcDcLG3dwb8uvcrstJo6pmFEzUwhzi0nGPOA",
+            "timestamp": 1671705355,
+            "subject_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0",
+            "sn": 0
+        })
+    }
+
+    async fn body_of(response: Response) -> Value {
+        let bytes = warp::hyper::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_return_minimal() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let preference =
+                ReturnPreference::from_header(Some("respond-async, return=minimal".into()))
+                    .unwrap();
+            assert_eq!(preference, ReturnPreference::Minimal);
+            let response = preference.reply(&request_data()).unwrap();
+            assert_eq!(response.headers()["Preference-Applied"], "return=minimal");
+            assert_eq!(
+                body_of(response).await,
+                serde_json::json!({
+                    "request_id": "JpxalqMTQcDcLG3dwb8uvcrstJo6pmFEzUwhzi0nGPOA",
+                    "subject_id": "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0"
+                })
+            );
+        });
+    }
+
+    #[test]
+    fn test_return_representation() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let preference =
+                ReturnPreference::from_header(Some("return=representation".into())).unwrap();
+            assert_eq!(preference, ReturnPreference::Representation);
+            let response = preference.reply(&request_data()).unwrap();
+            assert_eq!(
+                response.headers()["Preference-Applied"],
+                "return=representation"
+            );
+            assert_eq!(body_of(response).await, request_data());
+            assert_eq!(ReturnPreference::from_header(None), None);
+        });
+    }
+}
@@ -0,0 +1,223 @@
+use serde_json::{Map, Value};
+
+// Event fields that can be requested through `?fields=` and their location in a serialized event
+const EVENT_FIELDS: [(&str, &str); 10] = [
+    ("subject_id", "/event_content/subject_id"),
+    ("sn", "/event_content/sn"),
+    ("previous_hash", "/event_content/previous_hash"),
+    ("state_hash", "/event_content/state_hash"),
+    ("metadata", "/event_content/metadata"),
+    ("approved", "/event_content/approved"),
+    ("request", "/event_content/event_request/request"),
+    ("timestamp", "/event_content/event_request/timestamp"),
+    ("approvals", "/event_content/event_request/approvals"),
+    ("signature", "/signature"),
+];
+
+// Splits a comma separated `fields` parameter into the field names of the event endpoints.
+// None means no projection. JSON Pointers are left to the shared projection of every route,
+// so a parameter with pointers only is not a projection by name.
+pub fn parse_fields(fields: &Option<String>) -> Option<Vec<String>> {
+    let fields = fields.as_ref()?;
+    let fields: Vec<String> = fields
+        .split(',')
+        .map(|field| field.trim().to_owned())
+        .filter(|field| !field.is_empty())
+        .collect();
+    let names: Vec<String> = fields
+        .iter()
+        .filter(|field| !field.starts_with('/'))
+        .cloned()
+        .collect();
+    if names.is_empty() && !fields.is_empty() {
+        return None;
+    }
+    Some(names)
+}
+
+// JSON Pointers of a comma separated `fields` parameter. None if there is none.
+pub fn parse_pointers(fields: &Option<String>) -> Option<Vec<String>> {
+    let pointers: Vec<String> = fields
+        .as_ref()?
+        .split(',')
+        .map(str::trim)
+        .filter(|field| field.starts_with('/'))
+        .map(str::to_owned)
+        .collect();
+    if pointers.is_empty() {
+        None
+    } else {
+        Some(pointers)
+    }
+}
+
+// Keeps only the members addressed by the pointers, in their original place. Lists are
+// projected item by item. Pointers address object members, so those that cross an array or
+// match nothing are ignored.
+pub fn project_pointers(value: &Value, pointers: &[String]) -> Value {
+    if let Value::Array(items) = value {
+        return Value::Array(
+            items
+                .iter()
+                .map(|item| project_pointers(item, pointers))
+                .collect(),
+        );
+    }
+    let mut projected = Map::new();
+    for pointer in pointers {
+        let tokens: Vec<String> = pointer
+            .split('/')
+            .skip(1)
+            .map(|token| token.replace("~1", "/").replace("~0", "~"))
+            .collect();
+        let member = tokens.iter().try_fold(value, |source, token| {
+            source.as_object().and_then(|object| object.get(token))
+        });
+        let (Some(member), Some((last, path))) = (member, tokens.split_last()) else {
+            continue;
+        };
+        // Every member along the path is an object, as the pointer was resolved through them
+        let mut target = &mut projected;
+        for token in path {
+            target = target
+                .entry(token.clone())
+                .or_insert_with(|| Value::Object(Map::new()))
+                .as_object_mut()
+                .unwrap();
+        }
+        target.insert(last.clone(), member.clone());
+    }
+    Value::Object(projected)
+}
+
+// Builds a flat object with the requested event fields. Unknown names are ignored.
+pub fn project_event(event: &Value, fields: &[String]) -> Value {
+    let mut projected = Map::new();
+    for (name, pointer) in EVENT_FIELDS {
+        if !fields.iter().any(|field| field == name) {
+            continue;
+        }
+        if let Some(value) = event.pointer(pointer) {
+            projected.insert(name.to_owned(), value.clone());
+        }
+    }
+    Value::Object(projected)
+}
+
+// Flattens properties to dotted keys with scalar values. Array items use index notation and
+// empty objects or arrays are kept as they are, so no key is lost.
+pub fn flatten_properties(properties: &Value) -> Map<String, Value> {
+    let mut flat = Map::new();
+    flatten_into(properties, String::new(), &mut flat);
+    flat
+}
+
+fn flatten_into(value: &Value, path: String, flat: &mut Map<String, Value>) {
+    match value {
+        Value::Object(fields) if !fields.is_empty() => {
+            for (key, value) in fields {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                flatten_into(value, path, flat);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (index, item) in items.iter().enumerate() {
+                flatten_into(item, format!("{}[{}]", path, index), flat);
+            }
+        }
+        value => {
+            flat.insert(path, value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_minimal_event_projection() {
+        let event = serde_json::json!({
+            "event_content": {
+                "subject_id": "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
+                "event_request": { "request": {}, "timestamp": 1671706794, "approvals": [] },
+                "sn": 1,
+                "state_hash": "JMqLbPz7VY1pjuj9-n0qT0UuOGH_TpQVRaVEOHSaE_5Y",
+                "approved": true
+            },
+            "signature": {}
+        });
+        let fields = parse_fields(&Some("sn, timestamp,state_hash,unknown".into())).unwrap();
+        assert_eq!(
+            project_event(&event, &fields),
+            serde_json::json!({
+                "sn": 1,
+                "timestamp": 1671706794,
+                "state_hash": "JMqLbPz7VY1pjuj9-n0qT0UuOGH_TpQVRaVEOHSaE_5Y"
+            })
+        );
+    }
+
+    #[test]
+    fn test_pointer_projection() {
+        let subjects = serde_json::json!([
+            { "subject_id": "J1", "sn": 0, "owner": "E1", "a/b": 1 },
+            { "subject_id": "J2", "sn": 3, "owner": "E1", "a/b": 2 }
+        ]);
+        let pointers = parse_pointers(&Some("/subject_id, /sn, /a~1b, /missing".into())).unwrap();
+        assert_eq!(
+            project_pointers(&subjects, &pointers),
+            serde_json::json!([
+                { "subject_id": "J1", "sn": 0, "a/b": 1 },
+                { "subject_id": "J2", "sn": 3, "a/b": 2 }
+            ])
+        );
+        let event = serde_json::json!({
+            "event_content": { "sn": 1, "approved": true, "event_request": { "approvals": [] } },
+            "signature": {}
+        });
+        let pointers = vec![
+            "/event_content/sn".to_owned(),
+            "/event_content/approved".to_owned(),
+        ];
+        assert_eq!(
+            project_pointers(&event, &pointers),
+            serde_json::json!({ "event_content": { "sn": 1, "approved": true } })
+        );
+        // Pointers are not event field names
+        assert_eq!(parse_fields(&Some("/event_content/sn".into())), None);
+        assert_eq!(
+            parse_fields(&Some("sn,/event_content".into())),
+            Some(vec!["sn".to_owned()])
+        );
+    }
+
+    #[test]
+    fn test_flatten_nested_properties() {
+        let properties = serde_json::json!({
+            "localizacion": "España",
+            "sensor": { "temp": 10, "tags": ["a", "b"], "extra": {} },
+            "lecturas": [{ "valor": 1 }]
+        });
+        let flat = flatten_properties(&properties);
+        let mut keys: Vec<&str> = flat.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                "lecturas[0].valor",
+                "localizacion",
+                "sensor.extra",
+                "sensor.tags[0]",
+                "sensor.tags[1]",
+                "sensor.temp"
+            ]
+        );
+        assert_eq!(flat["sensor.temp"], 10);
+        assert_eq!(flat["sensor.tags[1]"], "b");
+    }
+}
@@ -1,5 +1,5 @@
 use serde::Deserialize;
-use utoipa::IntoParams;
+use utoipa::{IntoParams, ToSchema};
 
 #[derive(Debug, Deserialize, IntoParams)]
 #[into_params(parameter_in = Query)]
@@ -8,6 +8,28 @@ pub struct GetEventsQuery {
     pub from: Option<i64>,
     // Quantity of events requested
     pub quantity: Option<i64>,
+    // Quantity of most recent events requested, instead of from and quantity
+    pub tail: Option<i64>,
+    // Comma separated list of event fields to return
+    pub fields: Option<String>,
+    // Add to each event the properties of the subject after applying it
+    pub with_state: Option<bool>,
+    // Sn order of the events returned
+    pub order: Option<SortOrder>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetEventQuery {
+    // Comma separated list of event fields to return
+    pub fields: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetSubjectQuery {
+    // Add the number of events of the subject to the response
+    pub include_event_count: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, IntoParams)]
@@ -17,6 +39,30 @@ pub struct GetAllSubjectsQuery {
     pub from: Option<usize>,
     // Quantity of subjects requested
     pub quantity: Option<usize>,
+    // Namespace of the subjects. All namespaces when it is empty or omitted
+    pub namespace: Option<String>,
+    // Only subjects with this schema. Not filtered when it is empty
+    pub schema_id: Option<String>,
+    // Only subjects of this owner. Not filtered when it is empty
+    pub owner: Option<String>,
+    // Report the number of subjects of the whole listing, which reads all of it
+    pub count: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchSubjectsQuery {
+    // Property compared, a top level field or a JSON pointer
+    pub key: String,
+    pub op: SearchOperator,
+    // Read with the type of the property
+    pub value: String,
+    // Namespace of the subjects. All namespaces when it is empty or omitted
+    pub namespace: Option<String>,
+    // Number of initial subject among those that match
+    pub from: Option<usize>,
+    // Quantity of subjects requested
+    pub quantity: Option<usize>,
 }
 
 #[derive(Debug, Deserialize, IntoParams)]
@@ -27,3 +73,137 @@ pub struct GetSignaturesQuery {
     // Quantity of signatures requested
     pub quantity: Option<usize>,
 }
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ResolvedRequestQuery {
+    // Subject of the event produced by the request, needed once it is resolved
+    pub subject_id: Option<String>,
+    // SN of the event produced by the request
+    pub sn: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetEventsStreamQuery {
+    // SN of the first event to replay
+    pub from: Option<u64>,
+    // Representation of the events sent
+    pub format: Option<EventsFormat>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SimulatedBatchQuery {
+    // Return the state left by each payload besides the final subject
+    pub include_states: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SnapshotQuery {
+    // Only subjects of this governance
+    pub governance_id: Option<String>,
+    // Only subjects with this schema
+    pub schema_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TransitionsQuery {
+    // SN of the first transition. Defaults to 1
+    pub from: Option<u64>,
+    // Quantity of transitions requested
+    pub quantity: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AsOfQuery {
+    // Unix timestamp, in seconds
+    pub ts: i64,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ActivityQuery {
+    // Maximum number of events returned
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ResolveQuery {
+    // Alias to look up
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PendingRequestsQuery {
+    // Return a summary of each request instead of the whole request
+    pub summary: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CompareQuery {
+    // Subject whose properties are the origin of the patch
+    pub a: String,
+    // Subject whose properties are the target of the patch
+    pub b: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetAllGovernancesQuery {
+    // Field the governances are sorted by
+    pub order_by: Option<GovernanceOrder>,
+    // Direction of the sort
+    pub order: Option<SortOrder>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GovernanceOrder {
+    GovernanceId,
+    Sn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchOperator {
+    Eq,
+    Ne,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EventsFormat {
+    Full,
+    Compact,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ShapingQuery {
+    // Comma separated JSON Pointers of the members to keep in the response. Lists are projected
+    // item by item
+    pub fields: Option<String>,
+    // Wrap the response as {"data", "meta"}. Overrides the envelope setting of the server
+    pub meta: Option<bool>,
+    // Characters of the properties of each item of a list that are kept
+    pub truncate: Option<usize>,
+}
@@ -0,0 +1,294 @@
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::Stream;
+use hyper::{
+    body::Bytes,
+    server::{
+        accept::Accept,
+        conn::{AddrIncoming, AddrStream},
+    },
+    Body, Request,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{Instant, Sleep},
+};
+use warp::{
+    http::{header, StatusCode},
+    reply::Response,
+};
+
+// Written before closing a connection whose headers did not arrive in time
+const REQUEST_TIMEOUT_RESPONSE: &[u8] =
+    b"HTTP/1.1 408 Request Timeout\r\nconnection: close\r\ncontent-length: 0\r\n\r\n";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    // Receiving the headers of a request
+    Headers,
+    // Request handed to the service, which bounds the body on its own
+    Request,
+    // Waiting for the next request. The first byte received starts the headers phase
+    Idle,
+}
+
+// Phase of a connection, shared between its stream and its service
+#[derive(Debug, Clone)]
+pub struct ConnectionPhase(Arc<Mutex<Phase>>);
+
+impl ConnectionPhase {
+    pub fn start_request(&self) {
+        *self.0.lock().unwrap() = Phase::Request;
+    }
+
+    pub fn finish_request(&self) {
+        *self.0.lock().unwrap() = Phase::Idle;
+    }
+}
+
+// Connections that must send the headers of each request within the timeout. Responses are
+// not bounded, so long lived streams are not affected.
+pub struct TimedIncoming {
+    incoming: AddrIncoming,
+    timeout: Duration,
+}
+
+impl TimedIncoming {
+    pub fn new(incoming: AddrIncoming, timeout: Duration) -> Self {
+        Self { incoming, timeout }
+    }
+}
+
+impl Accept for TimedIncoming {
+    type Conn = TimedStream;
+    type Error = io::Error;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let timeout = self.timeout;
+        Pin::new(&mut self.incoming)
+            .poll_accept(cx)
+            .map(|conn| conn.map(|conn| conn.map(|stream| TimedStream::new(stream, timeout))))
+    }
+}
+
+pub struct TimedStream {
+    stream: AddrStream,
+    phase: ConnectionPhase,
+    timeout: Duration,
+    deadline: Pin<Box<Sleep>>,
+}
+
+impl TimedStream {
+    fn new(stream: AddrStream, timeout: Duration) -> Self {
+        // A client that connects and sends nothing is treated as a slow one
+        Self {
+            stream,
+            phase: ConnectionPhase(Arc::new(Mutex::new(Phase::Headers))),
+            timeout,
+            deadline: Box::pin(tokio::time::sleep(timeout)),
+        }
+    }
+
+    pub fn phase(&self) -> ConnectionPhase {
+        self.phase.clone()
+    }
+}
+
+impl AsyncRead for TimedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut self.stream).poll_read(cx, buf);
+        let received = buf.filled().len() > filled;
+        let (phase, started) = {
+            let mut phase = self.phase.0.lock().unwrap();
+            let started = *phase == Phase::Idle && received;
+            if started {
+                *phase = Phase::Headers;
+            }
+            (*phase, started)
+        };
+        if started {
+            let deadline = Instant::now() + self.timeout;
+            self.deadline.as_mut().reset(deadline);
+        }
+        if phase == Phase::Headers && self.deadline.as_mut().poll(cx).is_ready() {
+            // Best effort, the connection is closed either way
+            let _ = Pin::new(&mut self.stream).poll_write(cx, REQUEST_TIMEOUT_RESPONSE);
+            return Poll::Ready(Err(io::ErrorKind::TimedOut.into()));
+        }
+        result
+    }
+}
+
+impl AsyncWrite for TimedStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+// Body that fails once the timeout expires before it is fully received
+struct TimedBody {
+    body: Body,
+    deadline: Pin<Box<Sleep>>,
+    expired: Arc<AtomicBool>,
+}
+
+impl Stream for TimedBody {
+    type Item = Result<Bytes, io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.expired.load(Ordering::SeqCst) {
+            return Poll::Ready(None);
+        }
+        if let Poll::Ready(chunk) = Pin::new(&mut self.body).poll_next(cx) {
+            return Poll::Ready(
+                chunk.map(|chunk| {
+                    chunk.map_err(|error| io::Error::new(io::ErrorKind::Other, error))
+                }),
+            );
+        }
+        if self.deadline.as_mut().poll(cx).is_ready() {
+            self.expired.store(true, Ordering::SeqCst);
+            return Poll::Ready(Some(Err(io::ErrorKind::TimedOut.into())));
+        }
+        Poll::Pending
+    }
+}
+
+// Bounds the time taken to receive the body of the request. The returned flag is set if the
+// body did not arrive in time, and then the response must be replaced with request_timed_out.
+pub fn timed_request(
+    request: Request<Body>,
+    timeout: Duration,
+) -> (Request<Body>, Arc<AtomicBool>) {
+    let expired = Arc::new(AtomicBool::new(false));
+    let flag = expired.clone();
+    let request = request.map(|body| {
+        Body::wrap_stream(TimedBody {
+            body,
+            deadline: Box::pin(tokio::time::sleep(timeout)),
+            expired: flag,
+        })
+    });
+    (request, expired)
+}
+
+pub fn request_timed_out() -> Response {
+    let mut response = Response::new(String::from("Request Timeout").into());
+    *response.status_mut() = StatusCode::REQUEST_TIMEOUT;
+    response.headers_mut().insert(
+        header::CONNECTION,
+        header::HeaderValue::from_static("close"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use warp::Filter;
+
+    const PORT: u16 = 40199;
+
+    // Sends the request and reads until the server closes the connection
+    async fn exchange(request: &[u8]) -> String {
+        let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", PORT))
+            .await
+            .unwrap();
+        stream.write_all(request).await.unwrap();
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response).await;
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    #[test]
+    fn test_slow_requests_are_closed() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let filter = warp::post()
+                .and(warp::body::bytes())
+                .map(|body: Bytes| body.len().to_string());
+            let (shutdown, stop) = tokio::sync::oneshot::channel::<()>();
+            let server = tokio::spawn(crate::deadline::serve(
+                filter,
+                ([127, 0, 0, 1], PORT).into(),
+                Duration::from_secs(30),
+                Duration::from_millis(200),
+                None,
+                async {
+                    let _ = stop.await;
+                },
+            ));
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let slow = |request: &'static [u8]| {
+                tokio::time::timeout(Duration::from_secs(5), exchange(request))
+            };
+            // The headers are never completed
+            let response = slow(b"POST / HTTP/1.1\r\nhost: localhost\r\n")
+                .await
+                .unwrap();
+            assert!(response.starts_with("HTTP/1.1 408"));
+            // Only half of the body is sent
+            let response = slow(
+                b"POST / HTTP/1.1\r\nhost: localhost\r\n\
+                content-length: 10\r\n\r\n12345",
+            )
+            .await
+            .unwrap();
+            assert!(response.starts_with("HTTP/1.1 408"));
+            assert!(response.contains("connection: close"));
+            // Complete requests are not affected
+            let response = slow(
+                b"POST / HTTP/1.1\r\nhost: localhost\r\n\
+                content-length: 2\r\nconnection: close\r\n\r\n12",
+            )
+            .await
+            .unwrap();
+            assert!(response.starts_with("HTTP/1.1 200"));
+            shutdown.send(()).unwrap();
+            server.await.unwrap().unwrap();
+        });
+    }
+}
@@ -0,0 +1,262 @@
+use commons::{
+    identifier::Derivable,
+    models::{event::Event, event_request::EventRequestType},
+};
+use core::event_request::RequestPayload;
+use json_patch::Patch;
+use serde_json::Value;
+
+use super::responses::{CompactEvent, Transition};
+
+// State of a subject before its genesis event
+pub fn genesis_state() -> Value {
+    Value::Object(serde_json::Map::new())
+}
+
+pub fn event_payload(event: &Event) -> &RequestPayload {
+    match &event.event_content.event_request.request {
+        EventRequestType::Create(request) => &request.payload,
+        EventRequestType::State(request) => &request.payload,
+    }
+}
+
+// Applies the payload of an event to the state of its subject. A Json payload replaces the
+// whole state while a JsonPatch payload modifies it.
+pub fn apply_payload(state: &mut Value, payload: &RequestPayload) -> Result<(), String> {
+    match payload {
+        RequestPayload::Json(data) => {
+            *state = serde_json::from_str(data).map_err(|e| e.to_string())?;
+        }
+        RequestPayload::JsonPatch(data) => {
+            let patch: Patch = serde_json::from_str(data).map_err(|e| e.to_string())?;
+            json_patch::patch(state, &patch).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+// State of a subject after each of the given payloads, replayed from its genesis
+pub fn replay_states<'a>(
+    payloads: impl IntoIterator<Item = &'a RequestPayload>,
+) -> Result<Vec<Value>, String> {
+    let mut state = genesis_state();
+    payloads
+        .into_iter()
+        .map(|payload| {
+            apply_payload(&mut state, payload)?;
+            Ok(state.clone())
+        })
+        .collect()
+}
+
+// Sn of the state of a subject as of a timestamp, given its events as (sn, timestamp) pairs in
+// any order. The timestamps of the requests do not have to grow with the sn, so it is the
+// highest sn among the events requested at or before the timestamp, even if some previous
+// events were requested after it.
+pub fn sn_as_of(events: impl IntoIterator<Item = (u64, i64)>, timestamp: i64) -> Option<u64> {
+    events
+        .into_iter()
+        .filter(|(_, requested)| *requested <= timestamp)
+        .map(|(sn, _)| sn)
+        .max()
+}
+
+// Applies the payloads in order to a state, checking each resulting state with `validate`.
+// Stops at the first payload that can not be applied or leaves an invalid state, returning its
+// position and the reason.
+pub fn simulate_payloads(
+    mut state: Value,
+    payloads: &[RequestPayload],
+    validate: impl Fn(&Value) -> Result<(), String>,
+) -> Result<Vec<Value>, (usize, String)> {
+    payloads
+        .iter()
+        .enumerate()
+        .map(|(index, payload)| {
+            apply_payload(&mut state, payload)
+                .and_then(|_| validate(&state))
+                .map_err(|reason| (index, reason))?;
+            Ok(state.clone())
+        })
+        .collect()
+}
+
+// JSON Patch from the state left by the previous event to the one left by each event, given the
+// states replayed from the genesis. The genesis has no previous state, so the first transition
+// is the one of sn 1, even if `from` is lower.
+pub fn transitions(states: &[Value], from: u64) -> Vec<Transition> {
+    states
+        .windows(2)
+        .zip(1..)
+        .filter(|(_, sn)| *sn >= from)
+        .map(|(pair, sn)| Transition {
+            sn,
+            patch: serde_json::to_value(json_patch::diff(&pair[0], &pair[1]))
+                .expect("JSON Patches are always serializable"),
+        })
+        .collect()
+}
+
+// JSON Patch between two states, CBOR encoded and then base64 encoded to fit in text frames
+pub fn encode_diff(previous: &Value, next: &Value) -> String {
+    let patch = json_patch::diff(previous, next);
+    base64::encode(serde_cbor::to_vec(&patch).unwrap())
+}
+
+pub fn decode_diff(diff: &str) -> Result<Patch, String> {
+    let bytes = base64::decode(diff).map_err(|e| e.to_string())?;
+    serde_cbor::from_slice(&bytes).map_err(|e| e.to_string())
+}
+
+// Turns the full events of a subject into compact events. Events must be received in order
+// from the genesis; the ones before `from` only update the replayed state.
+pub struct CompactEncoder {
+    state: Value,
+    from: u64,
+}
+
+impl CompactEncoder {
+    pub fn new(from: u64) -> Self {
+        Self {
+            state: genesis_state(),
+            from,
+        }
+    }
+
+    pub fn encode(&mut self, event: &Event) -> Result<Option<CompactEvent>, String> {
+        let mut next = self.state.clone();
+        apply_payload(&mut next, event_payload(event))?;
+        let previous = std::mem::replace(&mut self.state, next);
+        if event.event_content.sn < self.from {
+            return Ok(None);
+        }
+        let request_type = match event.event_content.event_request.request {
+            EventRequestType::Create(_) => "Create",
+            EventRequestType::State(_) => "State",
+        };
+        let request_signature = &event.event_content.event_request.signature.content;
+        Ok(Some(CompactEvent {
+            sn: event.event_content.sn,
+            request_type: request_type.to_owned(),
+            signer: request_signature.signer.to_str(),
+            timestamp: event.event_content.event_request.timestamp,
+            diff: encode_diff(&previous, &self.state),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_apply_payloads() {
+        let mut state = genesis_state();
+        apply_payload(&mut state, &RequestPayload::Json(r#"{"a":"69"}"#.into())).unwrap();
+        apply_payload(
+            &mut state,
+            &RequestPayload::JsonPatch(r#"[{"op":"replace","path":"/a","value":"70"}]"#.into()),
+        )
+        .unwrap();
+        assert_eq!(state, serde_json::json!({"a": "70"}));
+    }
+
+    #[test]
+    fn test_replayed_states_follow_each_payload() {
+        let payloads = vec![
+            RequestPayload::Json(r#"{"a":"69"}"#.into()),
+            RequestPayload::JsonPatch(r#"[{"op":"replace","path":"/a","value":"70"}]"#.into()),
+            RequestPayload::JsonPatch(r#"[{"op":"add","path":"/b","value":1}]"#.into()),
+        ];
+        let states = replay_states(&payloads).unwrap();
+        assert_eq!(states.len(), payloads.len());
+        assert_eq!(states[0], serde_json::json!({"a": "69"}));
+        assert_eq!(states[1], serde_json::json!({"a": "70"}));
+        let mut current = genesis_state();
+        for payload in &payloads {
+            apply_payload(&mut current, payload).unwrap();
+        }
+        assert_eq!(states.last().unwrap(), &current);
+    }
+
+    #[test]
+    fn test_sn_as_of_timestamp() {
+        // The request of sn 2 was signed before the one of sn 1
+        let events = [(0, 100), (1, 300), (2, 250), (3, 400)];
+        assert_eq!(sn_as_of(events, 99), None);
+        assert_eq!(sn_as_of(events, 100), Some(0));
+        assert_eq!(sn_as_of(events, 260), Some(2));
+        assert_eq!(sn_as_of(events, 300), Some(2));
+        assert_eq!(sn_as_of(events, 1000), Some(3));
+    }
+
+    #[test]
+    fn test_simulated_payloads_are_cumulative() {
+        let payloads = vec![
+            RequestPayload::JsonPatch(r#"[{"op":"replace","path":"/a","value":"70"}]"#.into()),
+            RequestPayload::JsonPatch(r#"[{"op":"add","path":"/b","value":1}]"#.into()),
+        ];
+        let states =
+            simulate_payloads(serde_json::json!({"a": "69"}), &payloads, |_| Ok(())).unwrap();
+        assert_eq!(states.len(), 2);
+        assert_eq!(states[1], serde_json::json!({"a": "70", "b": 1}));
+        // The second payload leaves a state without "a"
+        let payloads = vec![
+            RequestPayload::Json(r#"{"a":"70"}"#.into()),
+            RequestPayload::Json(r#"{"b":1}"#.into()),
+            RequestPayload::Json(r#"{"a":"71"}"#.into()),
+        ];
+        let result = simulate_payloads(genesis_state(), &payloads, |state| {
+            state
+                .get("a")
+                .map(|_| ())
+                .ok_or_else(|| "a is required".to_owned())
+        });
+        assert_eq!(result, Err((1, "a is required".to_owned())));
+    }
+
+    #[test]
+    fn test_final_state_from_compact_diffs() {
+        let states = vec![
+            genesis_state(),
+            serde_json::json!({"localizacion": "España", "temperatura": 10}),
+            serde_json::json!({"localizacion": "Argentina", "temperatura": -3}),
+            serde_json::json!({"localizacion": "Argentina", "temperatura": -3, "sensor": {"id": 1}}),
+        ];
+        let diffs: Vec<String> = states
+            .windows(2)
+            .map(|pair| encode_diff(&pair[0], &pair[1]))
+            .collect();
+        let mut replica = genesis_state();
+        for diff in diffs {
+            json_patch::patch(&mut replica, &decode_diff(&diff).unwrap()).unwrap();
+        }
+        assert_eq!(&replica, states.last().unwrap());
+    }
+
+    #[test]
+    fn test_transitions_rebuild_the_current_state() {
+        let payloads = vec![
+            RequestPayload::Json(r#"{"localizacion":"España","temperatura":10}"#.into()),
+            RequestPayload::Json(r#"{"localizacion":"Argentina","temperatura":-3}"#.into()),
+            RequestPayload::JsonPatch(r#"[{"op":"add","path":"/sensor","value":{"id":1}}]"#.into()),
+            RequestPayload::JsonPatch(r#"[{"op":"remove","path":"/temperatura"}]"#.into()),
+        ];
+        let states = replay_states(&payloads).unwrap();
+        let all = transitions(&states, 0);
+        assert_eq!(
+            all.iter().map(|t| t.sn).collect::<Vec<u64>>(),
+            vec![1, 2, 3]
+        );
+        let mut replica = states[0].clone();
+        for transition in all {
+            let patch: Patch = serde_json::from_value(transition.patch).unwrap();
+            json_patch::patch(&mut replica, &patch).unwrap();
+        }
+        assert_eq!(&replica, states.last().unwrap());
+        // Pages start at `from`
+        let page = transitions(&states[..3], 2);
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].sn, 2);
+    }
+}
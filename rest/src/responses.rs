@@ -0,0 +1,676 @@
+use commons::models::{approval_signature::Acceptance, event::Event, state::SubjectData};
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TimelineEntry {
+    pub signer: String,
+    pub acceptance: Acceptance,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub enum RequestResolution {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RequestTimeline {
+    pub request_id: String,
+    pub votes: Vec<TimelineEntry>,
+    pub resolution: RequestResolution,
+}
+
+impl RequestTimeline {
+    pub fn new(
+        request_id: String,
+        mut votes: Vec<TimelineEntry>,
+        resolution: RequestResolution,
+    ) -> Self {
+        // Votes are stored in arrival order, which is not necessarily chronological
+        votes.sort_by_key(|vote| vote.timestamp);
+        Self {
+            request_id,
+            votes,
+            resolution,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ApprovalSignature {
+    pub signer: String,
+    pub acceptance: Acceptance,
+    pub timestamp: i64,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RequestSignatures {
+    pub request_id: String,
+    pub resolution: RequestResolution,
+    pub signatures: Vec<ApprovalSignature>,
+}
+
+impl RequestSignatures {
+    // Keeps the signatures of the quorum, those whose vote agrees with the resolution
+    pub fn new(request_id: String, approved: bool, signatures: Vec<ApprovalSignature>) -> Self {
+        let signatures = signatures
+            .into_iter()
+            .filter(|signature| matches!(signature.acceptance, Acceptance::Accept) == approved)
+            .collect();
+        Self {
+            request_id,
+            resolution: if approved {
+                RequestResolution::Approved
+            } else {
+                RequestResolution::Rejected
+            },
+            signatures,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct UiField {
+    pub name: String,
+    // JSON Schema type of the field, "any" if the schema does not restrict it
+    pub field_type: String,
+    pub required: bool,
+    pub widget: String,
+    // Extension keywords ("x-" prefixed) of the field as written in the schema
+    #[schema(value_type = Object)]
+    pub hints: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct SchemaUi {
+    pub schema_id: String,
+    // Fields in the order they should be laid out
+    pub fields: Vec<UiField>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ImpactedSubject {
+    pub subject_id: String,
+    pub schema_id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CompactEvent {
+    pub sn: u64,
+    pub request_type: String,
+    pub signer: String,
+    pub timestamp: i64,
+    // Base64 of the CBOR encoded JSON Patch from the previous state
+    pub diff: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+    pub retry_after: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Diagnostic {
+    pub timestamp: i64,
+    pub method: String,
+    pub route: String,
+    // Sent by the client in the x-request-id header, or assigned by the API if it did not
+    pub correlation_id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeStatus {
+    Ok,
+    Starting,
+    Ready,
+    #[serde(rename = "not ready")]
+    NotReady,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct HealthStatus {
+    pub status: NodeStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ValidationCheck {
+    pub check: String,
+    pub passed: bool,
+    pub message: Option<String>,
+}
+
+impl ValidationCheck {
+    pub fn new(check: &str, result: Result<(), String>) -> Self {
+        Self {
+            check: check.to_owned(),
+            passed: result.is_ok(),
+            message: result.err(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub checks: Vec<ValidationCheck>,
+}
+
+impl ValidationReport {
+    pub fn new(checks: Vec<ValidationCheck>) -> Self {
+        Self {
+            valid: checks.iter().all(|check| check.passed),
+            checks,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct GovernanceVersion {
+    pub governance_version: u64,
+}
+
+impl GovernanceVersion {
+    // The version a subject operates under is the one in the metadata of its latest event.
+    // Events are given as (sn, governance_version) pairs in any order.
+    pub fn latest(events: impl IntoIterator<Item = (u64, u64)>) -> Option<Self> {
+        events
+            .into_iter()
+            .max_by_key(|(sn, _)| *sn)
+            .map(|(_, governance_version)| Self { governance_version })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct LastUpdated {
+    pub sn: u64,
+    // Timestamp of the request of the latest event
+    pub timestamp: i64,
+}
+
+impl LastUpdated {
+    // Events are given as (sn, timestamp) pairs in any order
+    pub fn latest(events: impl IntoIterator<Item = (u64, i64)>) -> Option<Self> {
+        events
+            .into_iter()
+            .max_by_key(|(sn, _)| *sn)
+            .map(|(sn, timestamp)| Self { sn, timestamp })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct StateAsOf {
+    pub sn: u64,
+    // Timestamp of the request of the event at sn
+    pub timestamp: i64,
+    #[schema(value_type = Object)]
+    pub properties: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct SubjectState {
+    pub subject_id: String,
+    pub sn: u64,
+    // Hash of the state left by the latest event
+    pub state_hash: String,
+    pub properties: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Membership {
+    pub governance_id: String,
+    // Id of the member entry of the node in the governance
+    pub member_id: String,
+    // Key of the node identity listed in the governance
+    pub key: String,
+    pub description: Option<String>,
+    #[schema(value_type = Object)]
+    pub tags: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct BundledSchema {
+    pub governance_id: String,
+    // Sn of the governance the schema was taken from
+    pub governance_version: u64,
+    pub schema_id: String,
+    // JSON Schema of the subjects
+    #[schema(value_type = Object)]
+    pub schema: Value,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct GovernanceRevision {
+    pub sn: u64,
+    // Every event of a governance introduces a new version, numbered by its sn
+    pub governance_version: u64,
+    pub event: Event,
+}
+
+impl From<Event> for GovernanceRevision {
+    fn from(event: Event) -> Self {
+        Self {
+            sn: event.event_content.sn,
+            governance_version: event.event_content.sn,
+            event,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct SchemaPeriod {
+    pub schema_id: String,
+    pub from_sn: u64,
+    pub to_sn: u64,
+}
+
+impl SchemaPeriod {
+    // Groups consecutive events with the same schema. Events are given as (sn, schema_id) pairs
+    // in any order.
+    pub fn history(events: impl IntoIterator<Item = (u64, String)>) -> Vec<Self> {
+        let mut events: Vec<(u64, String)> = events.into_iter().collect();
+        events.sort_by_key(|(sn, _)| *sn);
+        let mut history: Vec<Self> = Vec::new();
+        for (sn, schema_id) in events {
+            match history.last_mut() {
+                Some(period) if period.schema_id == schema_id => period.to_sn = sn,
+                _ => history.push(Self {
+                    schema_id,
+                    from_sn: sn,
+                    to_sn: sn,
+                }),
+            }
+        }
+        history
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ApproverStats {
+    pub key: String,
+    // Member id in the governance, if the approver is still a member
+    pub member: Option<String>,
+    pub approvals: u64,
+    pub rejections: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ApprovalProgress {
+    pub approved: bool,
+    pub required: usize,
+    pub received: usize,
+    // Keys of the members that have not voted yet
+    pub missing_approvers: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Transition {
+    pub sn: u64,
+    // JSON Patch from the state left by the event sn - 1 to the one left by the event sn
+    #[schema(value_type = Object)]
+    pub patch: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct PendingCount {
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct QuorumCheck {
+    pub satisfied: bool,
+    pub required: usize,
+    // Acceptances of members of the governance at the version of the event
+    pub valid_approvals: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct RejectedRequest {
+    pub request_id: String,
+    pub subject_id: String,
+    pub sn: u64,
+    pub timestamp: i64,
+    // Keys of the approvers that voted against the request
+    pub rejected_by: Vec<String>,
+    // The node does not record why a request was rejected yet, so it is always empty
+    pub reason: Option<String>,
+}
+
+impl RejectedRequest {
+    pub fn new(
+        request_id: String,
+        subject_id: String,
+        sn: u64,
+        timestamp: i64,
+        votes: Vec<(String, Acceptance)>,
+    ) -> Self {
+        Self {
+            request_id,
+            subject_id,
+            sn,
+            timestamp,
+            rejected_by: votes
+                .into_iter()
+                .filter(|(_, acceptance)| matches!(acceptance, Acceptance::Reject))
+                .map(|(signer, _)| signer)
+                .collect(),
+            reason: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct RequestSummary {
+    pub request_id: String,
+    // Requests that create a subject have no subject id yet
+    pub subject_id: Option<String>,
+    pub schema_id: Option<String>,
+    pub timestamp: i64,
+    pub signer: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct NamespaceCount {
+    pub namespace: String,
+    pub subjects: usize,
+}
+
+impl NamespaceCount {
+    // Distinct namespaces in alphabetical order, given the namespace of each subject
+    pub fn count(namespaces: impl IntoIterator<Item = String>) -> Vec<Self> {
+        let mut counts: std::collections::BTreeMap<String, usize> = Default::default();
+        for namespace in namespaces {
+            *counts.entry(namespace).or_default() += 1;
+        }
+        counts
+            .into_iter()
+            .map(|(namespace, subjects)| Self {
+                namespace,
+                subjects,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct KeyEncodings {
+    // Key as written by the node, with its derivation code
+    pub key: String,
+    // Bytes of the public key, without the derivation code
+    pub base58: String,
+    pub base64: String,
+    pub hex: String,
+}
+
+impl KeyEncodings {
+    pub fn new(key: &str, public_key: &[u8]) -> Self {
+        Self {
+            key: key.to_owned(),
+            base58: bs58::encode(public_key).into_string(),
+            base64: base64::encode(public_key),
+            hex: public_key
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub enum AliasKind {
+    Member,
+    Subject,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ResolvedAlias {
+    pub kind: AliasKind,
+    // Subject id, or the key of a member
+    pub id: String,
+    // Governance that declares the member
+    pub governance_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SimulatedBatch {
+    // Subject as it would be after every payload is applied
+    pub subject: SubjectData,
+    // Properties left by each payload, only if requested
+    #[schema(value_type = Option<Vec<Object>>)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub states: Option<Vec<Value>>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SubjectComparison {
+    // JSON Patch that turns the properties of the first subject into those of the second
+    #[schema(value_type = Object)]
+    pub patch: Value,
+    pub warnings: Vec<String>,
+}
+
+impl SubjectComparison {
+    // Subjects are given as (schema_id, properties) pairs
+    pub fn new(a: (&str, &Value), b: (&str, &Value)) -> Self {
+        let mut warnings = Vec::new();
+        if a.0 != b.0 {
+            warnings.push(format!(
+                "Subjects have different schemas: {} and {}",
+                a.0, b.0
+            ));
+        }
+        Self {
+            patch: serde_json::to_value(json_patch::diff(a.1, b.1))
+                .expect("A JSON Patch is always serializable"),
+            warnings,
+        }
+    }
+}
+
+// Outcome of one of the votes of a batch, with the status that PUT /approvals/{id} would answer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct BatchVoteResult {
+    pub request_id: String,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_governance_version_of_latest_event() {
+        let version = GovernanceVersion::latest(vec![(0, 1), (2, 3), (1, 2)]);
+        assert_eq!(
+            version,
+            Some(GovernanceVersion {
+                governance_version: 3
+            })
+        );
+        assert_eq!(GovernanceVersion::latest(vec![]), None);
+    }
+
+    #[test]
+    fn test_last_updated_is_the_latest_event() {
+        let last = LastUpdated::latest(vec![(1, 1671706794), (2, 1671706700), (0, 1671705820)]);
+        assert_eq!(
+            last,
+            Some(LastUpdated {
+                sn: 2,
+                timestamp: 1671706700
+            })
+        );
+        assert_eq!(LastUpdated::latest(vec![]), None);
+    }
+
+    #[test]
+    fn test_single_schema_history() {
+        let history = SchemaPeriod::history((0..5).map(|sn| (sn, "Prueba".to_owned())));
+        assert_eq!(
+            history,
+            vec![SchemaPeriod {
+                schema_id: "Prueba".into(),
+                from_sn: 0,
+                to_sn: 4
+            }]
+        );
+    }
+
+    #[test]
+    fn test_changed_schema_history() {
+        let history = SchemaPeriod::history(vec![
+            (2, "Prueba2".to_owned()),
+            (0, "Prueba".to_owned()),
+            (1, "Prueba".to_owned()),
+            (3, "Prueba2".to_owned()),
+        ]);
+        let periods: Vec<(&str, u64, u64)> = history
+            .iter()
+            .map(|p| (p.schema_id.as_str(), p.from_sn, p.to_sn))
+            .collect();
+        assert_eq!(periods, vec![("Prueba", 0, 1), ("Prueba2", 2, 3)]);
+    }
+
+    #[test]
+    fn test_timeline_is_chronological() {
+        let vote = |signer: &str, acceptance: Acceptance, timestamp: i64| TimelineEntry {
+            signer: signer.into(),
+            acceptance,
+            timestamp,
+        };
+        let timeline = RequestTimeline::new(
+            "request".into(),
+            vec![
+                vote("C", Acceptance::Reject, 30),
+                vote("A", Acceptance::Accept, 10),
+                vote("B", Acceptance::Accept, 20),
+            ],
+            RequestResolution::Pending,
+        );
+        let signers: Vec<&str> = timeline.votes.iter().map(|v| v.signer.as_str()).collect();
+        assert_eq!(signers, vec!["A", "B", "C"]);
+    }
+
+    #[test]
+    fn test_signatures_of_the_quorum() {
+        let signature = |signer: &str, acceptance: Acceptance| ApprovalSignature {
+            signer: signer.into(),
+            acceptance,
+            timestamp: 10,
+            signature: format!("signature_{}", signer),
+        };
+        let approvals = vec![
+            signature("A", Acceptance::Accept),
+            signature("B", Acceptance::Reject),
+            signature("C", Acceptance::Accept),
+        ];
+        let approved = RequestSignatures::new("request".into(), true, approvals.clone());
+        assert_eq!(approved.resolution, RequestResolution::Approved);
+        let signers: Vec<&str> = approved
+            .signatures
+            .iter()
+            .map(|s| s.signer.as_str())
+            .collect();
+        assert_eq!(signers, vec!["A", "C"]);
+        assert_eq!(approved.signatures[1].signature, "signature_C");
+        let rejected = RequestSignatures::new("request".into(), false, approvals);
+        assert_eq!(rejected.resolution, RequestResolution::Rejected);
+        assert_eq!(rejected.signatures.len(), 1);
+        assert_eq!(rejected.signatures[0].signer, "B");
+    }
+
+    #[test]
+    fn test_compare_subjects_of_same_schema() {
+        let a = serde_json::json!({ "localizacion": "España", "temperatura": 10 });
+        let b = serde_json::json!({ "localizacion": "España", "temperatura": 15 });
+        let comparison = SubjectComparison::new(("Prueba", &a), ("Prueba", &b));
+        assert_eq!(
+            comparison.patch,
+            serde_json::json!([{ "op": "replace", "path": "/temperatura", "value": 15 }])
+        );
+        assert!(comparison.warnings.is_empty());
+        let comparison = SubjectComparison::new(("Prueba", &a), ("Prueba2", &a));
+        assert_eq!(comparison.patch, serde_json::json!([]));
+        assert_eq!(comparison.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_rejected_request_lists_rejecting_approvers() {
+        let rejected = RejectedRequest::new(
+            "request".into(),
+            "subject".into(),
+            3,
+            1671705820,
+            vec![
+                ("company_key".into(), Acceptance::Accept),
+                ("auditor_key".into(), Acceptance::Reject),
+            ],
+        );
+        assert_eq!(rejected.rejected_by, vec!["auditor_key".to_owned()]);
+        assert_eq!(rejected.reason, None);
+    }
+
+    #[test]
+    fn test_request_summary_omits_payload_and_signature() {
+        let summary = RequestSummary {
+            request_id: "JhEnzFVF1a-u-rH34cix2A_OXgcfesM6HGOyk7wdrGHk".into(),
+            subject_id: Some("J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0".into()),
+            schema_id: Some("governance".into()),
+            timestamp: 1671709394,
+            signer: "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w".into(),
+        };
+        let summary = serde_json::to_value(summary).unwrap();
+        let mut fields: Vec<&String> = summary.as_object().unwrap().keys().collect();
+        fields.sort();
+        assert_eq!(
+            fields,
+            vec![
+                "request_id",
+                "schema_id",
+                "signer",
+                "subject_id",
+                "timestamp"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_every_namespace_is_counted() {
+        let counts = NamespaceCount::count(
+            ["namespace1", "", "namespace2", "namespace1", "namespace1"]
+                .into_iter()
+                .map(String::from),
+        );
+        let counts: Vec<(&str, usize)> = counts
+            .iter()
+            .map(|count| (count.namespace.as_str(), count.subjects))
+            .collect();
+        assert_eq!(counts, vec![("", 1), ("namespace1", 3), ("namespace2", 1)]);
+    }
+
+    #[test]
+    fn test_key_encodings_have_the_same_bytes() {
+        let public_key: Vec<u8> = (0..32).map(|byte| byte * 7).collect();
+        let encodings = KeyEncodings::new("E", &public_key);
+        assert_eq!(
+            bs58::decode(&encodings.base58).into_vec().unwrap(),
+            public_key
+        );
+        assert_eq!(base64::decode(&encodings.base64).unwrap(), public_key);
+        let from_hex: Vec<u8> = (0..encodings.hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&encodings.hex[i..i + 2], 16).unwrap())
+            .collect();
+        assert_eq!(from_hex, public_key);
+    }
+}
@@ -0,0 +1,83 @@
+use std::cmp::Ordering;
+
+use serde_json::Value;
+
+use super::querys::SearchOperator;
+
+// Whether a property of the subject satisfies the search. Properties are stored as a JSON string,
+// and the key is a top level field or a JSON pointer into them. The value of the search is read
+// with the type of the property, so "10" matches both 10 and "10". Subjects without the property,
+// or whose property can not be compared with the value, never match
+pub fn property_matches(
+    properties: &str,
+    key: &str,
+    operator: SearchOperator,
+    value: &str,
+) -> bool {
+    let Ok(properties) = serde_json::from_str::<Value>(properties) else {
+        return false;
+    };
+    let field = if key.starts_with('/') {
+        properties.pointer(key)
+    } else {
+        properties.get(key)
+    };
+    let ordering = match field {
+        Some(Value::String(field)) => {
+            if operator == SearchOperator::Contains {
+                return field.contains(value);
+            }
+            Some(field.as_str().cmp(value))
+        }
+        Some(Value::Number(field)) => match (field.as_f64(), value.parse::<f64>()) {
+            (Some(field), Ok(value)) => field.partial_cmp(&value),
+            _ => None,
+        },
+        Some(Value::Bool(field)) => value.parse::<bool>().ok().map(|value| field.cmp(&value)),
+        _ => None,
+    };
+    let Some(ordering) = ordering else {
+        return false;
+    };
+    match operator {
+        SearchOperator::Eq => ordering == Ordering::Equal,
+        SearchOperator::Ne => ordering != Ordering::Equal,
+        SearchOperator::Lt => ordering == Ordering::Less,
+        SearchOperator::Lte => ordering != Ordering::Greater,
+        SearchOperator::Gt => ordering == Ordering::Greater,
+        SearchOperator::Gte => ordering != Ordering::Less,
+        // Only strings contain other strings
+        SearchOperator::Contains => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_properties_are_compared_with_their_type() {
+        let properties =
+            r#"{"localizacion":"España","temperatura":-2,"activo":true,"sede":{"id":"Compañía1"}}"#;
+        let search = |key: &str, operator: SearchOperator, value: &str| {
+            property_matches(properties, key, operator, value)
+        };
+        assert!(search("temperatura", SearchOperator::Lt, "0"));
+        assert!(search("temperatura", SearchOperator::Lte, "-2"));
+        assert!(search("temperatura", SearchOperator::Eq, "-2.0"));
+        assert!(search("temperatura", SearchOperator::Ne, "10"));
+        assert!(!search("temperatura", SearchOperator::Gt, "0"));
+        assert!(search("temperatura", SearchOperator::Gte, "-2"));
+        assert!(search("localizacion", SearchOperator::Eq, "España"));
+        assert!(search("localizacion", SearchOperator::Contains, "spa"));
+        assert!(search("activo", SearchOperator::Eq, "true"));
+        assert!(search("/sede/id", SearchOperator::Eq, "Compañía1"));
+        // Numbers do not contain, and values that are not numbers are not compared with them
+        assert!(!search("temperatura", SearchOperator::Contains, "2"));
+        assert!(!search("temperatura", SearchOperator::Ne, "cold"));
+        // Missing properties never match, not even for ne
+        assert!(!search("humedad", SearchOperator::Ne, "10"));
+        assert!(!search("sede", SearchOperator::Eq, "Compañía1"));
+        assert!(!property_matches("not json", "a", SearchOperator::Ne, "0"));
+    }
+}
@@ -0,0 +1,249 @@
+use std::{collections::HashMap, time::Duration};
+
+use regex::Regex;
+use serde::Deserialize;
+
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+pub const MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+pub const CBOR_CONTENT_TYPE: &str = "application/cbor";
+pub const JSON_API_CONTENT_TYPE: &str = "application/vnd.api+json";
+// Error bodies, as described in RFC 7807
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+// Content types in which responses can be sent, the first one being the default
+pub const RESPONSE_CONTENT_TYPES: [&str; 4] = [
+    JSON_CONTENT_TYPE,
+    MSGPACK_CONTENT_TYPE,
+    CBOR_CONTENT_TYPE,
+    JSON_API_CONTENT_TYPE,
+];
+pub const DEFAULT_MAX_TIMEOUT: u64 = 30;
+pub const DEFAULT_STARTUP_TIMEOUT: u64 = 60;
+pub const DEFAULT_READ_TIMEOUT: u64 = 10;
+pub const DEFAULT_READY_TIMEOUT: u64 = 2;
+pub const DEFAULT_LOG_BODIES_LIMIT: usize = 4096;
+pub const DEFAULT_DIAGNOSTICS_CAPACITY: usize = 100;
+// One year, as events never change once created
+pub const DEFAULT_EVENT_MAX_AGE: u64 = 31536000;
+// Alphanumerics, '-', '_' and '/'. The empty namespace is allowed
+pub const DEFAULT_NAMESPACE_PATTERN: &str = "^[A-Za-z0-9_/-]*$";
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RestSettings {
+    // Schemas of the subjects that clients may create. Empty means that every schema is allowed
+    #[serde(rename = "schemaallowlist", default)]
+    pub schema_allowlist: Vec<String>,
+    // Content types accepted in request bodies by route group (governances, requests, approvals).
+    // Groups that are not listed only accept JSON
+    #[serde(rename = "contenttypes", default)]
+    pub content_types: HashMap<String, Vec<String>>,
+    // Requests that may be in flight at once by route group (simulation, verification, reads,
+    // writes). Groups that are not listed are not limited
+    #[serde(rename = "concurrencylimits", default)]
+    pub concurrency_limits: HashMap<String, usize>,
+    // Content types in which responses may be sent. Empty means that every supported type is
+    // allowed
+    #[serde(rename = "responseallowlist", default)]
+    pub response_allowlist: Vec<String>,
+    // Content types in which responses are never sent, even if they are in the allowlist
+    #[serde(rename = "responsedenylist", default)]
+    pub response_denylist: Vec<String>,
+    // Seconds a request may take at most, even if the client allows more time
+    #[serde(rename = "maxtimeout", default)]
+    pub max_timeout: Option<u64>,
+    // Seconds a client may take to send the headers of a request, and then again to send its
+    // body. Slower connections are closed
+    #[serde(rename = "readtimeout", default)]
+    pub read_timeout: Option<u64>,
+    // File where the audit trail of the mutating operations is appended. Stdout if not set
+    #[serde(rename = "auditlog", default)]
+    pub audit_log: Option<String>,
+    // Regular expression that the namespace of new subjects must match
+    #[serde(rename = "namespacepattern", default)]
+    pub namespace_pattern: Option<String>,
+    // Seconds that clients may cache the responses with single events
+    #[serde(rename = "eventmaxage", default)]
+    pub event_max_age: Option<u64>,
+    // Seconds the node may take to initialize before the client exits with an error
+    #[serde(rename = "startuptimeout", default)]
+    pub startup_timeout: Option<u64>,
+    // Seconds the node may take to answer the ping of GET /ready before it is reported as not
+    // ready
+    #[serde(rename = "readytimeout", default)]
+    pub ready_timeout: Option<u64>,
+    // Whether successful responses are wrapped with server metadata unless ?meta=false is given
+    #[serde(rename = "envelope", default)]
+    pub envelope: bool,
+    // Whether the bodies of the requests to the API and of their responses are logged at debug
+    // level, with the API KEY header redacted. Only meant for debugging
+    #[serde(rename = "logbodies", default)]
+    pub log_bodies: bool,
+    // Bytes of each body that are logged at most
+    #[serde(rename = "logbodieslimit", default)]
+    pub log_bodies_limit: Option<usize>,
+    // Internal errors kept in memory to be served by GET /api/admin/diagnostics
+    #[serde(rename = "diagnosticscapacity", default)]
+    pub diagnostics_capacity: Option<usize>,
+    // Governances under which each API KEY may create subjects and events. Keys that are not
+    // listed, or that have an empty list, are not restricted. Scoped keys can not create
+    // governances, as the new one would not be in their list
+    #[serde(rename = "governancescopes", default)]
+    pub governance_scopes: HashMap<String, Vec<String>>,
+    // API KEYS accepted besides the one of the node settings, so each client can have its own
+    #[serde(rename = "apikeys", default)]
+    pub api_keys: Vec<String>,
+}
+
+impl RestSettings {
+    pub fn is_schema_allowed(&self, schema_id: &str) -> bool {
+        self.schema_allowlist.is_empty()
+            || self
+                .schema_allowlist
+                .iter()
+                .any(|allowed| allowed == schema_id)
+    }
+
+    pub fn is_governance_allowed(&self, api_key: &str, governance_id: &str) -> bool {
+        match self.governance_scopes.get(api_key) {
+            None => true,
+            Some(allowed) => {
+                allowed.is_empty() || allowed.iter().any(|allowed| allowed == governance_id)
+            }
+        }
+    }
+
+    pub fn accepted_content_types(&self, group: &str) -> Vec<String> {
+        self.content_types
+            .get(group)
+            .cloned()
+            .unwrap_or_else(|| vec![JSON_CONTENT_TYPE.to_owned()])
+    }
+
+    // Supported content types that are permitted for responses, in order of preference
+    pub fn response_content_types(&self) -> Vec<String> {
+        RESPONSE_CONTENT_TYPES
+            .iter()
+            .filter(|content_type| {
+                self.response_allowlist.is_empty()
+                    || self.response_allowlist.iter().any(|a| a == *content_type)
+            })
+            .filter(|content_type| !self.response_denylist.iter().any(|d| d == *content_type))
+            .map(|content_type| content_type.to_string())
+            .collect()
+    }
+
+    pub fn event_cache_control(&self) -> String {
+        format!(
+            "public, max-age={}, immutable",
+            self.event_max_age.unwrap_or(DEFAULT_EVENT_MAX_AGE)
+        )
+    }
+
+    pub fn namespace_pattern(&self) -> Regex {
+        let default = || Regex::new(DEFAULT_NAMESPACE_PATTERN).unwrap();
+        match &self.namespace_pattern {
+            None => default(),
+            Some(pattern) => Regex::new(pattern).unwrap_or_else(|error| {
+                log::error!(
+                    "Invalid namespace pattern, using the default one: {}",
+                    error
+                );
+                default()
+            }),
+        }
+    }
+
+    pub fn max_timeout(&self) -> Duration {
+        Duration::from_secs(self.max_timeout.unwrap_or(DEFAULT_MAX_TIMEOUT))
+    }
+
+    pub fn read_timeout(&self) -> Duration {
+        Duration::from_secs(self.read_timeout.unwrap_or(DEFAULT_READ_TIMEOUT))
+    }
+
+    pub fn log_bodies_limit(&self) -> usize {
+        self.log_bodies_limit.unwrap_or(DEFAULT_LOG_BODIES_LIMIT)
+    }
+
+    pub fn diagnostics_capacity(&self) -> usize {
+        self.diagnostics_capacity
+            .unwrap_or(DEFAULT_DIAGNOSTICS_CAPACITY)
+    }
+
+    pub fn startup_timeout(&self) -> Duration {
+        Duration::from_secs(self.startup_timeout.unwrap_or(DEFAULT_STARTUP_TIMEOUT))
+    }
+
+    pub fn ready_timeout(&self) -> Duration {
+        Duration::from_secs(self.ready_timeout.unwrap_or(DEFAULT_READY_TIMEOUT))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_schema_allowlist() {
+        let settings = RestSettings {
+            schema_allowlist: vec!["prueba".into()],
+            ..Default::default()
+        };
+        assert!(settings.is_schema_allowed("prueba"));
+        assert!(!settings.is_schema_allowed("other"));
+    }
+
+    #[test]
+    fn test_empty_schema_allowlist_allows_all() {
+        assert!(RestSettings::default().is_schema_allowed("other"));
+    }
+
+    #[test]
+    fn test_governance_scopes_per_key() {
+        let settings = RestSettings {
+            governance_scopes: HashMap::from([
+                ("tenant1".into(), vec!["governance1".into()]),
+                ("admin".into(), vec![]),
+            ]),
+            ..Default::default()
+        };
+        assert!(settings.is_governance_allowed("tenant1", "governance1"));
+        assert!(!settings.is_governance_allowed("tenant1", "governance2"));
+        // A new governance has no id yet
+        assert!(!settings.is_governance_allowed("tenant1", ""));
+        assert!(settings.is_governance_allowed("admin", "governance2"));
+        assert!(settings.is_governance_allowed("other", "governance2"));
+    }
+
+    #[test]
+    fn test_content_types_default_to_json() {
+        let settings = RestSettings {
+            content_types: HashMap::from([(
+                "requests".into(),
+                vec![JSON_CONTENT_TYPE.into(), MSGPACK_CONTENT_TYPE.into()],
+            )]),
+            ..Default::default()
+        };
+        assert_eq!(settings.accepted_content_types("requests").len(), 2);
+        assert_eq!(
+            settings.accepted_content_types("governances"),
+            vec![JSON_CONTENT_TYPE.to_owned()]
+        );
+    }
+
+    #[test]
+    fn test_response_content_types() {
+        assert_eq!(
+            RestSettings::default().response_content_types(),
+            RESPONSE_CONTENT_TYPES.to_vec()
+        );
+        let settings = RestSettings {
+            response_allowlist: vec![JSON_CONTENT_TYPE.into(), CBOR_CONTENT_TYPE.into()],
+            response_denylist: vec![CBOR_CONTENT_TYPE.into()],
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.response_content_types(),
+            vec![JSON_CONTENT_TYPE.to_owned()]
+        );
+    }
+}
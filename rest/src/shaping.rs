@@ -0,0 +1,371 @@
+use futures::{future, stream, Stream, StreamExt};
+use hyper::body::HttpBody;
+use serde::Serialize;
+use serde_json::Value;
+use warp::{
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+        StatusCode,
+    },
+    reply::Response,
+    Reply,
+};
+
+use super::{projection::project_pointers, settings::JSON_CONTENT_TYPE, stream::json_array};
+
+// Set by the listings when `from` is past their last item, so an empty page can be told apart
+// from an empty listing
+pub const OUT_OF_RANGE_HEADER: &str = "x-out-of-range";
+// Set by the events listing when some sns of the requested range are not stored in the node,
+// with the comma separated list of them
+pub const MISSING_SNS_HEADER: &str = "x-missing-sns";
+// Set by the paginated listings with the number of items of the whole listing
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResponseMeta {
+    // Unix time in milliseconds at which the response was produced
+    pub server_time: i64,
+    pub version: String,
+    // Only present, as true, in pages requested past the end of a listing
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub out_of_range: bool,
+    // Only present in pages of events with gaps, with the sns that are not stored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub missing_sns: Option<Vec<u64>>,
+    // Only present in pages of listings that report how many items they have
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
+}
+
+impl ResponseMeta {
+    pub fn now() -> Self {
+        Self {
+            server_time: chrono::Utc::now().timestamp_millis(),
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            out_of_range: false,
+            missing_sns: None,
+            total: None,
+        }
+    }
+}
+
+// Sns from `first` to `last`, both included, that are not in the page
+pub fn missing_sns(page: impl IntoIterator<Item = u64>, first: u64, last: u64) -> Vec<u64> {
+    let page: Vec<u64> = page.into_iter().collect();
+    (first..=last).filter(|sn| !page.contains(sn)).collect()
+}
+
+#[derive(Debug, Serialize)]
+struct Envelope {
+    data: Value,
+    meta: ResponseMeta,
+}
+
+// Shortens the properties of the items of a list to `limit` characters, ending them with an
+// ellipsis and marking the items as truncated. Single items are left whole
+pub fn truncate_properties(data: &mut Value, limit: usize) {
+    let Value::Array(items) = data else {
+        return;
+    };
+    for item in items.iter_mut() {
+        truncate_item(item, limit);
+    }
+}
+
+fn truncate_item(item: &mut Value, limit: usize) {
+    let Some(item) = item.as_object_mut() else {
+        return;
+    };
+    let Some(Value::String(properties)) = item.get_mut("properties") else {
+        return;
+    };
+    let Some((end, _)) = properties.char_indices().nth(limit) else {
+        return;
+    };
+    properties.truncate(end);
+    properties.push('…');
+    item.insert("truncated".to_owned(), Value::Bool(true));
+}
+
+// Shaping requested for a listing that streams its items. Streamed responses are not shaped
+// after being serialized, so their handlers shape each item as it is written
+#[derive(Debug, Clone, Default)]
+pub struct Shaping {
+    pub pointers: Option<Vec<String>>,
+    pub truncate: Option<usize>,
+    pub enveloped: bool,
+    // Negotiated content type of the response
+    pub content_type: String,
+}
+
+impl Shaping {
+    // Only JSON is written item by item. Listings in other content types are answered whole, so
+    // they are shaped and encoded as any other response
+    pub fn streams(&self) -> bool {
+        self.content_type == JSON_CONTENT_TYPE
+    }
+
+    // Identifies the representation the response is sent in. Validators of a response, as its
+    // ETag, only hold for the same representation
+    pub fn representation(&self) -> String {
+        format!(
+            "{};fields={};truncate={:?};meta={}",
+            self.content_type,
+            self.pointers.as_deref().unwrap_or_default().join(","),
+            self.truncate,
+            self.enveloped
+        )
+    }
+
+    // Item of a list with its properties truncated and then projected, as `shape` does with
+    // whole lists
+    pub fn item(&self, mut item: Value) -> Value {
+        if let Some(limit) = self.truncate {
+            truncate_item(&mut item, limit);
+        }
+        match &self.pointers {
+            Some(pointers) => project_pointers(&item, pointers),
+            None => item,
+        }
+    }
+
+    // Chunks of a streamed listing: the JSON array of the shaped items, wrapped in the envelope
+    // when requested. Streamed listings do not know their total, so it is never in the metadata
+    pub fn body<E>(
+        self,
+        items: impl Stream<Item = Result<Value, E>>,
+    ) -> impl Stream<Item = Result<Vec<u8>, E>> {
+        let enveloped = self.enveloped;
+        let array = json_array(items.map(move |item| item.map(|item| self.item(item))));
+        if !enveloped {
+            return array.left_stream();
+        }
+        let meta = serde_json::to_vec(&ResponseMeta::now()).expect("Metadata is serializable");
+        let suffix = [b",\"meta\":".as_slice(), &meta, b"}"].concat();
+        stream::once(future::ready(Ok(b"{\"data\":".to_vec())))
+            .chain(array)
+            .chain(stream::once(future::ready(Ok(suffix))))
+            .right_stream()
+    }
+}
+
+// Post-serialization shaping shared by every API route. The properties of lists are truncated
+// first, then the JSON Pointers of `?fields=` are applied to the payload and the envelope, if
+// requested, wraps the projected payload. Event field names in `?fields=` are handled before
+// this by the event endpoints themselves. Errors and streamed responses are left bare, the
+// latter because they would have to be buffered whole; their handlers shape them with `Shaping`.
+pub async fn shape(
+    reply: impl Reply,
+    pointers: Option<Vec<String>>,
+    truncate: Option<usize>,
+    enveloped: bool,
+) -> Response {
+    let response = reply.into_response();
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .map(|value| value.as_bytes().starts_with(JSON_CONTENT_TYPE.as_bytes()))
+        .unwrap_or(false);
+    if (pointers.is_none() && truncate.is_none() && !enveloped)
+        || !response.status().is_success()
+        || !is_json
+        || response.body().size_hint().exact().is_none()
+    {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(error) => {
+            log::error!("Response body could not be read: {}", error);
+            let mut response = Response::new(String::from("Internal Server Error").into());
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            return response;
+        }
+    };
+    let mut data = match serde_json::from_slice::<Value>(&body) {
+        Ok(data) => data,
+        Err(_) => return Response::from_parts(parts, body.into()),
+    };
+    if let Some(limit) = truncate {
+        truncate_properties(&mut data, limit);
+    }
+    if let Some(pointers) = pointers {
+        data = project_pointers(&data, &pointers);
+    }
+    let body = if enveloped {
+        let mut meta = ResponseMeta::now();
+        meta.out_of_range = parts.headers.contains_key(OUT_OF_RANGE_HEADER);
+        meta.missing_sns = parts
+            .headers
+            .get(MISSING_SNS_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.split(',').filter_map(|sn| sn.parse().ok()).collect());
+        meta.total = parts
+            .headers
+            .get(TOTAL_COUNT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok());
+        serde_json::to_vec(&Envelope { data, meta })
+    } else {
+        serde_json::to_vec(&data)
+    };
+    parts.headers.remove(CONTENT_LENGTH);
+    let body = body.expect("JSON values are always serializable");
+    Response::from_parts(parts, body.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use warp::http::HeaderValue;
+
+    fn json_reply(body: &Value) -> Response {
+        let mut response = Response::new(serde_json::to_vec(body).unwrap().into());
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static(JSON_CONTENT_TYPE));
+        response
+    }
+
+    async fn body_of(response: Response) -> Value {
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[test]
+    fn test_enveloped_and_bare_responses() {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                let subjects = serde_json::json!([{ "subject_id": "subject1", "sn": 0 }]);
+                let bare = body_of(shape(json_reply(&subjects), None, None, false).await).await;
+                assert_eq!(bare, subjects);
+                let enveloped = body_of(shape(json_reply(&subjects), None, None, true).await).await;
+                assert_eq!(enveloped["data"], bare);
+                assert!(enveloped["meta"]["server_time"].as_i64().unwrap() > 0);
+                assert_eq!(enveloped["meta"]["version"], env!("CARGO_PKG_VERSION"));
+                // Errors keep their own body
+                let not_found = shape(StatusCode::NOT_FOUND, None, None, true).await;
+                assert_eq!(not_found.status(), StatusCode::NOT_FOUND);
+                assert!(hyper::body::to_bytes(not_found.into_body())
+                    .await
+                    .unwrap()
+                    .is_empty());
+            });
+    }
+    #[test]
+    fn test_pages_past_the_end_are_flagged() {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                let page = serde_json::json!([]);
+                let mut past_the_end = json_reply(&page);
+                past_the_end
+                    .headers_mut()
+                    .insert(OUT_OF_RANGE_HEADER, HeaderValue::from_static("true"));
+                let response = shape(past_the_end, None, None, false).await;
+                assert!(response.headers().contains_key(OUT_OF_RANGE_HEADER));
+                assert_eq!(body_of(response).await, page);
+                let mut past_the_end = json_reply(&page);
+                past_the_end
+                    .headers_mut()
+                    .insert(OUT_OF_RANGE_HEADER, HeaderValue::from_static("true"));
+                past_the_end
+                    .headers_mut()
+                    .insert(TOTAL_COUNT_HEADER, HeaderValue::from_static("3"));
+                let enveloped = body_of(shape(past_the_end, None, None, true).await).await;
+                assert_eq!(enveloped["meta"]["out_of_range"], true);
+                assert_eq!(enveloped["meta"]["total"], 3);
+                // An empty listing is not out of range
+                let enveloped = body_of(shape(json_reply(&page), None, None, true).await).await;
+                assert!(enveloped["meta"].get("out_of_range").is_none());
+                assert!(enveloped["meta"].get("total").is_none());
+            });
+    }
+
+    #[test]
+    fn test_gaps_in_a_page_of_events() {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                // Events 2 and 3 of the subject were pruned
+                let stored = [0, 1, 4, 5];
+                let missing = missing_sns(stored, 1, 5);
+                assert_eq!(missing, vec![2, 3]);
+                assert!(missing_sns(stored, 4, 5).is_empty());
+                let page = serde_json::json!([{ "sn": 1 }, { "sn": 4 }, { "sn": 5 }]);
+                let mut with_gaps = json_reply(&page);
+                with_gaps
+                    .headers_mut()
+                    .insert(MISSING_SNS_HEADER, HeaderValue::from_static("2,3"));
+                let enveloped = body_of(shape(with_gaps, None, None, true).await).await;
+                assert_eq!(enveloped["data"], page);
+                assert_eq!(enveloped["meta"]["missing_sns"], serde_json::json!([2, 3]));
+                let enveloped = body_of(shape(json_reply(&page), None, None, true).await).await;
+                assert!(enveloped["meta"].get("missing_sns").is_none());
+            });
+    }
+
+    #[test]
+    fn test_properties_of_lists_are_truncated_on_request() {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                let subjects = serde_json::json!([
+                    { "subject_id": "subject1", "properties": "{\"localizacion\":\"España\"}" },
+                    { "subject_id": "subject2", "properties": "{}" }
+                ]);
+                let whole = body_of(shape(json_reply(&subjects), None, None, false).await).await;
+                assert_eq!(whole, subjects);
+                let truncated =
+                    body_of(shape(json_reply(&subjects), None, Some(20), false).await).await;
+                assert_eq!(truncated[0]["properties"], "{\"localizacion\":\"Esp…");
+                assert_eq!(truncated[0]["truncated"], true);
+                assert_eq!(truncated[1], subjects[1]);
+                // Single items are not lists
+                let subject =
+                    body_of(shape(json_reply(&subjects[0]), None, Some(20), false).await).await;
+                assert_eq!(subject, subjects[0]);
+            });
+    }
+
+    #[test]
+    fn test_streamed_listings_are_shaped_item_by_item() {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                let subjects = serde_json::json!([
+                    { "subject_id": "subject1", "properties": "{\"localizacion\":\"España\"}" },
+                    { "subject_id": "subject2", "properties": "{}" }
+                ]);
+                let streamed = |shaping: Shaping| {
+                    let items = subjects.as_array().unwrap().clone();
+                    let items = stream::iter(items.into_iter().map(Ok::<_, ()>));
+                    async move {
+                        let chunks: Vec<Vec<u8>> =
+                            shaping.body(items).map(Result::unwrap).collect().await;
+                        serde_json::from_slice::<Value>(&chunks.concat()).unwrap()
+                    }
+                };
+                assert_eq!(streamed(Shaping::default()).await, subjects);
+                let shaping = Shaping {
+                    pointers: Some(vec!["/properties".into(), "/truncated".into()]),
+                    truncate: Some(20),
+                    enveloped: true,
+                    content_type: JSON_CONTENT_TYPE.to_owned(),
+                };
+                let enveloped = streamed(shaping).await;
+                assert_eq!(
+                    enveloped["data"],
+                    serde_json::json!([
+                        { "properties": "{\"localizacion\":\"Esp…", "truncated": true },
+                        { "properties": "{}" }
+                    ])
+                );
+                assert_eq!(enveloped["meta"]["version"], env!("CARGO_PKG_VERSION"));
+                assert!(enveloped["meta"].get("total").is_none());
+            });
+    }
+}
@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+// Full state and chain of a subject, with a checksum of its contents to detect bundles damaged in
+// transit. The checksum is not keyed, anyone who edits the bundle can recompute it, and the hashes
+// of the events can not be recomputed here, so an edited event content that keeps its declared
+// hashes is not detected. Only the signatures of the events, checked by the node, prove who wrote
+// them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SubjectSnapshot {
+    #[schema(value_type = Object)]
+    pub subject: Value,
+    // Every event of the subject from the genesis one, with their signatures and approvals
+    #[schema(value_type = Vec<Object>)]
+    pub events: Vec<Value>,
+    pub checksum: String,
+}
+
+impl SubjectSnapshot {
+    pub fn new(subject: Value, events: Vec<Value>) -> Self {
+        let checksum = checksum(&subject, &events);
+        Self {
+            subject,
+            events,
+            checksum,
+        }
+    }
+
+    // Checks that the bundle matches its checksum and that its chain is complete and consistent:
+    // every event belongs to the subject and points to the hash of the previous one
+    pub fn verify(&self) -> Result<(), String> {
+        if self.checksum != checksum(&self.subject, &self.events) {
+            return Err(String::from(
+                "The snapshot checksum does not match its contents",
+            ));
+        }
+        let subject_id = self.subject.get("subject_id");
+        // The genesis event has no previous one
+        let mut previous_hash = Some(&Value::String(String::new()));
+        for (expected, event) in self.events.iter().enumerate() {
+            let sn = event.pointer("/event_content/sn").and_then(Value::as_u64);
+            if sn != Some(expected as u64) {
+                return Err(format!("Event {} is missing from the snapshot", expected));
+            }
+            if event.pointer("/event_content/subject_id") != subject_id {
+                return Err(format!("Event {} belongs to another subject", expected));
+            }
+            if event.pointer("/event_content/previous_hash") != previous_hash {
+                return Err(format!(
+                    "Event {} does not follow the previous event",
+                    expected
+                ));
+            }
+            previous_hash = event.pointer("/signature/content/event_content_hash");
+        }
+        let last_sn = self.events.len().checked_sub(1).map(|sn| sn as u64);
+        if self.subject.get("sn").and_then(Value::as_u64) != last_sn {
+            return Err(String::from("The subject sn does not match its last event"));
+        }
+        Ok(())
+    }
+}
+
+fn checksum(subject: &Value, events: &[Value]) -> String {
+    let mut hasher = Sha256::new();
+    // Values are written with their keys sorted, so the checksum does not depend on field order
+    hasher.update(serde_json::to_vec(subject).expect("JSON values are always serializable"));
+    for event in events {
+        hasher.update(serde_json::to_vec(event).expect("JSON values are always serializable"));
+    }
+    base64::encode_config(hasher.finalize(), base64::URL_SAFE_NO_PAD)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event(sn: u64, temperature: u64) -> Value {
+        let previous_hash = match sn {
+            0 => String::new(),
+            sn => format!("J{}", sn - 1),
+        };
+        serde_json::json!({
+            "event_content": {
+                "subject_id": "J1",
+                "sn": sn,
+                "previous_hash": previous_hash,
+                "state_hash": format!("J{}", temperature),
+                "approved": true
+            },
+            "signature": {
+                "content": { "signer": "E1", "event_content_hash": format!("J{}", sn) },
+                "signature": "SE1"
+            }
+        })
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let subject = serde_json::json!({ "subject_id": "J1", "sn": 1, "properties": "{}" });
+        let snapshot = SubjectSnapshot::new(subject, vec![event(0, 10), event(1, 15)]);
+        let exported = serde_json::to_string(&snapshot).unwrap();
+        let imported: SubjectSnapshot = serde_json::from_str(&exported).unwrap();
+        assert_eq!(imported, snapshot);
+        assert_eq!(imported.verify(), Ok(()));
+
+        let mut damaged = imported.clone();
+        damaged.events[1]["event_content"]["state_hash"] = "J20".into();
+        assert!(damaged.verify().is_err());
+        let mut truncated = imported;
+        truncated.events.pop();
+        truncated.checksum = checksum(&truncated.subject, &truncated.events);
+        assert!(truncated.verify().is_err());
+    }
+
+    #[test]
+    fn test_inconsistent_chain_is_rejected() {
+        let subject = serde_json::json!({ "subject_id": "J1", "sn": 1, "properties": "{}" });
+        let snapshot = SubjectSnapshot::new(subject, vec![event(0, 10), event(1, 15)]);
+        // Modified events are rejected even with a recomputed checksum
+        let mut unlinked = snapshot.clone();
+        unlinked.events[1]["event_content"]["previous_hash"] = "J7".into();
+        unlinked.checksum = checksum(&unlinked.subject, &unlinked.events);
+        assert_eq!(
+            unlinked.verify(),
+            Err(String::from("Event 1 does not follow the previous event"))
+        );
+        let mut foreign = snapshot;
+        foreign.events[1]["event_content"]["subject_id"] = "J2".into();
+        foreign.checksum = checksum(&foreign.subject, &foreign.events);
+        assert_eq!(
+            foreign.verify(),
+            Err(String::from("Event 1 belongs to another subject"))
+        );
+    }
+}
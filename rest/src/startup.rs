@@ -0,0 +1,153 @@
+use std::{
+    fmt::Display,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use super::{
+    error::Error,
+    responses::{HealthStatus, NodeStatus},
+};
+
+// Seconds clients are told to wait while the node is initializing
+pub const STARTUP_RETRY_AFTER: u64 = 5;
+
+// Whether the node has finished its initialization. The server starts accepting connections
+// before that, answering the API requests with 503 until the node is ready.
+#[derive(Debug, Clone, Default)]
+pub struct Startup {
+    ready: Arc<AtomicBool>,
+}
+
+impl Startup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_ready(&self) {
+        self.ready.store(true, Ordering::SeqCst);
+    }
+
+    pub fn status(&self) -> HealthStatus {
+        HealthStatus {
+            status: if self.ready.load(Ordering::SeqCst) {
+                NodeStatus::Ready
+            } else {
+                NodeStatus::Starting
+            },
+        }
+    }
+
+    pub fn check_ready(&self) -> Result<(), Error> {
+        match self.status().status {
+            NodeStatus::Ready => Ok(()),
+            _ => Err(Error::Initializing(STARTUP_RETRY_AFTER)),
+        }
+    }
+
+    // Whether the node can serve requests: it has finished its initialization and answers the
+    // ping within the timeout
+    pub async fn readiness<T, E>(
+        &self,
+        timeout: Duration,
+        ping: impl Future<Output = Result<T, E>>,
+    ) -> HealthStatus
+    where
+        E: Display,
+    {
+        if self.check_ready().is_err() {
+            return self.status();
+        }
+        let status = match tokio::time::timeout(timeout, ping).await {
+            Ok(Ok(_)) => NodeStatus::Ready,
+            Ok(Err(error)) => {
+                log::warn!("Node is not ready: {}", error);
+                NodeStatus::NotReady
+            }
+            Err(_) => {
+                log::warn!("Node did not answer within {} seconds", timeout.as_secs());
+                NodeStatus::NotReady
+            }
+        };
+        HealthStatus { status }
+    }
+}
+
+// Waits for the node initialization, failing if it does not finish within the timeout
+pub async fn wait_initialization<T, E>(
+    timeout: Duration,
+    initialization: impl Future<Output = Result<T, E>>,
+) -> Result<T, String>
+where
+    E: Display,
+{
+    match tokio::time::timeout(timeout, initialization).await {
+        Ok(Ok(result)) => Ok(result),
+        Ok(Err(error)) => Err(format!("Node initialization failed: {}", error)),
+        Err(_) => Err(format!(
+            "Node not ready after {} seconds",
+            timeout.as_secs()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_slow_initialization_times_out() {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                let slow = async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    Ok::<_, String>(())
+                };
+                let result = wait_initialization(Duration::from_millis(50), slow).await;
+                assert_eq!(result, Err(String::from("Node not ready after 0 seconds")));
+                let failed = async { Err::<(), _>("Database not available") };
+                let result = wait_initialization(Duration::from_millis(50), failed).await;
+                assert!(result.is_err());
+                let fast = async { Ok::<_, String>(()) };
+                let result = wait_initialization(Duration::from_millis(50), fast).await;
+                assert!(result.is_ok());
+            });
+    }
+
+    #[test]
+    fn test_readiness_pings_the_node() {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                let timeout = Duration::from_millis(50);
+                let startup = Startup::new();
+                let status = startup
+                    .readiness(timeout, async { Ok::<_, String>(()) })
+                    .await;
+                assert_eq!(status.status, NodeStatus::Starting);
+                startup.set_ready();
+                let status = startup
+                    .readiness(timeout, async { Ok::<_, String>(()) })
+                    .await;
+                assert_eq!(status.status, NodeStatus::Ready);
+                let failed = async { Err::<(), _>("Database not available") };
+                let status = startup.readiness(timeout, failed).await;
+                assert_eq!(status.status, NodeStatus::NotReady);
+                let slow = async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    Ok::<_, String>(())
+                };
+                let status = startup.readiness(timeout, slow).await;
+                assert_eq!(status.status, NodeStatus::NotReady);
+                assert_eq!(
+                    serde_json::to_string(&status).unwrap(),
+                    "{\"status\":\"not ready\"}"
+                );
+            });
+    }
+}
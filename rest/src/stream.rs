@@ -0,0 +1,337 @@
+use std::{collections::VecDeque, future::Future, time::Duration};
+
+use commons::models::event::Event;
+use core::{ApiModuleInterface, NodeAPI};
+use futures::{future, stream, stream::BoxStream, Stream, StreamExt, TryStreamExt};
+use serde::Serialize;
+
+// Interval used to check for new events once the history has been replayed
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// Interval of the keep-alive comments of the Server-Sent Events streams without new events
+pub const SSE_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+// Maximum number of events requested to the node in each fetch
+pub const FETCH_BATCH: u64 = 100;
+// Maximum number of items requested to the node in each fetch of a listing
+pub const LISTING_BATCH: usize = 100;
+
+// Events of a subject from the given sn onwards. Historical events are replayed first and then
+// the node is polled for new ones, so both phases share the same sn tracking and the stream
+// never skips nor repeats an sn at the boundary.
+pub fn subject_events(node: NodeAPI, subject_id: String, from: u64) -> impl Stream<Item = Event> {
+    contiguous_stream(
+        from,
+        POLL_INTERVAL,
+        move |sn| {
+            let node = node.clone();
+            let subject_id = subject_id.clone();
+            async move {
+                match node
+                    .get_event_of_subject(subject_id, Some(sn as i64), Some(FETCH_BATCH as i64))
+                    .await
+                {
+                    Ok(events) => Some(events),
+                    Err(error) => {
+                        log::warn!("Event stream stopped: {:?}", error);
+                        None
+                    }
+                }
+            }
+        },
+        |event: &Event| event.event_content.sn,
+    )
+}
+
+// Generic stream of items with consecutive sns. `fetch` receives the next expected sn and
+// returns the items from it onwards, or None to finish the stream.
+pub fn contiguous_stream<T, F, Fut>(
+    from: u64,
+    poll_interval: Duration,
+    fetch: F,
+    sn_of: fn(&T) -> u64,
+) -> impl Stream<Item = T>
+where
+    F: Fn(u64) -> Fut,
+    Fut: Future<Output = Option<Vec<T>>>,
+{
+    let state = (from, VecDeque::new(), fetch);
+    stream::unfold(state, move |(next_sn, mut buffer, fetch)| async move {
+        loop {
+            if let Some(item) = buffer.pop_front() {
+                return Some((item, (next_sn + 1, buffer, fetch)));
+            }
+            let items = fetch(next_sn).await?;
+            buffer = take_contiguous(next_sn, items, sn_of);
+            if buffer.is_empty() {
+                tokio::time::sleep(poll_interval).await;
+            }
+        }
+    })
+}
+
+// Items of a paged listing from the given position. Items are fetched in batches and the next
+// batch is only requested once the previous one has been consumed, so at most one batch is
+// held in memory. `fetch` receives the position and the number of items wanted; a shorter
+// batch ends the listing.
+pub fn paged_stream<T, E, F, Fut>(
+    from: usize,
+    quantity: Option<usize>,
+    batch: usize,
+    fetch: F,
+) -> impl Stream<Item = Result<T, E>>
+where
+    F: Fn(usize, usize) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, E>>,
+{
+    let state = (from, quantity, VecDeque::new(), false, fetch);
+    stream::unfold(
+        state,
+        move |(mut next, mut remaining, mut buffer, mut exhausted, fetch)| async move {
+            loop {
+                if let Some(item) = buffer.pop_front() {
+                    return Some((Ok(item), (next, remaining, buffer, exhausted, fetch)));
+                }
+                if exhausted || remaining == Some(0) {
+                    return None;
+                }
+                let size = remaining.map_or(batch, |remaining| remaining.min(batch));
+                match fetch(next, size).await {
+                    Ok(mut items) => {
+                        items.truncate(size);
+                        exhausted = items.len() < size;
+                        next += items.len();
+                        remaining = remaining.map(|remaining| remaining - items.len());
+                        buffer = items.into();
+                    }
+                    Err(error) => {
+                        return Some((Err(error), (next, remaining, buffer, true, fetch)))
+                    }
+                }
+            }
+        },
+    )
+}
+
+// Page of a listing that is filtered after being fetched, so the node can not skip the items
+// before `from`. Errors are never skipped, they end the page
+pub fn page_of<T, E>(
+    items: impl Stream<Item = Result<T, E>>,
+    from: usize,
+    quantity: Option<usize>,
+) -> impl Stream<Item = Result<T, E>> {
+    items
+        .scan(0, move |position, item| {
+            let kept = item.is_err() || {
+                *position += 1;
+                *position > from
+            };
+            future::ready(Some((kept, item)))
+        })
+        .filter_map(|(kept, item)| future::ready(kept.then_some(item)))
+        .take(quantity.unwrap_or(usize::MAX))
+}
+
+// Page of a listing, optionally filtered, and the number of items of the whole listing when
+// `count` is requested. The node can not count its items, so counting reads the whole listing
+// once and the page is kept in memory; otherwise only the batches of the page are fetched, and
+// lazily
+pub async fn listing_page<T, E, F, Fut, M>(
+    fetch: F,
+    matches: Option<M>,
+    from: usize,
+    quantity: Option<usize>,
+    count: bool,
+) -> Result<(BoxStream<'static, Result<T, E>>, Option<usize>), E>
+where
+    T: Send + 'static,
+    E: Send + 'static,
+    F: Fn(usize, usize) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Vec<T>, E>> + Send + 'static,
+    M: Fn(&T) -> bool + Send + 'static,
+{
+    if !count {
+        let page = match matches {
+            None => paged_stream(from, quantity, LISTING_BATCH, fetch).boxed(),
+            Some(matches) => {
+                let items = paged_stream(0, None, LISTING_BATCH, fetch)
+                    .try_filter(move |item| future::ready(matches(item)));
+                page_of(items, from, quantity).boxed()
+            }
+        };
+        return Ok((page, None));
+    }
+    let end = quantity.map_or(usize::MAX, |quantity| from.saturating_add(quantity));
+    let mut items = paged_stream(0, None, LISTING_BATCH, fetch).boxed();
+    let mut page = Vec::new();
+    let mut total = 0;
+    while let Some(item) = items.try_next().await? {
+        if matches.as_ref().map_or(true, |matches| matches(&item)) {
+            if total >= from && total < end {
+                page.push(item);
+            }
+            total += 1;
+        }
+    }
+    Ok((stream::iter(page.into_iter().map(Ok)).boxed(), Some(total)))
+}
+
+// Serializes a stream of items as the chunks of a JSON array
+pub fn json_array<T: Serialize, E>(
+    items: impl Stream<Item = Result<T, E>>,
+) -> impl Stream<Item = Result<Vec<u8>, E>> {
+    let items = items.enumerate().map(|(index, item)| {
+        let item = serde_json::to_vec(&item?).unwrap_or_else(|_| b"null".to_vec());
+        if index == 0 {
+            Ok(item)
+        } else {
+            Ok([b",".as_slice(), &item].concat())
+        }
+    });
+    stream::once(future::ready(Ok(b"[".to_vec())))
+        .chain(items)
+        .chain(stream::once(future::ready(Ok(b"]".to_vec()))))
+}
+
+// Keeps the items that continue the sequence at `next_sn`, discarding repeated ones and
+// everything after a gap
+fn take_contiguous<T>(mut next_sn: u64, items: Vec<T>, sn_of: fn(&T) -> u64) -> VecDeque<T> {
+    let mut contiguous = VecDeque::new();
+    for item in items {
+        let sn = sn_of(&item);
+        if sn < next_sn {
+            continue;
+        }
+        if sn > next_sn {
+            break;
+        }
+        contiguous.push_back(item);
+        next_sn += 1;
+    }
+    contiguous
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_no_gap_between_history_and_live_events() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            // History, then overlapping and out of order live responses
+            let responses = Arc::new(Mutex::new(VecDeque::from(vec![
+                vec![0u64, 1, 2],
+                vec![2, 3],
+                vec![],
+                vec![5],
+                vec![4, 5, 6],
+            ])));
+            let fetch = move |_sn: u64| {
+                let responses = responses.clone();
+                async move { responses.lock().unwrap().pop_front() }
+            };
+            let sns: Vec<u64> = contiguous_stream(0, Duration::ZERO, fetch, |sn| *sn)
+                .collect()
+                .await;
+            assert_eq!(sns, vec![0, 1, 2, 3, 4, 5, 6]);
+        });
+    }
+
+    #[test]
+    fn test_large_listing_is_fetched_in_batches() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let fetch = {
+                let requests = requests.clone();
+                move |from: usize, quantity: usize| {
+                    requests.lock().unwrap().push(quantity);
+                    let items: Vec<usize> = (from..(from + quantity).min(1050)).collect();
+                    async move { Ok::<_, ()>(items) }
+                }
+            };
+            let chunks: Vec<Vec<u8>> = json_array(paged_stream(0, None, LISTING_BATCH, fetch))
+                .map(Result::unwrap)
+                .collect()
+                .await;
+            let listing: Vec<usize> = serde_json::from_slice(&chunks.concat()).unwrap();
+            assert_eq!(listing, (0..1050).collect::<Vec<usize>>());
+            let requests = requests.lock().unwrap();
+            assert_eq!(requests.len(), 11);
+            assert!(requests.iter().all(|quantity| *quantity <= LISTING_BATCH));
+        });
+    }
+
+    #[test]
+    fn test_listing_honors_from_and_quantity() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let fetch = |from: usize, quantity: usize| async move {
+                Ok::<_, ()>((from..from + quantity).collect::<Vec<usize>>())
+            };
+            let chunks: Vec<Vec<u8>> =
+                json_array(paged_stream(10, Some(250), LISTING_BATCH, fetch))
+                    .map(Result::unwrap)
+                    .collect()
+                    .await;
+            let listing: Vec<usize> = serde_json::from_slice(&chunks.concat()).unwrap();
+            assert_eq!(listing, (10..260).collect::<Vec<usize>>());
+            let empty: Vec<Vec<u8>> = json_array(paged_stream(0, Some(0), LISTING_BATCH, fetch))
+                .map(Result::unwrap)
+                .collect()
+                .await;
+            assert_eq!(empty.concat(), b"[]".to_vec());
+        });
+    }
+
+    #[test]
+    fn test_filtered_listing_is_paged_after_filtering() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let fetch = |from: usize, quantity: usize| async move {
+                Ok::<_, ()>((from..(from + quantity).min(300)).collect::<Vec<usize>>())
+            };
+            let even = paged_stream(0, None, LISTING_BATCH, fetch)
+                .try_filter(|item| future::ready(item % 2 == 0));
+            let page: Vec<Result<usize, ()>> = page_of(even, 5, Some(3)).collect().await;
+            assert_eq!(page, vec![Ok(10), Ok(12), Ok(14)]);
+            // A failure before the page is reported instead of being skipped
+            let failing = stream::iter(vec![Ok(0), Err(()), Ok(2)]);
+            let page: Vec<Result<usize, ()>> = page_of(failing, 2, None).collect().await;
+            assert_eq!(page, vec![Err(()), Ok(2)]);
+        });
+    }
+
+    #[test]
+    fn test_only_counted_pages_read_the_whole_listing() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let requests = Arc::new(Mutex::new(0));
+            let fetch = {
+                let requests = requests.clone();
+                move |from: usize, quantity: usize| {
+                    *requests.lock().unwrap() += 1;
+                    let items: Vec<usize> = (from..(from + quantity).min(1050)).collect();
+                    async move { Ok::<_, ()>(items) }
+                }
+            };
+            let no_filter = None::<fn(&usize) -> bool>;
+            let (page, total) = listing_page(fetch.clone(), no_filter, 20, Some(10), false)
+                .await
+                .unwrap();
+            let page: Vec<usize> = page.try_collect().await.unwrap();
+            assert_eq!(page, (20..30).collect::<Vec<usize>>());
+            assert_eq!(total, None);
+            assert_eq!(*requests.lock().unwrap(), 1);
+
+            // Counted pages read every batch once, also when filtered
+            *requests.lock().unwrap() = 0;
+            let even = Some(|item: &usize| item % 2 == 0);
+            let (page, total) = listing_page(fetch, even, 5, Some(3), true).await.unwrap();
+            let page: Vec<usize> = page.try_collect().await.unwrap();
+            assert_eq!(page, vec![10, 12, 14]);
+            assert_eq!(total, Some(525));
+            assert_eq!(*requests.lock().unwrap(), 11);
+        });
+    }
+}
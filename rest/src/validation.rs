@@ -0,0 +1,483 @@
+use std::{collections::HashMap, str::FromStr};
+
+use commons::identifier::{Derivable, DigestIdentifier, KeyIdentifier};
+use core::SignatureRequest;
+use regex::Regex;
+use serde_json::Value;
+
+use super::{
+    bodys::{EventRequestTypeBody, Payload, PostEventRequestBody, PostSubjectBody},
+    error::FieldError,
+    governance::{find_schema, validate_properties},
+    responses::ValidationCheck,
+    settings::RestSettings,
+};
+
+// Schema id reserved by the node for governance subjects
+pub const GOVERNANCE_SCHEMA_ID: &str = "governance";
+// Seconds that the timestamp of an external request may differ from the node clock
+pub const TIMESTAMP_WINDOW: i64 = 300;
+
+pub fn validate_create_request(
+    governance_id: &str,
+    schema_id: &str,
+    payload: &Payload,
+) -> Result<(), Vec<FieldError>> {
+    if schema_id == GOVERNANCE_SCHEMA_ID {
+        return validate_governance_payload(payload);
+    }
+    let mut errors = Vec::new();
+    if governance_id.is_empty() {
+        errors.push(FieldError::new("governance_id", "must not be empty"));
+    }
+    if schema_id.is_empty() {
+        errors.push(FieldError::new("schema_id", "must not be empty"));
+    }
+    match payload {
+        Payload::Json(Value::Object(_)) => {}
+        Payload::Json(_) => errors.push(FieldError::new("payload", "must be a JSON object")),
+        Payload::JsonPatch(_) => errors.push(FieldError::new(
+            "payload",
+            "the initial state of a subject can not be a JSON Patch",
+        )),
+    }
+    into_result(errors)
+}
+
+pub fn validate_namespace(namespace: &str, pattern: &Regex) -> Result<(), Vec<FieldError>> {
+    if pattern.is_match(namespace) {
+        Ok(())
+    } else {
+        Err(vec![FieldError::new(
+            "namespace",
+            &format!("must match the pattern {}", pattern.as_str()),
+        )])
+    }
+}
+
+// Identifiers must be in the encoding produced by the node, a derivation code followed by URL
+// safe base64 without padding. Anything that decodes but is written differently is rejected, so
+// the same subject or request can not be referenced by two different ids.
+pub fn validate_identifier(field: &str, id: &str) -> Result<(), Vec<FieldError>> {
+    match DigestIdentifier::from_str(id) {
+        Ok(identifier) if identifier.to_str() == id => Ok(()),
+        _ => Err(vec![FieldError::new(
+            field,
+            "must be an identifier in URL safe base64 with its derivation code",
+        )]),
+    }
+}
+
+// Public keys follow the same encoding as the identifiers
+pub fn validate_key(field: &str, key: &str) -> Result<KeyIdentifier, Vec<FieldError>> {
+    match KeyIdentifier::from_str(key) {
+        Ok(identifier) if identifier.to_str() == key => Ok(identifier),
+        _ => Err(vec![FieldError::new(
+            field,
+            "must be a public key in URL safe base64 with its derivation code",
+        )]),
+    }
+}
+
+pub fn validate_governance_payload(payload: &Payload) -> Result<(), Vec<FieldError>> {
+    let Payload::Json(governance) = payload else {
+        return Err(vec![FieldError::new(
+            "payload",
+            "a governance must be specified as a JSON object",
+        )]);
+    };
+    validate_governance(governance)
+}
+
+pub fn validate_governance(governance: &Value) -> Result<(), Vec<FieldError>> {
+    let mut errors = Vec::new();
+    match governance.get("members") {
+        Some(Value::Array(members)) => {
+            for (index, member) in members.iter().enumerate() {
+                check_string_field(member, &format!("members[{}]", index), "id", &mut errors);
+                check_string_field(member, &format!("members[{}]", index), "key", &mut errors);
+            }
+        }
+        _ => errors.push(FieldError::new("members", "must be an array")),
+    }
+    match governance.get("schemas") {
+        Some(Value::Array(schemas)) => {
+            for (index, schema) in schemas.iter().enumerate() {
+                let prefix = format!("schemas[{}]", index);
+                check_string_field(schema, &prefix, "id", &mut errors);
+                if !schema.get("content").map_or(false, Value::is_object) {
+                    errors.push(FieldError::new(
+                        &format!("{}.content", prefix),
+                        "must be a JSON Schema object",
+                    ));
+                }
+            }
+        }
+        _ => errors.push(FieldError::new("schemas", "must be an array")),
+    }
+    into_result(errors)
+}
+
+// Schema ids, member ids and member keys must be unique, or validation and voting would be
+// ambiguous. Every repetition is reported with the position of its first occurrence.
+pub fn check_governance_duplicates(governance: &Value) -> Result<(), Vec<FieldError>> {
+    let mut errors = Vec::new();
+    for (list, field) in [("schemas", "id"), ("members", "id"), ("members", "key")] {
+        let Some(Value::Array(items)) = governance.get(list) else {
+            continue;
+        };
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        for (index, item) in items.iter().enumerate() {
+            let Some(Value::String(value)) = item.get(field) else {
+                continue;
+            };
+            if let Some(first) = seen.get(value.as_str()) {
+                errors.push(FieldError::new(
+                    &format!("{}[{}].{}", list, index, field),
+                    &format!("duplicates {}[{}].{} {}", list, first, field, value),
+                ));
+            } else {
+                seen.insert(value, index);
+            }
+        }
+    }
+    into_result(errors)
+}
+
+// Checks the form, timestamp and signature of an external request. The verifier receives the
+// signature once it is known to cover the request timestamp.
+pub fn check_external_request(
+    body: &PostEventRequestBody,
+    now: i64,
+    verify: impl Fn(&SignatureRequest) -> Result<(), String>,
+) -> Vec<ValidationCheck> {
+    let form = match (&body.request, body.timestamp, &body.signature) {
+        (EventRequestTypeBody::State(_), Some(_), Some(_)) => Ok(()),
+        (EventRequestTypeBody::Create(_), _, _) => {
+            Err("external requests can only modify the state of a subject".to_owned())
+        }
+        _ => Err("external requests need a timestamp and a signature".to_owned()),
+    };
+    let mut checks = vec![ValidationCheck::new("request", form)];
+    let (Some(timestamp), Some(signature)) = (body.timestamp, &body.signature) else {
+        return checks;
+    };
+    let window = if (now - timestamp).abs() <= TIMESTAMP_WINDOW {
+        Ok(())
+    } else {
+        Err(format!(
+            "differs more than {} seconds from the node clock",
+            TIMESTAMP_WINDOW
+        ))
+    };
+    checks.push(ValidationCheck::new("timestamp", window));
+    let signature = if signature.content.timestamp != timestamp {
+        Err("does not cover the request timestamp".to_owned())
+    } else {
+        verify(signature)
+    };
+    checks.push(ValidationCheck::new("signature", signature));
+    checks
+}
+
+// Runs every check of a subject creation without stopping at the first failure. The governance
+// is given by its properties, None if the node does not know it.
+pub fn check_create_subject(
+    body: &PostSubjectBody,
+    settings: &RestSettings,
+    api_key: &str,
+    governance: Option<&Value>,
+) -> Vec<ValidationCheck> {
+    let describe = |errors: Vec<FieldError>| {
+        errors
+            .iter()
+            .map(|error| format!("{} {}", error.field, error.message))
+            .collect::<Vec<String>>()
+            .join("; ")
+    };
+    let mut checks = vec![
+        ValidationCheck::new(
+            "request",
+            validate_create_request(&body.governance_id, &body.schema_id, &body.payload)
+                .map_err(describe),
+        ),
+        ValidationCheck::new(
+            "namespace",
+            validate_namespace(&body.namespace, &settings.namespace_pattern()).map_err(describe),
+        ),
+    ];
+    let permissions = if !settings.is_schema_allowed(&body.schema_id) {
+        Err(format!("schema {} is not allowed", body.schema_id))
+    } else if !settings.is_governance_allowed(api_key, &body.governance_id) {
+        Err(format!("governance {} is not allowed", body.governance_id))
+    } else {
+        Ok(())
+    };
+    checks.push(ValidationCheck::new("permissions", permissions));
+    // New governances are not created under another one
+    if body.schema_id == GOVERNANCE_SCHEMA_ID {
+        return checks;
+    }
+    let Some(governance) = governance else {
+        checks.push(ValidationCheck::new(
+            "governance",
+            Err(format!("governance {} not found", body.governance_id)),
+        ));
+        return checks;
+    };
+    checks.push(ValidationCheck::new("governance", Ok(())));
+    let Some(schema) = find_schema(governance, &body.schema_id) else {
+        checks.push(ValidationCheck::new(
+            "schema",
+            Err(format!(
+                "schema {} is not in the governance",
+                body.schema_id
+            )),
+        ));
+        return checks;
+    };
+    checks.push(ValidationCheck::new("schema", Ok(())));
+    if let Payload::Json(payload) = &body.payload {
+        checks.push(ValidationCheck::new(
+            "payload",
+            validate_properties(schema, &payload.to_string()),
+        ));
+    }
+    checks
+}
+
+pub fn verify_signature(signature: &SignatureRequest) -> Result<(), String> {
+    signature
+        .content
+        .signer
+        .verify(
+            &signature.content.event_content_hash.digest,
+            signature.signature.clone(),
+        )
+        .map_err(|_| "does not match the signer".to_owned())
+}
+
+fn check_string_field(item: &Value, prefix: &str, field: &str, errors: &mut Vec<FieldError>) {
+    match item.get(field) {
+        Some(Value::String(value)) if !value.is_empty() => {}
+        _ => errors.push(FieldError::new(
+            &format!("{}.{}", prefix, field),
+            "must be a non empty string",
+        )),
+    }
+}
+
+fn into_result(errors: Vec<FieldError>) -> Result<(), Vec<FieldError>> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::settings::RestSettings;
+
+    #[test]
+    fn test_identifiers_in_other_encodings_are_rejected() {
+        let id = "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0";
+        assert!(validate_identifier("id", id).is_ok());
+        // Once parsed, the identifier is written back in the same encoding
+        let identifier = DigestIdentifier::from_str(id).unwrap();
+        assert_eq!(identifier.to_str(), id);
+        assert_eq!(serde_json::to_value(&identifier).unwrap(), id);
+        // Standard base64, with padding and base58 versions of ids
+        for invalid in [
+            "J7BgD3dqZ8vO4WEH7+rpWIH+IhMqaSDnuJ3Jb8K6KvL0",
+            "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0=",
+            "3yZe7d4DMxPknZmBi5L6rDbwvVvBm8FphTeyRvGgYB6Y",
+            "",
+        ] {
+            let errors = validate_identifier("id", invalid).unwrap_err();
+            assert_eq!(errors[0].field, "id");
+        }
+    }
+
+    #[test]
+    fn test_keys_in_other_encodings_are_rejected() {
+        let key = "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w";
+        assert_eq!(validate_key("key", key).unwrap().to_str(), key);
+        for invalid in [
+            "EFXv0jBIr6BtoqFMR7G/JBSuozRc2jZnu5VGUH2gy6+w",
+            "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w=",
+            "",
+        ] {
+            let errors = validate_key("key", invalid).unwrap_err();
+            assert_eq!(errors[0].field, "key");
+        }
+    }
+
+    #[test]
+    fn test_broken_create_reports_every_problem() {
+        let governance = serde_json::json!({
+            "members": [],
+            "schemas": [{
+                "id": "Prueba",
+                "content": {
+                    "type": "object",
+                    "properties": { "temperatura": { "type": "integer" } },
+                    "required": ["temperatura"]
+                }
+            }]
+        });
+        let settings = RestSettings {
+            schema_allowlist: vec!["Prueba".into()],
+            ..Default::default()
+        };
+        let body = |schema_id: &str, namespace: &str, payload: Value| PostSubjectBody {
+            governance_id: "J7BgD3dqZ8vO4WEH7-rpWIH-IhMqaSDnuJ3Jb8K6KvL0".into(),
+            schema_id: schema_id.into(),
+            namespace: namespace.into(),
+            payload: Payload::Json(payload),
+        };
+        let failed = |checks: Vec<ValidationCheck>| -> Vec<String> {
+            checks
+                .into_iter()
+                .filter(|check| !check.passed)
+                .map(|check| check.check)
+                .collect()
+        };
+
+        let valid = body(
+            "Prueba",
+            "namespace1",
+            serde_json::json!({ "temperatura": 10 }),
+        );
+        let checks = check_create_subject(&valid, &settings, "", Some(&governance));
+        assert!(failed(checks).is_empty());
+
+        let broken = body(
+            "Prueba",
+            "name space",
+            serde_json::json!({ "temperatura": "10" }),
+        );
+        let checks = check_create_subject(&broken, &settings, "", Some(&governance));
+        assert_eq!(failed(checks), vec!["namespace", "payload"]);
+
+        let broken = body("Otro", "", serde_json::json!([]));
+        let checks = check_create_subject(&broken, &settings, "", Some(&governance));
+        assert_eq!(failed(checks), vec!["request", "permissions", "schema"]);
+
+        let checks = check_create_subject(&valid, &settings, "", None);
+        assert_eq!(failed(checks), vec!["governance"]);
+    }
+
+    #[test]
+    fn test_every_invalid_field_is_reported() {
+        let errors =
+            validate_create_request("", "", &Payload::Json(serde_json::json!({}))).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                FieldError::new("governance_id", "must not be empty"),
+                FieldError::new("schema_id", "must not be empty"),
+            ]
+        );
+    }
+
+    fn external_request(signature: &str) -> PostEventRequestBody {
+        serde_json::from_value(serde_json::json!({
+            "request": {
+                "State": {
+                    "subject_id": "JKZgYhPjQdWNWWwkac0wSwqLKoOJsT0QimJmj6zjimWc",
+                    "payload": { "Json": { "localizacion": "España", "temperatura": 10 } }
+                }
+            },
+            "timestamp": 1671705355,
+            "signature": {
+                "content": {
+                    "signer": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                    "event_content_hash": "JpxalqMTQcDcLG3dwb8uvcrstJo6pmFEzUwhzi0nGPOA",
+                    "timestamp": 1671705355
+                },
+                "signature": signature
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_valid_external_request() {
+        let body = external_request("SEAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA");
+        let checks = check_external_request(&body, 1671705400, |_| Ok(()));
+        assert!(checks.iter().all(|check| check.passed));
+        assert_eq!(checks.len(), 3);
+    }
+
+    #[test]
+    fn test_external_request_with_bad_signature() {
+        let body = external_request("SEAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA");
+        let checks = check_external_request(&body, 1671705400, verify_signature);
+        let failed: Vec<&str> = checks
+            .iter()
+            .filter(|check| !check.passed)
+            .map(|check| check.check.as_str())
+            .collect();
+        assert_eq!(failed, vec!["signature"]);
+        let checks = check_external_request(&body, 1671709999, |_| Ok(()));
+        assert!(!checks[1].passed);
+    }
+
+    #[test]
+    fn test_governance_payload() {
+        let payload = Payload::Json(serde_json::json!({
+            "members": [{ "id": "Company", "key": "" }],
+            "schemas": "none"
+        }));
+        let errors = validate_governance_payload(&payload).unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["members[0].key", "schemas"]);
+    }
+
+    #[test]
+    fn test_duplicated_governance_ids() {
+        let schema = |id: &str| serde_json::json!({ "id": id, "content": { "type": "object" } });
+        let governance = serde_json::json!({
+            "members": [
+                { "id": "Company1", "key": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w" },
+                { "id": "Company2", "key": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w" }
+            ],
+            "schemas": [schema("prueba"), schema("other"), schema("prueba")]
+        });
+        assert!(validate_governance(&governance).is_ok());
+        let errors = check_governance_duplicates(&governance).unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["schemas[2].id", "members[1].key"]);
+        assert_eq!(errors[0].message, "duplicates schemas[0].id prueba");
+        let governance = serde_json::json!({
+            "members": [{ "id": "Company1", "key": "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w" }],
+            "schemas": [schema("prueba"), schema("other")]
+        });
+        assert!(check_governance_duplicates(&governance).is_ok());
+    }
+
+    #[test]
+    fn test_valid_namespace() {
+        let pattern = RestSettings::default().namespace_pattern();
+        assert!(validate_namespace("namespace1", &pattern).is_ok());
+    }
+
+    #[test]
+    fn test_namespace_with_illegal_characters() {
+        let pattern = RestSettings::default().namespace_pattern();
+        for namespace in ["name space", "name\nspace", "name.space", "name\u{0}space"] {
+            let errors = validate_namespace(namespace, &pattern).unwrap_err();
+            assert_eq!(errors[0].field, "namespace");
+        }
+    }
+
+    #[test]
+    fn test_default_allowed_namespaces() {
+        let pattern = RestSettings::default().namespace_pattern();
+        for namespace in ["", "Sede-Madrid_1", "company/sensors", "a/b-c/d_e"] {
+            assert!(validate_namespace(namespace, &pattern).is_ok());
+        }
+    }
+}
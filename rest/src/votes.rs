@@ -0,0 +1,141 @@
+use std::{collections::HashMap, sync::Arc};
+
+use commons::models::approval_signature::Acceptance;
+use tokio::sync::Mutex;
+
+use super::{bodys::PutVoteBody, identity::NodeIdentity};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VoteCheck {
+    New,
+    Repeated,
+    Conflict,
+}
+
+// Compares a vote with the one the node recorded for the request, as (signer, acceptance).
+// Votes cast by the node itself count, whether they came through this API or not.
+pub fn check_vote(
+    approvals: impl IntoIterator<Item = (String, Acceptance)>,
+    identity: &NodeIdentity,
+    vote: &PutVoteBody,
+) -> VoteCheck {
+    let recorded = approvals
+        .into_iter()
+        .find(|(signer, _)| identity.owns(signer));
+    match (recorded, vote) {
+        (None, _) => VoteCheck::New,
+        (Some((_, Acceptance::Accept)), PutVoteBody::Accept)
+        | (Some((_, Acceptance::Reject)), PutVoteBody::Reject) => VoteCheck::Repeated,
+        (Some(_), _) => VoteCheck::Conflict,
+    }
+}
+
+// Votes on their way to the node, which only records a vote once it has processed it. It is
+// shared by every request handler, so it uses an async aware lock that is never held across
+// calls to the node. A vote is released as soon as the node answers it, so only the votes in
+// flight are kept.
+#[derive(Debug, Clone, Default)]
+pub struct VotesInFlight {
+    votes: Arc<Mutex<HashMap<String, PutVoteBody>>>,
+}
+
+impl VotesInFlight {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Checks the vote against the one in flight for the request and, if there is none, records
+    // it in the same step so concurrent votes for a request can not both be new
+    pub async fn claim(&self, request_id: &str, vote: &PutVoteBody) -> VoteCheck {
+        let mut votes = self.votes.lock().await;
+        match votes.get(request_id) {
+            None => {
+                votes.insert(request_id.to_owned(), vote.clone());
+                VoteCheck::New
+            }
+            Some(previous) if previous == vote => VoteCheck::Repeated,
+            Some(_) => VoteCheck::Conflict,
+        }
+    }
+
+    // Forgets a claimed vote once the node has answered it
+    pub async fn release(&self, request_id: &str) {
+        self.votes.lock().await.remove(request_id);
+    }
+
+    pub async fn count(&self) -> usize {
+        self.votes.lock().await.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_repeated_vote() {
+        let identity = NodeIdentity::new(vec!["node_key".into()]);
+        let approvals = vec![
+            ("other_key".to_owned(), Acceptance::Reject),
+            ("node_key".to_owned(), Acceptance::Accept),
+        ];
+        assert_eq!(
+            check_vote(approvals, &identity, &PutVoteBody::Accept),
+            VoteCheck::Repeated
+        );
+    }
+
+    #[test]
+    fn test_conflicting_vote() {
+        let identity = NodeIdentity::new(vec!["node_key".into()]);
+        let approvals = vec![("node_key".to_owned(), Acceptance::Accept)];
+        assert_eq!(
+            check_vote(approvals, &identity, &PutVoteBody::Reject),
+            VoteCheck::Conflict
+        );
+        // The votes of other approvers do not count
+        let approvals = vec![("other_key".to_owned(), Acceptance::Accept)];
+        assert_eq!(
+            check_vote(approvals, &identity, &PutVoteBody::Reject),
+            VoteCheck::New
+        );
+    }
+
+    #[test]
+    fn test_concurrent_votes() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let in_flight = VotesInFlight::new();
+            let mut tasks = Vec::new();
+            for i in 0..500 {
+                let in_flight = in_flight.clone();
+                tasks.push(tokio::spawn(async move {
+                    let request_id = format!("request{}", i % 10);
+                    let vote = if i % 2 == 0 {
+                        PutVoteBody::Accept
+                    } else {
+                        PutVoteBody::Reject
+                    };
+                    let check = in_flight.claim(&request_id, &vote).await;
+                    if i % 7 == 0 && check == VoteCheck::New {
+                        in_flight.release(&request_id).await;
+                        in_flight.claim(&request_id, &vote).await
+                    } else {
+                        check
+                    }
+                }));
+            }
+            let checks = futures::future::join_all(tasks).await;
+            let new = checks
+                .into_iter()
+                .filter(|check| *check.as_ref().unwrap() == VoteCheck::New)
+                .count();
+            // Releasing and claiming again keeps a single vote per request
+            assert_eq!(new, 10);
+            for i in 0..10 {
+                in_flight.release(&format!("request{}", i)).await;
+            }
+            assert_eq!(in_flight.count().await, 0);
+        });
+    }
+}
@@ -16,6 +16,9 @@ use commons::{
     identifier::derive::{digest::DigestDerivator, KeyDerivator},
 };
 use rest::bodys::{PostEventBody, PostGovernanceBody, PostSubjectBody};
+use rest::settings::RestSettings;
+use rest::identity::NodeIdentity;
+use rest::startup::Startup;
 use rest::handlers::{
     __path_get_all_subjects_handler, __path_get_event_handler, __path_get_event_properties_handler,
     __path_get_events_of_subject_handler, __path_get_signatures_handler,
@@ -180,15 +183,22 @@ impl NodeBuilderAPI {
             .and(warp::path::tail())
             .and(warp::any().map(move || config.clone()))
             .and_then(serve_swagger);
-        let api_rest = warp::serve(
-            api_doc
-                .or(swagger_ui)
-                .or(rest::routes::routes(taple.get_api(), None)),
+        // The node is already started
+        let startup = Startup::new();
+        startup.set_ready();
+        let routes = rest::routes::routes(
+            taple.get_api(),
+            None,
+            RestSettings::default(),
+            NodeIdentity::new(vec![taple.controller_id().unwrap().to_string()]),
+            startup,
         )
-        .bind_with_graceful_shutdown(http_addr, async move {
-            stream.recv().await;
-        })
-        .1;
+        .unwrap();
+        let api_rest = warp::serve(api_doc.or(swagger_ui).or(routes))
+            .bind_with_graceful_shutdown(http_addr, async move {
+                stream.recv().await;
+            })
+            .1;
         tokio::spawn(api_rest);
         taple.get_api()
     }
@@ -1,5 +1,5 @@
 mod common;
-use std::time::Duration;
+use std::{io::Read, time::Duration};
 
 use common::*;
 use commons::{
@@ -8,6 +8,7 @@ use commons::{
 };
 use core::ApiModuleInterface;
 use futures::FutureExt;
+use rest::bodys::PostEventRequestBody;
 use ureq::Agent;
 
 #[test]
@@ -62,8 +63,17 @@ fn event_creation() {
             .await;
         tokio::time::sleep(Duration::from_secs(1)).await;
 
+        // Both nodes answer the ping of the readiness check, without the API KEY
+        for port in [3001, 3002] {
+            let response = ureq::get(&format!("http://localhost:{}/ready", port))
+                .call()
+                .unwrap();
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.into_string().unwrap(), "{\"status\":\"ready\"}");
+        }
+
         // Governance is created
-        let result: RequestData = ureq::post(&format!("http://localhost:3001/api/requests"))
+        let response = ureq::post(&format!("http://localhost:3001/api/requests"))
             .set("X-API-KEY", "apikeyexamplevalue123")
             .send_json(serde_json::json!({
                 "request": {
@@ -77,11 +87,14 @@ fn event_creation() {
                     }
                 }
             }))
-            .unwrap()
-            .into_json()
             .unwrap();
+        // Creations are never voted
+        assert_eq!(response.status(), 201);
+        let location = response.header("Location").unwrap().to_owned();
+        let result: RequestData = response.into_json().unwrap();
         tokio::time::sleep(Duration::from_secs(1)).await;
         let governance_id = result.subject_id.unwrap();
+        assert_eq!(location, format!("/api/subjects/{}", governance_id));
         let governance_subj_data: SubjectData = ureq::get(&format!(
             "http://localhost:3001/api/subjects/{}",
             governance_id
@@ -92,6 +105,26 @@ fn event_creation() {
         .unwrap();
         assert_eq!(governance_id, governance_subj_data.subject_id.to_str());
 
+        // Subjects are not created in a namespace that does not match the pattern
+        let error = ureq::post("http://localhost:3001/api/requests")
+            .set("X-API-KEY", "apikeyexamplevalue123")
+            .send_json(serde_json::json!({
+                "request": {
+                    "Create": {
+                        "governance_id": governance_id,
+                        "namespace": "namespace\u{1}",
+                        "schema_id": "prueba",
+                        "payload": {
+                            "Json": {
+                                "a": "69"
+                            }
+                        }
+                    }
+                }
+            }))
+            .unwrap_err();
+        assert!(matches!(error, ureq::Error::Status(400, _)));
+
         // A subject for governance is created
         let result: RequestData = ureq::post(&format!("http://localhost:3001/api/requests"))
             .set("X-API-KEY", "apikeyexamplevalue123")
@@ -112,7 +145,7 @@ fn event_creation() {
             .unwrap()
             .into_json()
             .unwrap();
-        let _subject_id = result.subject_id.unwrap();
+        let first_subject_id = result.subject_id.unwrap();
         tokio::time::sleep(Duration::from_secs(1)).await;
         // An event is created for the creation of a subject with the schema established in governance
         let result: RequestData = ureq::post(&format!("http://localhost:3001/api/requests"))
@@ -137,8 +170,98 @@ fn event_creation() {
         let subject_id = result.subject_id.unwrap();
         tokio::time::sleep(Duration::from_secs(1)).await;
 
+        // The total counts every subject of the namespace, not just the page, when requested
+        let response = ureq::get(
+            "http://localhost:3001/api/subjects?namespace=namespace1&quantity=1&count=true",
+        )
+        .call()
+        .unwrap();
+        assert_eq!(response.header("X-Total-Count"), Some("2"));
+        let page: Vec<SubjectData> = response.into_json().unwrap();
+        assert_eq!(page.len(), 1);
+        let page: serde_json::Value = ureq::get(
+            "http://localhost:3001/api/subjects?namespace=namespace1&quantity=1&count=true&meta=true",
+        )
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+        assert_eq!(page["meta"]["total"], 2);
+        let response = ureq::get(
+            "http://localhost:3001/api/subjects?namespace=namespace1&quantity=1",
+        )
+        .call()
+        .unwrap();
+        assert!(response.header("X-Total-Count").is_none());
+        // Filters apply before the page is taken
+        let page: Vec<SubjectData> =
+            ureq::get("http://localhost:3001/api/subjects?schema_id=governance&owner=")
+                .call()
+                .unwrap()
+                .into_json()
+                .unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].subject_id.to_str(), governance_id);
+        // The streamed listing is shaped item by item
+        let page: serde_json::Value = ureq::get(
+            "http://localhost:3001/api/subjects?namespace=namespace1&truncate=3&fields=/properties,/truncated&meta=true",
+        )
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+        assert_eq!(
+            page["data"],
+            serde_json::json!([
+                { "properties": "{\"a…", "truncated": true },
+                { "properties": "{\"a…", "truncated": true }
+            ])
+        );
+        assert_eq!(page["meta"]["version"], env!("CARGO_PKG_VERSION"));
+        // and answered whole in other content types
+        let response = ureq::get("http://localhost:3001/api/subjects?namespace=namespace1")
+            .set("Accept", "application/cbor")
+            .call()
+            .unwrap();
+        assert_eq!(response.content_type(), "application/cbor");
+        let mut body = Vec::new();
+        response.into_reader().read_to_end(&mut body).unwrap();
+        let page: Vec<SubjectData> = serde_cbor::from_slice(&body).unwrap();
+        assert_eq!(page.len(), 2);
+        let error = ureq::get("http://localhost:3001/api/subjects")
+            .set("Accept", "text/html")
+            .call()
+            .unwrap_err();
+        assert!(matches!(error, ureq::Error::Status(406, _)));
+
+        // The subject is not sent again while it does not change
+        let subject_url = format!("http://localhost:3001/api/subjects/{}", subject_id);
+        let response = ureq::get(&subject_url).call().unwrap();
+        assert_eq!(response.status(), 200);
+        let etag = response.header("ETag").unwrap().to_owned();
+        let response = ureq::get(&subject_url)
+            .set("If-None-Match", &etag)
+            .call()
+            .unwrap();
+        assert_eq!(response.status(), 304);
+        // The ETag of the JSON representation does not hold for the others
+        let response = ureq::get(&subject_url)
+            .set("Accept", "application/cbor")
+            .set("If-None-Match", &etag)
+            .call()
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.content_type(), "application/cbor");
+        assert_eq!(response.header("Vary"), Some("accept"));
+        assert_ne!(response.header("ETag"), Some(etag.as_str()));
+        let response = ureq::get(&format!("{}?include_event_count=true", subject_url))
+            .set("If-None-Match", &etag)
+            .call()
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
         // An event is created for the subject change
-        let _result: RequestData = ureq::post(&format!("http://localhost:3001/api/requests"))
+        let response = ureq::post(&format!("http://localhost:3001/api/requests"))
             .set("X-API-KEY", "apikeyexamplevalue123")
             .send_json(serde_json::json!({
                 "request": {
@@ -152,12 +275,21 @@ fn event_creation() {
                     }
                 }
             }))
-            .unwrap()
-            .into_json()
             .unwrap();
+        // Events go through approval
+        assert_eq!(response.status(), 202);
+        assert!(response.header("Location").is_none());
+        let _result: RequestData = response.into_json().unwrap();
 
         // The event that has been created is checked
         tokio::time::sleep(Duration::from_secs(1)).await;
+        // The new event makes the ETag stale
+        let response = ureq::get(&subject_url)
+            .set("If-None-Match", &etag)
+            .call()
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_ne!(response.header("ETag"), Some(etag.as_str()));
         let result2: Event = ureq::get(&format!(
             "http://localhost:3002/api/subjects/{}/events/1",
             subject_id
@@ -183,7 +315,7 @@ fn event_creation() {
         assert_eq!(result.unwrap().len(), 2);
 
         // An event is created for the subject change
-        let _result: RequestData = ureq::post(&format!("http://localhost:3001/api/requests"))
+        let last_request: RequestData = ureq::post(&format!("http://localhost:3001/api/requests"))
             .set("X-API-KEY", "apikeyexamplevalue123")
             .send_json(serde_json::json!({
                 "request": {
@@ -222,6 +354,242 @@ fn event_creation() {
             .await;
         assert!(result.is_ok());
         assert_eq!(result.unwrap().len(), 2);
+
+        // The event count of the subject matches its events
+        let events: Vec<Event> = ureq::get(&format!(
+            "http://localhost:3001/api/subjects/{}/events",
+            subject_id
+        ))
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+        let subject: serde_json::Value = ureq::get(&format!(
+            "http://localhost:3001/api/subjects/{}?include_event_count=true",
+            subject_id
+        ))
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+        assert_eq!(subject["event_count"], events.len());
+        let subject: serde_json::Value = ureq::get(&format!(
+            "http://localhost:3001/api/subjects/{}",
+            subject_id
+        ))
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+        assert!(subject.get("event_count").is_none());
+
+        // The state inlined with the last event is the current state of the subject
+        let events: Vec<serde_json::Value> = ureq::get(&format!(
+            "http://localhost:3001/api/subjects/{}/events?with_state=true",
+            subject_id
+        ))
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+        let subject_data: SubjectData = ureq::get(&format!(
+            "http://localhost:3001/api/subjects/{}",
+            subject_id
+        ))
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+        let properties: serde_json::Value = serde_json::from_str(&subject_data.properties).unwrap();
+        assert_eq!(events.last().unwrap()["properties"], properties);
+        assert_eq!(properties, serde_json::json!({"a": "71"}));
+
+        // The subject is found by the value of its properties
+        let found: Vec<SubjectData> =
+            ureq::get("http://localhost:3001/api/subjects/search?key=a&op=gte&value=71")
+                .call()
+                .unwrap()
+                .into_json()
+                .unwrap();
+        assert!(found
+            .iter()
+            .any(|subject| subject.subject_id == subject_data.subject_id));
+        let found: Vec<SubjectData> =
+            ureq::get("http://localhost:3001/api/subjects/search?key=a&op=lt&value=71")
+                .call()
+                .unwrap()
+                .into_json()
+                .unwrap();
+        assert!(found
+            .iter()
+            .all(|subject| subject.subject_id != subject_data.subject_id));
+
+        // The last update of the subject is its latest event
+        let last_updated: serde_json::Value = ureq::get(&format!(
+            "http://localhost:3001/api/subjects/{}/last-updated",
+            subject_id
+        ))
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+        let latest = &events.last().unwrap()["event_content"];
+        assert_eq!(last_updated["sn"], latest["sn"]);
+        assert_eq!(
+            last_updated["timestamp"],
+            latest["event_request"]["timestamp"]
+        );
+
+        // Paging past the last event is told apart from an empty listing
+        let response = ureq::get(&format!(
+            "http://localhost:3001/api/subjects/{}/events?from=100",
+            subject_id
+        ))
+        .call()
+        .unwrap();
+        assert_eq!(response.header("x-out-of-range"), Some("true"));
+        let page: Vec<Event> = response.into_json().unwrap();
+        assert!(page.is_empty());
+        let response = ureq::get(&format!(
+            "http://localhost:3001/api/subjects/{}/events?from=1",
+            subject_id
+        ))
+        .call()
+        .unwrap();
+        assert!(response.header("x-out-of-range").is_none());
+
+        // The tail of the subject are its last events, in ascending order
+        let tail: Vec<Event> = ureq::get(&format!(
+            "http://localhost:3001/api/subjects/{}/events?tail=2",
+            subject_id
+        ))
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+        let sns: Vec<u64> = tail.iter().map(|event| event.event_content.sn).collect();
+        assert_eq!(sns, vec![1, 2]);
+        let error = ureq::get(&format!(
+            "http://localhost:3001/api/subjects/{}/events?tail=2&from=0",
+            subject_id
+        ))
+        .call()
+        .unwrap_err();
+        assert!(matches!(error, ureq::Error::Status(400, _)));
+
+        // Newest first, with from counting downward
+        for (query, expected) in [
+            ("order=desc", vec![2, 1, 0]),
+            ("order=desc&from=1", vec![1, 0]),
+            ("order=desc&quantity=2", vec![2, 1]),
+            ("order=desc&tail=2", vec![2, 1]),
+        ] {
+            let events: Vec<Event> = ureq::get(&format!(
+                "http://localhost:3001/api/subjects/{}/events?{}",
+                subject_id, query
+            ))
+            .call()
+            .unwrap()
+            .into_json()
+            .unwrap();
+            let sns: Vec<u64> = events.iter().map(|event| event.event_content.sn).collect();
+            assert_eq!(sns, expected, "{}", query);
+        }
+        let error = ureq::get(&format!(
+            "http://localhost:3001/api/subjects/{}/events?order=newest",
+            subject_id
+        ))
+        .call()
+        .unwrap_err();
+        assert!(matches!(error, ureq::Error::Status(400, _)));
+
+        // Every tracked subject is in the snapshot once, at its current sn
+        let snapshot: Vec<serde_json::Value> = ureq::get("http://localhost:3001/api/snapshot")
+            .call()
+            .unwrap()
+            .into_json()
+            .unwrap();
+        assert_eq!(snapshot.len(), 3);
+        for (id, sn) in [
+            (&governance_id, 0),
+            (&first_subject_id, 0),
+            (&subject_id, 2),
+        ] {
+            let states: Vec<&serde_json::Value> = snapshot
+                .iter()
+                .filter(|state| state["subject_id"] == id.as_str())
+                .collect();
+            assert_eq!(states.len(), 1);
+            assert_eq!(states[0]["sn"], sn);
+        }
+        let snapshot: Vec<serde_json::Value> = ureq::get(&format!(
+            "http://localhost:3001/api/snapshot?schema_id=prueba&governance_id={}",
+            governance_id
+        ))
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+        assert_eq!(snapshot.len(), 2);
+
+        // The state as of the request of the event 1 does not include the later events
+        let event: Event = ureq::get(&format!(
+            "http://localhost:3001/api/subjects/{}/events/1",
+            subject_id
+        ))
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+        let as_of: serde_json::Value = ureq::get(&format!(
+            "http://localhost:3001/api/subjects/{}/as-of?ts={}",
+            subject_id, event.event_content.event_request.timestamp
+        ))
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+        assert_eq!(as_of["sn"], 1);
+        assert_eq!(as_of["properties"], serde_json::json!({ "a": "70" }));
+        let error = ureq::get(&format!(
+            "http://localhost:3001/api/subjects/{}/as-of?ts=0",
+            subject_id
+        ))
+        .call()
+        .unwrap_err();
+        assert!(matches!(error, ureq::Error::Status(404, _)));
+
+        // The payload of a request can be sent again in a new one
+        let payload: serde_json::Value = ureq::get(&format!(
+            "http://localhost:3001/api/requests/{}/payload",
+            last_request.request_id
+        ))
+        .query("subject_id", &subject_id)
+        .query("sn", "2")
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+        assert_eq!(payload, serde_json::json!({ "Json": { "a": "71" } }));
+        let body: PostEventRequestBody = serde_json::from_value(serde_json::json!({
+            "request": {
+                "State": {
+                    "subject_id": subject_id,
+                    "payload": payload
+                }
+            }
+        }))
+        .unwrap();
+        let _result: RequestData = ureq::post(&format!("http://localhost:3001/api/requests"))
+            .set("X-API-KEY", "apikeyexamplevalue123")
+            .send_json(serde_json::to_value(body).unwrap())
+            .unwrap()
+            .into_json()
+            .unwrap();
+        let error = ureq::get("http://localhost:3001/api/requests/JhEnzFVF1a-u-rH34cix2A_OXgcfesM6HGOyk7wdrGHk/payload")
+            .call()
+            .unwrap_err();
+        assert!(matches!(error, ureq::Error::Status(404, _)));
         tokio::time::sleep(Duration::from_millis(100)).await;
         let result = node.shutdown().await;
         assert!(result.is_ok());
@@ -291,6 +659,9 @@ fn add_new_member_to_governance() {
         .unwrap()
         .into_json()
         .unwrap();
+        // The changes of the new version need the votes of both members
+        let mut governance = governance_two();
+        governance["policies"][1]["approval"]["quorum"] = serde_json::json!(1.0);
         let result: RequestData = ureq::post(&format!("http://localhost:3001/api/requests"))
             .set("X-API-KEY", "apikeyexamplevalue123")
             .send_json(serde_json::json!({
@@ -298,7 +669,7 @@ fn add_new_member_to_governance() {
                     "State": {
                         "subject_id": governance_id,
                         "payload": {
-                            "Json": governance_two()
+                            "Json": governance
                         }
                     }
                 }
@@ -308,15 +679,29 @@ fn add_new_member_to_governance() {
             .unwrap();
         tokio::time::sleep(Duration::from_secs(1)).await;
         let request_id = result.request_id;
-        let _: () = ureq::put(&format!(
+        // The count of pending requests is the length of their list
+        let pending: Vec<serde_json::Value> = ureq::get("http://localhost:3001/api/approvals")
+            .call()
+            .unwrap()
+            .into_json()
+            .unwrap();
+        let count: serde_json::Value = ureq::get("http://localhost:3001/api/approvals/count")
+            .call()
+            .unwrap()
+            .into_json()
+            .unwrap();
+        assert!(!pending.is_empty());
+        assert_eq!(count["count"], pending.len());
+        // The vote reaches the handler through /api/approvals/{id}
+        let response = ureq::put(&format!(
             "http://localhost:3001/api/approvals/{}",
             request_id
         ))
         .set("X-API-KEY", "apikeyexamplevalue123")
         .send_json(serde_json::json!({"approvalType": "Accept"}))
-        .unwrap()
-        .into_json()
         .unwrap();
+        assert_eq!(response.status(), 200);
+        let _: () = response.into_json().unwrap();
         tokio::time::sleep(Duration::from_secs(1)).await;
         let _governance_subj_data: SubjectData = ureq::get(&format!(
             "http://localhost:3002/api/subjects/{}",
@@ -326,5 +711,115 @@ fn add_new_member_to_governance() {
         .unwrap()
         .into_json()
         .unwrap();
+        // The approved change is a new version of the governance
+        let versions: Vec<serde_json::Value> = ureq::get(&format!(
+            "http://localhost:3001/api/governances/{}/versions",
+            governance_id
+        ))
+        .call()
+        .unwrap()
+        .into_json()
+        .unwrap();
+        let numbers: Vec<u64> = versions
+            .iter()
+            .map(|version| version["governance_version"].as_u64().unwrap())
+            .collect();
+        assert_eq!(numbers, vec![0, 1]);
+        assert_eq!(versions[1]["event"]["event_content"]["sn"], 1);
+
+        // Each member votes a new change in turn
+        governance["members"][1]["description"] = serde_json::json!("c");
+        let result: RequestData = ureq::post(&format!("http://localhost:3001/api/requests"))
+            .set("X-API-KEY", "apikeyexamplevalue123")
+            .send_json(serde_json::json!({
+                "request": {
+                    "State": {
+                        "subject_id": governance_id,
+                        "payload": {
+                            "Json": governance
+                        }
+                    }
+                }
+            }))
+            .unwrap()
+            .into_json()
+            .unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let request_id = result.request_id;
+        let timeline_url = format!("http://localhost:3001/api/requests/{}/timeline", request_id);
+        ureq::put(&format!(
+            "http://localhost:3001/api/approvals/{}",
+            request_id
+        ))
+        .set("X-API-KEY", "apikeyexamplevalue123")
+        .send_json(serde_json::json!({"approvalType": "Accept"}))
+        .unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let timeline: serde_json::Value = ureq::get(&timeline_url)
+            .set("X-API-KEY", "apikeyexamplevalue123")
+            .call()
+            .unwrap()
+            .into_json()
+            .unwrap();
+        assert_eq!(timeline["resolution"], "Pending");
+        assert_eq!(
+            timeline["votes"][0]["signer"],
+            "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w"
+        );
+        // The vote recorded by the node can be repeated but not changed. A batch answers every
+        // vote, even when some of them fail
+        let results: Vec<serde_json::Value> = ureq::put("http://localhost:3001/api/approvals")
+            .set("X-API-KEY", "apikeyexamplevalue123")
+            .send_json(serde_json::json!({
+                "votes": [
+                    { "request_id": request_id, "vote": "Accept" },
+                    { "request_id": request_id, "vote": "Reject" },
+                    { "request_id": "J1", "vote": "Accept" }
+                ]
+            }))
+            .unwrap()
+            .into_json()
+            .unwrap();
+        let statuses: Vec<&serde_json::Value> =
+            results.iter().map(|result| &result["status"]).collect();
+        assert_eq!(statuses, vec![200, 409, 400]);
+        assert!(results[0].get("error").is_none());
+        assert!(results[1]["error"].is_string());
+        ureq::put(&format!(
+            "http://localhost:3002/api/approvals/{}",
+            request_id
+        ))
+        .set("X-API-KEY", "apikeyexamplevalue123")
+        .send_json(serde_json::json!({"approvalType": "Accept"}))
+        .unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let timeline: serde_json::Value = ureq::get(&timeline_url)
+            .set("X-API-KEY", "apikeyexamplevalue123")
+            .query("subject_id", &governance_id)
+            .query("sn", "2")
+            .call()
+            .unwrap()
+            .into_json()
+            .unwrap();
+        assert_eq!(timeline["resolution"], "Approved");
+        let signers: Vec<&str> = timeline["votes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|vote| vote["signer"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            signers,
+            vec![
+                "EFXv0jBIr6BtoqFMR7G_JBSuozRc2jZnu5VGUH2gy6-w",
+                "ECQnl-h1vEWmu-ZlPuweR3N1x6SUImyVdPrCLmnJJMyU"
+            ]
+        );
+        // Once resolved the request is only found through its event
+        let error = ureq::get(&timeline_url)
+            .set("X-API-KEY", "apikeyexamplevalue123")
+            .call()
+            .unwrap_err();
+        assert!(matches!(error, ureq::Error::Status(404, _)));
     });
 }